@@ -17,12 +17,53 @@ import {
 } from '@/components/ui/select';
 import { AnimatePresence, motion } from 'framer-motion';
 
+// Mirrors trim_it_core::trim::TrimResult
+interface TrimResult {
+  output_path: string;
+  outputs: string[];
+  output_dir: string;
+  duration: number;
+  size_bytes: number;
+  codec: string | null;
+  elapsed: number;
+}
+
+// Mirrors trim_it_core::error::TrimError
+interface TrimError {
+  code:
+    | 'FfmpegMissing'
+    | 'YtDlpMissing'
+    | 'DownloadFailed'
+    | 'DrmProtected'
+    | 'EncodeFailed'
+    | 'InvalidRange'
+    | 'InvalidOptions'
+    | 'UnsupportedFeature'
+    | 'ConfirmationRequired'
+    | 'NotEnoughSpace'
+    | 'FileNotFound'
+    | 'ProbeFailed'
+    | 'TempDirUnavailable'
+    | 'DuplicateClip'
+    | 'OutputExists'
+    | 'Cancelled'
+    | 'Other';
+  message: string;
+}
+
+const errorMessage = (error: unknown) =>
+  typeof error === 'object' && error !== null && 'message' in error
+    ? (error as TrimError).message
+    : String(error);
+
 export default function HomePage() {
   const [videoSource, setVideoSource] = useState<string>('');
   const [startTime, setStartTime] = useState<string>('00:00:00');
   const [endTime, setEndTime] = useState<string>('00:00:10');
   const [ratio, setRatio] = useState<string>('Original');
   const [message, setMessage] = useState<string>('');
+  // The last export, for the reveal and open buttons
+  const [lastOutput, setLastOutput] = useState<string | null>(null);
   const [isLoading, setIsLoading] = useState<boolean>(false);
   const [ffmpegStatus, setFfmpegStatus] =
     useState<string>('Checking FFmpeg...');
@@ -76,21 +117,39 @@ export default function HomePage() {
 
     setIsLoading(true);
     setMessage('Processing video...');
+    setLastOutput(null);
     try {
-      const result: string = await invoke('trim_video', {
+      const result: TrimResult = await invoke('trim_video', {
         videoSource,
         startTime,
         endTime,
         ratio,
       });
-      setMessage(result);
-    } catch (error: any) {
-      setMessage(`Error: ${error}`);
+      setMessage(
+        result.outputs.length > 1
+          ? `Video trimmed successfully! Saved in ${result.outputs.length} parts to: ${result.output_dir}`
+          : `Video trimmed successfully! Saved to: ${result.output_path}`,
+      );
+      setLastOutput(result.outputs[0] ?? result.output_path);
+    } catch (error) {
+      setMessage(`Error: ${errorMessage(error)}`);
     } finally {
       setIsLoading(false);
     }
   }, [videoSource, startTime, endTime, ratio]);
 
+  const handleOutputAction = useCallback(
+    async (command: 'reveal_in_file_manager' | 'open_output') => {
+      if (!lastOutput) return;
+      try {
+        await invoke(command, { path: lastOutput });
+      } catch (error) {
+        setMessage(`Error: ${errorMessage(error)}`);
+      }
+    },
+    [lastOutput],
+  );
+
   const isUiDisabled = !isFfmpegReady || isLoading;
 
   return (
@@ -243,6 +302,25 @@ export default function HomePage() {
             {message}
           </motion.p>
         )}
+
+        {lastOutput && !isLoading && (
+          <div className="mt-4 flex justify-center gap-2">
+            <Button
+              onClick={() => handleOutputAction('reveal_in_file_manager')}
+              variant="outline"
+              className="border-gray-700"
+            >
+              Show in Folder
+            </Button>
+            <Button
+              onClick={() => handleOutputAction('open_output')}
+              variant="outline"
+              className="border-gray-700"
+            >
+              Open
+            </Button>
+          </div>
+        )}
       </motion.div>
     </main>
   );
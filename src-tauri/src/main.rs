@@ -1,15 +1,41 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::path::{Path, PathBuf};
-use tauri::{Window, Emitter};
-use url::Url;
-use chrono;
-use tempfile;
-use tokio::process::Command;
+use trim_it_core::{
+    analysis, attachments, cache, cancel, capabilities, captions, chapters, chat, concat,
+    diagnostics, download, encryption, error, estimate, ffmpeg, frames, gpu, history, job, joblog,
+    kiosk, markers, metered, mirror, multicam, plugins, politeness, power, presets, privacy, probe,
+    profiles, range, remux, replay, retention, reveal, scripting, separation, settings, sync,
+    telemetry, timecode, tools, translation, trim, volume, ytdlp,
+};
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::{Emitter, Manager, State, Window};
 
 #[tauri::command]
-async fn ensure_ffmpeg_is_ready(window: Window) -> Result<(), String> {
-    let mut test_command = ffmpeg_sidecar::command::FfmpegCommand::new();
+async fn ensure_ffmpeg_is_ready(
+    window: Window,
+    settings: State<'_, settings::SettingsState>,
+) -> Result<(), error::TrimError> {
+    if ffmpeg::configured_ffmpeg_path().is_none() && ffmpeg::managed_ffmpeg_path().is_none() {
+        let _ = window.emit("ffmpeg_status", "Downloading FFmpeg...");
+        let client = download::build_client(settings.current().proxy.as_ref())?;
+        let progress_window = window.clone();
+        let provisioned = ffmpeg::provision_managed_ffmpeg(&client, move |downloaded_bytes, total_bytes| {
+            let _ = progress_window.emit(
+                "ffmpeg_download_progress",
+                download::DownloadProgress { downloaded_bytes, total_bytes },
+            );
+        })
+        .await;
+        // A failed download isn't fatal as long as a system FFmpeg works
+        if let Err(e) = provisioned {
+            log::warn!("Managed FFmpeg download failed: {}", privacy::scrub(&e));
+            let _ = window.emit("ffmpeg_status", "FFmpeg download failed, trying system FFmpeg...");
+        }
+    }
+
+    let mut test_command = ffmpeg::new_command();
     
     let spawn_result = test_command
         .arg("-f")
@@ -26,7 +52,7 @@ async fn ensure_ffmpeg_is_ready(window: Window) -> Result<(), String> {
     match spawn_result {
         Ok(mut child) => {
             let success = child.iter()
-                .map_err(|e| e.to_string())?
+                .map_err(|e| error::ErrorCode::FfmpegMissing.error(e.to_string()))?
                 .any(|event| matches!(event, ffmpeg_sidecar::event::FfmpegEvent::Done));
             
             if success {
@@ -34,328 +60,1333 @@ async fn ensure_ffmpeg_is_ready(window: Window) -> Result<(), String> {
                 Ok(())
             } else {
                 let _ = window.emit("ffmpeg_status", "FFmpeg not working properly.");
-                Err("FFmpeg did not complete successfully.".to_string())
+                Err(error::ErrorCode::FfmpegMissing.error("FFmpeg did not complete successfully."))
             }
         }
         Err(e) => {
             let _ = window.emit("ffmpeg_status", "FFmpeg not found. Please install FFmpeg manually.");
-            Err(format!("FFmpeg is not installed or failed to spawn: {}. Please ensure it's in your PATH.", e))
+            Err(error::ErrorCode::FfmpegMissing.error(format!(
+                "FFmpeg could not be downloaded and no system FFmpeg was found: {}. Please ensure it's in your PATH.",
+                e
+            )))
         }
     }
 }
 
-// Optimized function to download only the required segment from YouTube
-async fn download_youtube_video_segment(
-    url: &str, 
-    output_dir: &Path, 
-    start_time: &str, 
-    end_time: &str
-) -> Result<PathBuf, String> {
-    let output_template = output_dir.join("video.%(ext)s");
+#[tauri::command]
+async fn trim_video(
+    window: Window,
+    video_source: String,
+    start_time: String,
+    end_time: String,
+    ratio: String,
+    options: Option<job::TrimOptions>,
+    preset: Option<String>,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    presets: State<'_, presets::PresetState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<trim::TrimResult, error::TrimError> {
+    let mut spec = job::JobSpec {
+        version: job::JOB_SPEC_VERSION,
+        source: video_source,
+        start_time,
+        end_time,
+        ratio,
+        options: options.unwrap_or_default(),
+    };
+    // A preset's ratio and options replace the ones passed in
+    let Some(name) = preset else {
+        return run_job_spec(&window, spec, passphrase, &settings, &history, &plugins, &canceller, &kiosk).await;
+    };
+    let preset = presets.get(&name).map_err(|e| error::ErrorCode::InvalidOptions.error(e))?;
+    spec.ratio = preset.ratio;
+    spec.options = preset.options;
+    kiosk.check_job_source(&spec.source, &plugins)?;
+    let mut builder = configure_job(trim::TrimJob::from_spec(spec), &window, passphrase.as_deref(), &settings, &plugins, &canceller)?;
+    if let Some(output_dir) = preset.output_dir {
+        kiosk.check_output_dir(std::path::Path::new(&output_dir), &settings.current().resolved_output_dir())?;
+        builder = builder.output_dir(output_dir);
+    }
+    let output = builder.build()?.run(Some(history.inner())).await?;
+    Ok(output.into())
+}
 
-    // Convert time format from HH:MM:SS to seconds for yt-dlp
-    let start_seconds = time_to_seconds(start_time)?;
-    let end_seconds = time_to_seconds(end_time)?;
-    
-    // Create download sections parameter
-    let download_sections = format!("*{}-{}", start_seconds, end_seconds);
+#[tauri::command]
+fn list_presets(presets: State<'_, presets::PresetState>) -> Vec<presets::Preset> {
+    presets.list()
+}
 
-    let status = Command::new("yt-dlp")
-        // Get absolute best quality
-        .arg("-f")
-        .arg("bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best")
-        .arg("--merge-output-format")
-        .arg("mp4")
-        .arg("--download-sections")
-        .arg(&download_sections)
-        .arg("--force-keyframes-at-cuts")
-        // Quality settings
-        .arg("--audio-quality")
-        .arg("0") // Best audio quality
-        .arg("--remux-video")
-        .arg("mp4")
-        // Speed optimizations
-        .arg("--concurrent-fragments")
-        .arg("4")
-        .arg("-o")
-        .arg(&output_template)
-        .arg(url)
-        .status()
-        .await
-        .map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                "yt-dlp command not found. Please install yt-dlp and ensure it is in your system's PATH.".to_string()
-            } else {
-                format!("Failed to execute yt-dlp: {}", e)
-            }
-        })?;
+#[tauri::command]
+fn create_preset(
+    preset: presets::Preset,
+    presets: State<'_, presets::PresetState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    presets.create(preset)
+}
 
-    if !status.success() {
-        return Err("yt-dlp failed to download the video segment. The URL might be invalid, private, or require a login.".to_string());
-    }
+#[tauri::command]
+fn update_preset(
+    name: String,
+    preset: presets::Preset,
+    presets: State<'_, presets::PresetState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    presets.update(&name, preset)
+}
 
-    // Find the downloaded file (it should be video.mp4)
-    let expected_path = output_dir.join("video.mp4");
-    if expected_path.exists() {
-        Ok(expected_path)
-    } else {
-        Err("yt-dlp ran, but the expected output file was not found.".to_string())
-    }
+#[tauri::command]
+fn delete_preset(
+    name: String,
+    presets: State<'_, presets::PresetState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    presets.delete(&name)
 }
 
-// Helper function to convert HH:MM:SS to seconds
-fn time_to_seconds(time_str: &str) -> Result<f64, String> {
-    let parts: Vec<&str> = time_str.split(':').collect();
-    if parts.len() != 3 {
-        return Err("Invalid time format. Expected HH:MM:SS".to_string());
-    }
-    
-    let hours: f64 = parts[0].parse().map_err(|_| "Invalid hours")?;
-    let minutes: f64 = parts[1].parse().map_err(|_| "Invalid minutes")?;
-    let seconds: f64 = parts[2].parse().map_err(|_| "Invalid seconds")?;
-    
-    Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+// Cuts a source, or a range of it, into parts. Without an end time the
+// whole source is split, which needs its duration from ffprobe.
+#[tauri::command]
+async fn split_video(
+    window: Window,
+    video_source: String,
+    split: job::Split,
+    start_time: Option<String>,
+    end_time: Option<String>,
+    options: Option<job::TrimOptions>,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<trim::TrimResult, error::TrimError> {
+    let end_time = match end_time {
+        Some(end_time) => end_time,
+        None if video_source.contains("://") => {
+            return Err(error::ErrorCode::InvalidRange.error("Set an end time to split a remote source"))
+        }
+        None => {
+            kiosk.check_source(&video_source)?;
+            let duration = probe::probe(std::path::Path::new(&video_source))
+                .await?
+                .duration()
+                .ok_or_else(|| "Couldn't read the source's duration".to_string())?;
+            timecode::format_timecode(duration)
+        }
+    };
+    let mut options = options.unwrap_or_default();
+    options.split = Some(split);
+    let spec = job::JobSpec {
+        version: job::JOB_SPEC_VERSION,
+        source: video_source,
+        start_time: start_time.unwrap_or_else(|| "00:00:00".to_string()),
+        end_time,
+        ratio: "Original".to_string(),
+        options,
+    };
+    run_job_spec(&window, spec, passphrase, &settings, &history, &plugins, &canceller, &kiosk).await
 }
 
 #[tauri::command]
-async fn trim_video(
+async fn estimate_output(
     video_source: String,
     start_time: String,
     end_time: String,
     ratio: String,
+    options: Option<job::TrimOptions>,
+    settings: State<'_, settings::SettingsState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<estimate::OutputEstimate, String> {
+    kiosk.check_job_source(&video_source, &plugins)?;
+    // Encryption barely changes the size and needs no passphrase to estimate
+    let options = job::TrimOptions {
+        encrypt: false,
+        ..options.unwrap_or_default()
+    };
+    trim::TrimJob::builder(video_source, start_time, end_time)
+        .ratio(ratio)
+        .options(options)
+        .settings(settings.current())
+        .plugins(plugins.inner().clone())
+        .build()?
+        .estimate()
+        .await
+}
+
+#[tauri::command]
+fn export_job_spec(
+    video_source: String,
+    start_time: String,
+    end_time: String,
+    ratio: String,
+    options: Option<job::TrimOptions>,
 ) -> Result<String, String> {
-    let video_path: PathBuf;
-    let _temp_dir_guard: Option<tempfile::TempDir>;
-    let is_youtube_video: bool;
-
-    // Check if it's a YouTube video before consuming the string
-    is_youtube_video = video_source.contains("youtube.com") || video_source.contains("youtu.be");
-
-    if video_source.starts_with("http") {
-        let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
-        
-        // Check for YouTube URLs and download only the segment
-        if is_youtube_video {
-            video_path = download_youtube_video_segment(
-                &video_source, 
-                temp_dir.path(), 
-                &start_time, 
-                &end_time
-            ).await?;
-        } else {
-            // For other direct video links, download the full video
-            let parsed_url = Url::parse(&video_source).map_err(|e| format!("Invalid URL: {}", e))?;
-            let filename = parsed_url
-                .path_segments()
-                .and_then(|segments| segments.last())
-                .unwrap_or("downloaded_video.mp4")
-                .to_string();
-
-            let temp_path = temp_dir.path().join(filename);
-
-            download_video_from_url(&video_source, &temp_path)
-                .await
-                .map_err(|e| format!("Failed to download video: {}", e))?;
-
-            video_path = temp_path;
-        }
-        
-        _temp_dir_guard = Some(temp_dir);
-    } else {
-        video_path = PathBuf::from(video_source);
-        if !video_path.exists() {
-            return Err(format!("Local video file not found: {}", video_path.display()));
-        }
-        _temp_dir_guard = None;
+    job::JobSpec {
+        version: job::JOB_SPEC_VERSION,
+        source: video_source,
+        start_time,
+        end_time,
+        ratio,
+        options: options.unwrap_or_default(),
     }
+    .to_json()
+}
 
-    let output_dir = if cfg!(target_os = "windows") {
-        std::env::var("USERPROFILE")
-            .map(|home| PathBuf::from(home).join("Downloads"))
-            .unwrap_or_else(|_| PathBuf::from("."))
-    } else {
-        std::env::var("HOME")
-            .map(|home| PathBuf::from(home).join("Downloads"))
-            .unwrap_or_else(|_| PathBuf::from("."))
-    };
+#[tauri::command]
+async fn submit_job_spec(
+    window: Window,
+    json: String,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<trim::TrimResult, error::TrimError> {
+    let spec = job::JobSpec::from_json(&json).map_err(|e| error::ErrorCode::InvalidOptions.error(e))?;
+    run_job_spec(&window, spec, passphrase, &settings, &history, &plugins, &canceller, &kiosk).await
+}
+
+// Newest first
+#[tauri::command]
+fn get_history(history: State<'_, history::HistoryState>) -> Vec<history::HistoryEntry> {
+    let mut entries = history.entries();
+    entries.reverse();
+    entries
+}
+
+// Exports a clip from history again with the same range and options. Its
+// earlier output is usually still there, so the duplicate check is skipped.
+#[tauri::command]
+async fn re_run_job(
+    window: Window,
+    id: String,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<trim::TrimResult, error::TrimError> {
+    let entry = history
+        .find(&id)
+        .ok_or_else(|| format!("No history entry with id {}", id))?;
+    let mut spec = entry.to_spec()?;
+    spec.options.allow_duplicates = true;
+    run_job_spec(&window, spec, passphrase, &settings, &history, &plugins, &canceller, &kiosk).await
+}
+
+#[tauri::command]
+fn clear_history(
+    history: State<'_, history::HistoryState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    history.clear()
+}
+
+#[tauri::command]
+fn get_cache_info(settings: State<'_, settings::SettingsState>) -> Result<cache::CacheInfo, String> {
+    cache::info(settings.current().download_cache_bytes())
+}
+
+// Returns how many bytes were freed
+#[tauri::command]
+fn clear_cache(kiosk: State<'_, kiosk::KioskPolicy>) -> Result<u64, String> {
+    kiosk.ensure_unlocked()?;
+    cache::clear()
+}
 
-    if !output_dir.exists() {
-        std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create Downloads directory: {}", e))?;
+#[tauri::command]
+async fn reveal_in_file_manager(path: PathBuf, kiosk: State<'_, kiosk::KioskPolicy>) -> Result<(), String> {
+    kiosk.ensure_can_open_files()?;
+    reveal::reveal_in_file_manager(&path).await
+}
+
+#[tauri::command]
+async fn open_output(path: PathBuf, kiosk: State<'_, kiosk::KioskPolicy>) -> Result<(), String> {
+    kiosk.ensure_can_open_files()?;
+    reveal::open_output(&path).await
+}
+
+// Stops every running job, including queued ones and batch items not yet
+// started. Each fails with the Cancelled code once its tools have stopped.
+#[tauri::command]
+fn cancel_jobs(canceller: State<'_, cancel::JobCanceller>) {
+    canceller.cancel_all();
+}
+
+// The passphrase is passed alongside the spec rather than in it, so it is
+// never written to history or an exported spec.
+async fn run_job_spec(
+    window: &Window,
+    spec: job::JobSpec,
+    passphrase: Option<String>,
+    settings: &settings::SettingsState,
+    history: &history::HistoryState,
+    plugins: &Arc<plugins::PluginRegistry>,
+    canceller: &cancel::JobCanceller,
+    kiosk: &kiosk::KioskPolicy,
+) -> Result<trim::TrimResult, error::TrimError> {
+    kiosk.check_job_source(&spec.source, plugins)?;
+    let builder = trim::TrimJob::from_spec(spec);
+    let output = configure_job(builder, window, passphrase.as_deref(), settings, plugins, canceller)?
+        .build()?
+        .run(Some(history))
+        .await?;
+    Ok(output.into())
+}
+
+// What every job started from the app shares: settings, plugins, events
+// back to the window and the passphrase
+fn configure_job(
+    builder: trim::TrimJobBuilder,
+    window: &Window,
+    passphrase: Option<&str>,
+    settings: &settings::SettingsState,
+    plugins: &Arc<plugins::PluginRegistry>,
+    canceller: &cancel::JobCanceller,
+) -> Result<trim::TrimJobBuilder, String> {
+    let resource_window = window.clone();
+    let notice_window = window.clone();
+    let collision_window = window.clone();
+    let download_window = window.clone();
+    let mut builder = builder
+        .settings(settings.current())
+        .plugins(plugins.clone())
+        .cancellation(canceller.token())
+        .resource_listener(telemetry::ResourceListener::new(move |sample| {
+            let _ = resource_window.emit("job_resources", sample);
+        }))
+        .notice_listener(telemetry::NoticeListener::new(move |message| {
+            let _ = notice_window.emit("job_notice", message);
+        }))
+        .collision_listener(telemetry::CollisionListener::new(move |existing| {
+            let _ = collision_window.emit("output_exists", existing.display().to_string());
+        }))
+        .download_listener(telemetry::DownloadListener::new(move |progress| {
+            let _ = download_window.emit("video_download_progress", progress);
+        }));
+    if let Some(passphrase) = passphrase.filter(|p| !p.is_empty()) {
+        builder = builder.passphrase(encryption::Passphrase::new(passphrase)?);
     }
+    Ok(builder)
+}
 
-    let output_filename = format!(
-        "trimmed_{}.mp4",
-        chrono::Utc::now().format("%Y%m%d%H%M%S")
-    );
-    let output_path = output_dir.join(output_filename);
-    
-    let mut command = ffmpeg_sidecar::command::FfmpegCommand::new();
-    
-    // If it's a YouTube video and we only need to copy (no aspect ratio change)
-    if is_youtube_video {
-        if ratio == "Original" {
-            // Just copy the already-trimmed YouTube video with quality preservation
-            command
-                .input(&video_path.to_string_lossy())
-                .args(&["-c:v", "copy"])
-                .args(&["-c:a", "copy"])
-                .args(&["-movflags", "+faststart"])
-                .output(&output_path.to_string_lossy())
-                .overwrite();
-        } else {
-            // Apply aspect ratio conversion to the YouTube segment
-            command.input(&video_path.to_string_lossy());
-            apply_aspect_ratio_filter_best_quality(&mut command, &ratio)?;
-            command.output(&output_path.to_string_lossy()).overwrite();
-        }
-    } else {
-        // For non-YouTube videos or local files, do the full trim + conversion
-        command
-            .input(&video_path.to_string_lossy())
-            .arg("-ss")
-            .arg(&start_time)
-            .arg("-to")
-            .arg(&end_time);
-
-        if ratio == "Original" {
-            command
-                .args(&["-c:v", "copy"])
-                .args(&["-c:a", "copy"])
-                .args(&["-avoid_negative_ts", "make_zero"])
-                .args(&["-movflags", "+faststart"]);
-        } else {
-            apply_aspect_ratio_filter_best_quality(&mut command, &ratio)?;
+#[tauri::command]
+async fn trim_multicam(
+    window: Window,
+    mut job: multicam::MulticamJob,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<String, error::TrimError> {
+    for camera in &job.sources {
+        kiosk.check_job_source(&camera.source, &plugins)?;
+    }
+    job.sync_offsets().await?;
+    let jobs = job.jobs(|builder| configure_job(builder, &window, passphrase.as_deref(), &settings, &plugins, &canceller))?;
+    let mut output_dir = None;
+    for trim_job in jobs {
+        output_dir = Some(trim_job.run(Some(history.inner())).await?.output_dir);
+    }
+    Ok(format!(
+        "Trimmed {} sources to: {}",
+        job.sources.len(),
+        output_dir.map(|dir| dir.display().to_string()).unwrap_or_default()
+    ))
+}
+
+#[tauri::command]
+async fn concat_videos(
+    window: Window,
+    items: Vec<concat::ConcatItem>,
+    output_name: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<String, error::TrimError> {
+    for item in &items {
+        match item {
+            concat::ConcatItem::File(path) => kiosk.check_source(&path.to_string_lossy())?,
+            concat::ConcatItem::Range { source, .. } => kiosk.check_job_source(source, &plugins)?,
         }
+    }
+    let output = concat::concat_videos(&items, output_name.as_deref(), &settings.current(), |builder| {
+        configure_job(builder, &window, None, &settings, &plugins, &canceller)
+    })
+    .await?;
+    Ok(format!("Joined {} clips into: {}", items.len(), output.display()))
+}
+
+#[tauri::command]
+async fn remux(
+    source: String,
+    container: remux::Container,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<remux::RemuxOutput, String> {
+    kiosk.check_source(&source)?;
+    remux::remux(std::path::Path::new(&source), container, &settings.current()).await
+}
+
+#[tauri::command]
+async fn list_available_builds() -> Vec<ffmpeg::ManagedBuild> {
+    ffmpeg::list_available_builds().await
+}
 
-        command.output(&output_path.to_string_lossy()).overwrite();
+// Downloads a managed FFmpeg release if needed and pins it, so jobs using
+// the managed build run with it from now on
+#[tauri::command]
+async fn install_build(
+    version: String,
+    window: Window,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<ffmpeg::ManagedBuild, String> {
+    kiosk.ensure_unlocked()?;
+    let installed = ffmpeg::list_available_builds()
+        .await
+        .into_iter()
+        .find(|build| build.version == version)
+        .is_some_and(|build| build.installed);
+    if !installed {
+        let client = download::build_client(settings.current().proxy.as_ref())?;
+        ffmpeg::install_build(&client, &version, move |downloaded_bytes, total_bytes| {
+            let _ = window.emit(
+                "ffmpeg_download_progress",
+                download::DownloadProgress { downloaded_bytes, total_bytes },
+            );
+        })
+        .await?;
     }
+    settings.update(|s| s.ffmpeg_version = Some(version.clone()))?;
+    ffmpeg::list_available_builds()
+        .await
+        .into_iter()
+        .find(|build| build.version == version)
+        .ok_or_else(|| format!("FFmpeg {} isn't available", version))
+}
 
-    let mut child = command
-        .spawn()
-        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
-
-    let mut success = false;
-    let mut ffmpeg_errors: Vec<String> = Vec::new();
-    for event in child.iter().map_err(|e| e.to_string())? {
-        match event {
-            ffmpeg_sidecar::event::FfmpegEvent::Done => {
-                success = true;
-                break;
-            }
-            ffmpeg_sidecar::event::FfmpegEvent::Error(e) => {
-                ffmpeg_errors.push(e.to_string());
-            }
-            _ => {}
-        }
+#[tauri::command]
+fn get_settings(settings: State<'_, settings::SettingsState>) -> settings::Settings {
+    settings.current()
+}
+
+// Replaces every setting at once; returns them as saved, with machine-wide
+// defaults filled in
+#[tauri::command]
+fn update_settings(
+    new_settings: settings::Settings,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<settings::Settings, String> {
+    kiosk.ensure_unlocked()?;
+    new_settings.validate()?;
+    settings.update(|s| *s = new_settings)
+}
+
+#[tauri::command]
+fn get_replay_config(settings: State<'_, settings::SettingsState>) -> Option<replay::ReplayConfig> {
+    settings.current().replay
+}
+
+#[tauri::command]
+fn set_replay_config(
+    config: Option<replay::ReplayConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.replay = config)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn start_replay_buffer(
+    settings: State<'_, settings::SettingsState>,
+    replay: State<'_, replay::ReplayBuffer>,
+) -> Result<(), String> {
+    let config = settings
+        .current()
+        .replay
+        .ok_or("Choose a capture source for the replay buffer first")?;
+    replay.start(&config)
+}
+
+#[tauri::command]
+fn stop_replay_buffer(replay: State<'_, replay::ReplayBuffer>) -> Result<(), String> {
+    replay.stop()
+}
+
+#[tauri::command]
+fn get_replay_status(replay: State<'_, replay::ReplayBuffer>) -> replay::ReplayStatus {
+    replay.status()
+}
+
+// Saves the last `seconds` (two minutes by default) of the replay buffer
+// like any other trim
+#[tauri::command]
+async fn clip_replay(
+    window: Window,
+    seconds: Option<f64>,
+    ratio: Option<String>,
+    options: Option<job::TrimOptions>,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    replay: State<'_, replay::ReplayBuffer>,
+) -> Result<trim::TrimResult, error::TrimError> {
+    let clip = replay.recent(seconds.unwrap_or(120.0)).await?;
+    let builder = trim::TrimJob::builder(clip.path.display().to_string(), clip.start_time.clone(), clip.end_time.clone())
+        .ratio(ratio.unwrap_or_else(|| "Original".to_string()))
+        .options(options.unwrap_or_default())
+        .output_name(clip.name.clone());
+    let output = configure_job(builder, &window, passphrase.as_deref(), &settings, &plugins, &canceller)?
+        .build()?
+        .run(Some(history.inner()))
+        .await?;
+    Ok(output.into())
+}
+
+#[tauri::command]
+fn get_proxy(settings: State<'_, settings::SettingsState>) -> Option<settings::ProxyConfig> {
+    settings.current().proxy
+}
+
+#[tauri::command]
+fn set_proxy(
+    proxy: Option<settings::ProxyConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    if let Some(proxy) = &proxy {
+        proxy.validate()?;
     }
+    settings.update(|s| s.proxy = proxy)?;
+    Ok(())
+}
 
-    if success && output_path.exists() {
-        Ok(format!("Video trimmed successfully! Saved to: {}", output_path.display()))
-    } else {
-        if !ffmpeg_errors.is_empty() {
-            Err(format!("FFmpeg failed: {}", ffmpeg_errors.join("; ")))
-        } else {
-            Err("FFmpeg failed to create the output file or did not finish successfully.".to_string())
-        }
+#[tauri::command]
+fn get_download_rate_limit(settings: State<'_, settings::SettingsState>) -> Option<u64> {
+    settings.current().max_download_rate_kbps
+}
+
+#[tauri::command]
+fn set_download_rate_limit(
+    kbps: Option<u64>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.max_download_rate_kbps = kbps.filter(|&kbps| kbps > 0))?;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_metered_policy(settings: State<'_, settings::SettingsState>) -> metered::MeteredPolicy {
+    settings.current().metered
+}
+
+#[tauri::command]
+fn set_download_retry(
+    policy: download::RetryPolicy,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    policy.validate()?;
+    settings.update(|s| s.download_retry = policy)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_metered_policy(
+    policy: metered::MeteredPolicy,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    if !policy.confirm_above_mb.is_finite() || policy.confirm_above_mb < 0.0 {
+        return Err("The confirmation threshold must be zero or more MB".to_string());
     }
+    settings.update(|s| s.metered = policy)?;
+    Ok(())
 }
 
-// Best quality helper function for video processing
-fn apply_aspect_ratio_filter_best_quality(command: &mut ffmpeg_sidecar::command::FfmpegCommand, ratio: &str) -> Result<(), String> {
-    // Use highest quality settings
-    match ratio {
-        "16:9" => {
-            command.args(&[
-                "-vf", "scale=1920:1080:flags=lanczos:force_original_aspect_ratio=decrease,pad=1920:1080:(ow-iw)/2:(oh-ih)/2:black,setsar=1",
-                "-c:v", "libx264",
-                "-preset", "slow", // Better quality than fast
-                "-crf", "17", // Very high quality
-                "-profile:v", "high",
-                "-level", "4.2",
-                "-pix_fmt", "yuv420p",
-                "-g", "30", // Keyframe interval
-                "-bf", "2", // B-frames
-                "-c:a", "aac",
-                "-b:a", "256k", // High audio bitrate
-                "-ar", "48000",
-                "-ac", "2", // Stereo
-                "-movflags", "+faststart",
-            ]);
-        }
-        "9:16" => {
-            command.args(&[
-                "-vf", "scale=1080:1920:flags=lanczos:force_original_aspect_ratio=decrease,pad=1080:1920:(ow-iw)/2:(oh-ih)/2:black,setsar=1",
-                "-c:v", "libx264",
-                "-preset", "slow",
-                "-crf", "17",
-                "-profile:v", "high",
-                "-level", "4.2",
-                "-pix_fmt", "yuv420p",
-                "-g", "30",
-                "-bf", "2",
-                "-c:a", "aac",
-                "-b:a", "256k",
-                "-ar", "48000",
-                "-ac", "2",
-                "-movflags", "+faststart",
-            ]);
+// What a URL job would download, and whether it needs confirming first
+#[tauri::command]
+async fn estimate_download(
+    source: String,
+    start_time: String,
+    end_time: String,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<metered::DownloadEstimate, String> {
+    kiosk.check_source(&source)?;
+    metered::estimate_download(&source, &start_time, &end_time, &settings.current()).await
+}
+
+#[tauri::command]
+fn get_politeness(settings: State<'_, settings::SettingsState>) -> politeness::Politeness {
+    settings.current().politeness
+}
+
+#[tauri::command]
+fn set_politeness(
+    politeness: politeness::Politeness,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    let mut limits = politeness.default.iter().chain(politeness.domains.values());
+    if limits.any(|limits| !limits.delay_seconds.is_finite() || limits.delay_seconds < 0.0) {
+        return Err("Request delays must be zero or more seconds".to_string());
+    }
+    settings.update(|s| s.politeness = politeness)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn add_offset(time: String, offset_seconds: f64) -> Result<String, String> {
+    timecode::add_offset(&time, offset_seconds)
+}
+
+#[tauri::command]
+fn clamp_to_duration(time: String, duration_seconds: f64) -> Result<String, String> {
+    timecode::clamp_to_duration(&time, duration_seconds)
+}
+
+#[tauri::command]
+fn format_timecode(seconds: f64) -> String {
+    timecode::format_timecode(seconds)
+}
+
+// Turns any accepted time input into HH:MM:SS(.mmm). Frame numbers are
+// converted with `source`'s frame rate.
+#[tauri::command]
+async fn normalize_time(
+    time: String,
+    source: Option<String>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<String, String> {
+    let fps = match source.filter(|_| timecode::is_frame_number(&time)) {
+        Some(source) => {
+            kiosk.check_source(&source)?;
+            probe::probe(std::path::Path::new(&source))
+                .await?
+                .video_stream()
+                .and_then(|v| v.frame_rate())
         }
-        "1:1" => {
-            command.args(&[
-                "-vf", "scale=1080:1080:flags=lanczos:force_original_aspect_ratio=decrease,pad=1080:1080:(ow-iw)/2:(oh-ih)/2:black,setsar=1",
-                "-c:v", "libx264",
-                "-preset", "slow",
-                "-crf", "17",
-                "-profile:v", "high",
-                "-level", "4.2",
-                "-pix_fmt", "yuv420p",
-                "-g", "30",
-                "-bf", "2",
-                "-c:a", "aac",
-                "-b:a", "256k",
-                "-ar", "48000",
-                "-ac", "2",
-                "-movflags", "+faststart",
-            ]);
+        None => None,
+    };
+    timecode::normalize_time(&time, fps)
+}
+
+#[tauri::command]
+async fn validate_trim_range(
+    source: String,
+    start_time: String,
+    end_time: String,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<range::RangeReport, String> {
+    kiosk.check_source(&source)?;
+    range::validate_trim_range(&source, &start_time, &end_time).await
+}
+
+#[tauri::command]
+async fn get_streams(
+    source: String,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<probe::StreamInfo>, String> {
+    kiosk.check_source(&source)?;
+    Ok(probe::probe(std::path::Path::new(&source)).await?.selectable_streams())
+}
+
+#[tauri::command]
+async fn export_frames(
+    source: String,
+    start_time: String,
+    end_time: String,
+    dir: String,
+    format: Option<frames::FrameFormat>,
+    sampling: Option<frames::FrameSampling>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<frames::FrameExport, String> {
+    kiosk.check_source(&source)?;
+    kiosk.check_output_dir(std::path::Path::new(&dir), &settings.current().resolved_output_dir())?;
+    frames::export_frames(
+        &source,
+        &start_time,
+        &end_time,
+        std::path::Path::new(&dir),
+        format.unwrap_or_default(),
+        sampling,
+    )
+    .await
+}
+
+#[tauri::command]
+async fn list_attachments(
+    source: String,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<attachments::Attachment>, String> {
+    kiosk.check_source(&source)?;
+    attachments::list_attachments(std::path::Path::new(&source)).await
+}
+
+#[tauri::command]
+async fn extract_attachments(
+    source: String,
+    indices: Option<Vec<u32>>,
+    dir: String,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<String>, String> {
+    kiosk.check_source(&source)?;
+    kiosk.check_output_dir(std::path::Path::new(&dir), &settings.current().resolved_output_dir())?;
+    attachments::extract_attachments(
+        std::path::Path::new(&source),
+        &indices.unwrap_or_default(),
+        std::path::Path::new(&dir),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn get_chapters(
+    source: String,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<chapters::Chapter>, String> {
+    kiosk.check_source(&source)?;
+    Ok(chapters::get_chapters(&source, &settings.current()).await?)
+}
+
+// Exports the chosen chapters (by index), or all of them, each to its own file
+#[tauri::command]
+async fn export_chapters(
+    window: Window,
+    video_source: String,
+    indexes: Option<Vec<usize>>,
+    ratio: String,
+    options: Option<job::TrimOptions>,
+    passphrase: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    plugins: State<'_, Arc<plugins::PluginRegistry>>,
+    canceller: State<'_, cancel::JobCanceller>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<String, error::TrimError> {
+    kiosk.check_job_source(&video_source, &plugins)?;
+    let mut selected = chapters::get_chapters(&video_source, &settings.current()).await?;
+    if let Some(indexes) = indexes {
+        selected.retain(|chapter| indexes.contains(&chapter.index));
+    }
+    let jobs = chapters::chapter_jobs(&video_source, &selected, &ratio, &options.unwrap_or_default(), |builder| {
+        configure_job(builder, &window, passphrase.as_deref(), &settings, &plugins, &canceller)
+    })?;
+    let mut output_dir = None;
+    for trim_job in jobs {
+        output_dir = Some(trim_job.run(Some(history.inner())).await?.output_dir);
+    }
+    Ok(format!(
+        "Exported {} chapters to: {}",
+        selected.len(),
+        output_dir.map(|dir| dir.display().to_string()).unwrap_or_default()
+    ))
+}
+
+#[tauri::command]
+async fn summarize_audio(
+    source: String,
+    bucket_seconds: f64,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<analysis::LoudnessBucket>, String> {
+    kiosk.check_source(&source)?;
+    analysis::summarize_audio(&source, bucket_seconds).await
+}
+
+#[tauri::command]
+async fn detect_silence(
+    source: String,
+    noise_db: f64,
+    min_duration: f64,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<analysis::SilentRange>, String> {
+    kiosk.check_source(&source)?;
+    analysis::detect_silence(&source, noise_db, min_duration).await
+}
+
+#[tauri::command]
+async fn find_sync_offset(
+    a: String,
+    b: String,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<sync::SyncOffset, String> {
+    kiosk.check_source(&a)?;
+    kiosk.check_source(&b)?;
+    sync::find_sync_offset(std::path::Path::new(&a), std::path::Path::new(&b)).await
+}
+
+#[tauri::command]
+async fn analyze_motion(
+    source: String,
+    bucket_seconds: f64,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<analysis::MotionTimeline, String> {
+    kiosk.check_source(&source)?;
+    analysis::analyze_motion(&source, bucket_seconds).await
+}
+
+#[tauri::command]
+async fn detect_defects(source: String, kiosk: State<'_, kiosk::KioskPolicy>) -> Result<Vec<analysis::Defect>, String> {
+    kiosk.check_source(&source)?;
+    analysis::detect_defects(&source).await
+}
+
+#[tauri::command]
+async fn detect_chat_spikes(
+    url: String,
+    bucket_seconds: Option<f64>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<analysis::SuggestedCut>, String> {
+    kiosk.check_source(&url)?;
+    let bucket_seconds = bucket_seconds.unwrap_or(30.0);
+    if !bucket_seconds.is_finite() || bucket_seconds <= 0.0 {
+        return Err("Bucket size must be a positive number of seconds".to_string());
+    }
+    let offsets = chat::fetch_chat_offsets(&url, &settings.current()).await?;
+    Ok(chat::detect_spikes(&offsets, bucket_seconds))
+}
+
+#[tauri::command]
+async fn detect_markers(
+    source: String,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<Vec<analysis::SuggestedCut>, String> {
+    kiosk.check_source(&source)?;
+    let config = settings
+        .current()
+        .marker_detector
+        .ok_or("No marker detector is configured.")?;
+    markers::detect_markers(&source, &config).await
+}
+
+#[tauri::command]
+fn set_marker_detector(
+    detector: Option<markers::MarkerDetectorConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.marker_detector = detector)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_whisper(
+    whisper: Option<captions::WhisperConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    if let Some(whisper) = &whisper {
+        if !std::path::Path::new(&whisper.model).is_file() {
+            return Err(format!("Whisper model not found: {}", whisper.model));
         }
-        _ => return Err(format!("Unsupported ratio: {}", ratio)),
     }
+    settings.update(|s| s.whisper = whisper)?;
     Ok(())
 }
 
-async fn download_video_from_url(url: &str, output_path: &PathBuf) -> Result<(), String> {
-    use tokio::io::AsyncWriteExt;
-    use futures::StreamExt;
+#[tauri::command]
+fn set_translator(
+    translator: Option<translation::TranslatorConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.translator = translator)?;
+    Ok(())
+}
 
-    let response = reqwest::get(url)
-        .await
-        .map_err(|e| format!("Failed to fetch URL: {}", e))?;
+// Kept in the OS keychain; None removes the saved key
+#[tauri::command]
+fn set_deepl_api_key(key: Option<String>, kiosk: State<'_, kiosk::KioskPolicy>) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    translation::set_deepl_api_key(key.as_deref())
+}
 
-    if !response.status().is_success() {
-        return Err(format!("Failed to download video: HTTP status {}", response.status()));
+#[tauri::command]
+fn set_stem_separator(
+    separator: Option<separation::SeparatorConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    if separator.as_ref().is_some_and(|separator| separator.command.trim().is_empty()) {
+        return Err("Enter the stem separator's command".to_string());
     }
+    settings.update(|s| s.stem_separator = separator)?;
+    Ok(())
+}
 
-    let mut file = tokio::fs::File::create(output_path)
-        .await
-        .map_err(|e| format!("Failed to create temporary file: {}", e))?;
+#[tauri::command]
+fn set_redact_logs(
+    enabled: bool,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.redact_logs = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_battery_policy(
+    policy: Option<power::BatteryPolicy>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.battery_policy = policy)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_mux_options(
+    options: remux::MuxOptions,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    settings.update(|s| s.mux = options)?;
+    Ok(())
+}
 
-    let mut stream = response.bytes_stream();
+#[tauri::command]
+fn set_job_script(
+    path: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &path {
+        scripting::check_script(std::path::Path::new(path))?;
+    }
+    settings.update(|s| s.job_script = path)?;
+    Ok(())
+}
 
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Error while downloading chunk: {}", e))?;
-        file.write_all(&chunk)
+#[tauri::command]
+async fn ensure_ytdlp_is_ready(
+    window: Window,
+    settings: State<'_, settings::SettingsState>,
+) -> Result<ytdlp::YtDlpStatus, String> {
+    if ytdlp::configured_ytdlp_path().is_none() && ytdlp::managed_ytdlp_path().is_none() {
+        let _ = window.emit("ytdlp_status", "Downloading yt-dlp...");
+        let client = download::build_client(settings.current().proxy.as_ref())?;
+        let progress_window = window.clone();
+        let installed = match ytdlp::latest_release_tag(&client).await {
+            Ok(tag) => ytdlp::install_release(&client, &tag, move |downloaded_bytes, total_bytes| {
+                let _ = progress_window.emit(
+                    "ytdlp_download_progress",
+                    download::DownloadProgress { downloaded_bytes, total_bytes },
+                );
+            })
             .await
-            .map_err(|e| format!("Failed to write chunk to file: {}", e))?;
+            .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = installed {
+            log::warn!("Managed yt-dlp download failed: {}", privacy::scrub(&e));
+            let _ = window.emit("ytdlp_status", "yt-dlp download failed, trying system yt-dlp...");
+        }
+    }
+
+    let path = ytdlp::ytdlp_path();
+    match ytdlp::installed_version(&path).await {
+        Some(version) => {
+            let _ = window.emit("ytdlp_status", "yt-dlp is ready.");
+            Ok(ytdlp::YtDlpStatus {
+                path: path.display().to_string(),
+                version,
+                managed: ytdlp::managed_ytdlp_path().is_some_and(|managed| managed == path),
+            })
+        }
+        None => {
+            let _ = window.emit("ytdlp_status", "yt-dlp not found. Please install yt-dlp manually.");
+            Err("yt-dlp could not be downloaded and no system yt-dlp was found. Please ensure it's in your PATH.".to_string())
+        }
     }
+}
+
+#[tauri::command]
+async fn update_ytdlp(
+    window: Window,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<ytdlp::YtDlpUpdate, String> {
+    kiosk.ensure_unlocked()?;
+    let client = download::build_client(settings.current().proxy.as_ref())?;
+    let latest = ytdlp::latest_release_tag(&client).await?;
+    let previous_version = match ytdlp::managed_ytdlp_path() {
+        Some(path) => ytdlp::installed_version(&path).await,
+        None => None,
+    };
+
+    if previous_version.as_deref() == Some(latest.as_str()) {
+        return Ok(ytdlp::YtDlpUpdate {
+            previous_version,
+            current_version: latest,
+            updated: false,
+        });
+    }
+
+    ytdlp::install_release(&client, &latest, move |downloaded_bytes, total_bytes| {
+        let _ = window.emit(
+            "ytdlp_download_progress",
+            download::DownloadProgress { downloaded_bytes, total_bytes },
+        );
+    })
+    .await?;
+
+    Ok(ytdlp::YtDlpUpdate {
+        previous_version,
+        current_version: latest,
+        updated: true,
+    })
+}
+
+#[tauri::command]
+async fn get_tool_status() -> tools::ToolStatus {
+    tools::get_tool_status().await
+}
+
+// Which options the current FFmpeg build can handle; `refresh` probes again
+// instead of reusing the last result
+#[tauri::command]
+async fn get_ffmpeg_capabilities(refresh: bool) -> Result<capabilities::CapabilityReport, String> {
+    capabilities::self_test(refresh).await
+}
+
+// The NVIDIA GPUs a job can be pinned to
+#[tauri::command]
+async fn list_gpus() -> Result<Vec<gpu::Gpu>, String> {
+    gpu::list_gpus().await
+}
+
+#[tauri::command]
+fn get_last_job_log() -> Result<String, String> {
+    joblog::last_job_log()
+}
+
+// Saved to `dir`, or the output folder, for the user to attach to a report
+#[tauri::command]
+async fn export_diagnostics(
+    dir: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<String, String> {
+    let settings = settings.current();
+    let dir = dir.map(PathBuf::from).unwrap_or_else(|| settings.resolved_output_dir());
+    kiosk.check_output_dir(&dir, &settings.resolved_output_dir())?;
+    let bundle = diagnostics::export_bundle(&dir, &settings).await?;
+    Ok(bundle.display().to_string())
+}
+
+#[tauri::command]
+async fn check_output_target(
+    path: Option<String>,
+    expected_bytes: Option<u64>,
+    settings: State<'_, settings::SettingsState>,
+) -> Result<volume::OutputTargetReport, String> {
+    let path = path.map(PathBuf::from).unwrap_or_else(|| settings.current().resolved_output_dir());
+    Ok(volume::check_output_target(&path, expected_bytes))
+}
+
+#[tauri::command]
+fn get_kiosk_policy(kiosk: State<'_, kiosk::KioskPolicy>) -> kiosk::KioskPolicy {
+    kiosk.inner().clone()
+}
 
+#[tauri::command]
+fn list_plugins(plugins: State<'_, Arc<plugins::PluginRegistry>>) -> Vec<plugins::PluginManifest> {
+    plugins.manifests()
+}
+
+#[tauri::command]
+fn list_profiles(profiles: State<'_, profiles::ProfileState>) -> profiles::ProfileList {
+    profiles.list()
+}
+
+#[tauri::command]
+fn create_profile(
+    name: String,
+    profiles: State<'_, profiles::ProfileState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    profiles.create(&name)
+}
+
+#[tauri::command]
+fn switch_profile(
+    name: String,
+    profiles: State<'_, profiles::ProfileState>,
+    settings: State<'_, settings::SettingsState>,
+    presets: State<'_, presets::PresetState>,
+    history: State<'_, history::HistoryState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    profiles.switch(&name, &settings, &presets, &history)
+}
+
+#[tauri::command]
+fn delete_profile(
+    name: String,
+    profiles: State<'_, profiles::ProfileState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    profiles.delete(&name)
+}
+
+#[tauri::command]
+fn set_output_dir(
+    path: Option<String>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &path {
+        std::fs::create_dir_all(path).map_err(|e| format!("Failed to create output directory: {}", e))?;
+    }
+    settings.update(|s| s.output_dir = path)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_mirror(
+    mirror: Option<mirror::MirrorConfig>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    let mirror = mirror.filter(|m| !m.dir.trim().is_empty());
+    // Checked now so a typo is caught here rather than after the next export
+    if let Some(mirror) = &mirror {
+        if !std::path::Path::new(&mirror.dir).is_dir() {
+            return Err(format!("Mirror folder not found: {}", mirror.dir));
+        }
+    }
+    settings.update(|s| s.mirror = mirror)?;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_retention_policy(
+    policy: Option<retention::RetentionPolicy>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    let policy = policy.filter(|p| p.max_age_days.is_some() || p.keep_per_source.is_some());
+    settings.update(|s| s.retention = policy)?;
+    Ok(())
+}
+
+// Lists what the retention policy would remove, without removing anything
+#[tauri::command]
+fn preview_cleanup(
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+) -> Result<retention::CleanupReport, String> {
+    clean_outputs(&settings, &history, true)
+}
+
+#[tauri::command]
+fn run_cleanup(
+    settings: State<'_, settings::SettingsState>,
+    history: State<'_, history::HistoryState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<retention::CleanupReport, String> {
+    kiosk.ensure_unlocked()?;
+    clean_outputs(&settings, &history, false)
+}
+
+fn clean_outputs(
+    settings: &settings::SettingsState,
+    history: &history::HistoryState,
+    dry_run: bool,
+) -> Result<retention::CleanupReport, String> {
+    let current = settings.current();
+    let policy = current.retention.as_ref().ok_or("No retention policy is set")?;
+    retention::apply(&current.resolved_output_dir(), policy, &history.entries(), dry_run)
+}
+
+#[tauri::command]
+fn set_default_watermark(
+    watermark: Option<job::Watermark>,
+    settings: State<'_, settings::SettingsState>,
+    kiosk: State<'_, kiosk::KioskPolicy>,
+) -> Result<(), String> {
+    kiosk.ensure_unlocked()?;
+    if let Some(watermark) = &watermark {
+        if !std::path::Path::new(&watermark.path).is_file() {
+            return Err(format!("Watermark image not found: {}", watermark.path));
+        }
+    }
+    settings.update(|s| s.watermark = watermark)?;
     Ok(())
 }
 
 fn main() {
     tauri::Builder::default()
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            ffmpeg::set_managed_dir(data_dir.join("ffmpeg"));
+            ytdlp::set_managed_dir(data_dir.join("yt-dlp"));
+            joblog::set_log_dir(data_dir.join("logs"));
+            cache::set_cache_dir(app.path().app_cache_dir()?.join("downloads"));
+            let config_dir = app.path().app_config_dir()?;
+            app.manage(kiosk::KioskPolicy::load(&config_dir));
+            let profiles = profiles::ProfileState::load(config_dir, data_dir.clone());
+            let active_profile = profiles.active();
+            app.manage(presets::PresetState::load(profiles.presets_path(&active_profile)));
+            let settings = settings::SettingsState::load(profiles.settings_path(&active_profile));
+            let history = history::HistoryState::load(profiles.history_path(&active_profile));
+            if settings.current().retention.is_some() {
+                match clean_outputs(&settings, &history, false) {
+                    Ok(report) if !report.items.is_empty() => log::info!(
+                        "Retention policy removed {} old outputs ({} bytes)",
+                        report.items.len(),
+                        report.total_bytes
+                    ),
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Retention cleanup failed: {}", privacy::scrub(&e)),
+                }
+            }
+            app.manage(settings);
+            app.manage(history);
+            app.manage(profiles);
+            app.manage(replay::ReplayBuffer::default());
+            app.manage(cancel::JobCanceller::default());
+            app.manage(Arc::new(plugins::PluginRegistry::discover(&data_dir.join("plugins"))));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             ensure_ffmpeg_is_ready,
-            trim_video
+            trim_video,
+            list_presets,
+            create_preset,
+            update_preset,
+            delete_preset,
+            trim_multicam,
+            split_video,
+            concat_videos,
+            remux,
+            estimate_output,
+            export_job_spec,
+            submit_job_spec,
+            get_history,
+            re_run_job,
+            clear_history,
+            get_cache_info,
+            clear_cache,
+            cancel_jobs,
+            reveal_in_file_manager,
+            open_output,
+            get_settings,
+            update_settings,
+            get_replay_config,
+            set_replay_config,
+            start_replay_buffer,
+            stop_replay_buffer,
+            get_replay_status,
+            clip_replay,
+            get_proxy,
+            set_proxy,
+            get_download_rate_limit,
+            set_download_rate_limit,
+            get_metered_policy,
+            set_download_retry,
+            set_metered_policy,
+            estimate_download,
+            get_politeness,
+            set_politeness,
+            add_offset,
+            clamp_to_duration,
+            format_timecode,
+            normalize_time,
+            validate_trim_range,
+            get_streams,
+            get_chapters,
+            export_chapters,
+            export_frames,
+            list_attachments,
+            extract_attachments,
+            summarize_audio,
+            detect_silence,
+            analyze_motion,
+            detect_defects,
+            find_sync_offset,
+            detect_chat_spikes,
+            detect_markers,
+            set_marker_detector,
+            set_whisper,
+            set_translator,
+            set_deepl_api_key,
+            set_stem_separator,
+            set_redact_logs,
+            set_battery_policy,
+            set_mux_options,
+            set_job_script,
+            ensure_ytdlp_is_ready,
+            update_ytdlp,
+            get_tool_status,
+            get_ffmpeg_capabilities,
+            list_available_builds,
+            install_build,
+            list_gpus,
+            get_last_job_log,
+            export_diagnostics,
+            check_output_target,
+            get_kiosk_policy,
+            list_plugins,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            delete_profile,
+            set_output_dir,
+            set_mirror,
+            set_retention_policy,
+            preview_cleanup,
+            run_cleanup,
+            set_default_watermark
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
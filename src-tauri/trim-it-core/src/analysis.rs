@@ -0,0 +1,295 @@
+use serde::Serialize;
+
+use crate::ffmpeg;
+
+// Analysis passes decode at a low sample rate; loudness per bucket doesn't
+// need full-band audio and this keeps multi-hour VODs fast.
+const ANALYSIS_SAMPLE_RATE: u32 = 8000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoudnessBucket {
+    pub start: f64,
+    pub duration: f64,
+    // None when the bucket is digital silence (-inf dB)
+    pub rms_db: Option<f64>,
+    pub peak_db: Option<f64>,
+}
+
+pub async fn summarize_audio(source: &str, bucket_seconds: f64) -> Result<Vec<LoudnessBucket>, String> {
+    if !bucket_seconds.is_finite() || bucket_seconds <= 0.0 {
+        return Err("Bucket size must be a positive number of seconds".to_string());
+    }
+    let samples_per_bucket = ((bucket_seconds * ANALYSIS_SAMPLE_RATE as f64).round() as u64).max(1);
+
+    // asetnsamples turns each bucket into exactly one audio frame, so astats
+    // with reset=1 reports one RMS/peak pair per bucket.
+    let filter = format!(
+        "aresample={},asetnsamples=n={}:p=0,astats=metadata=1:reset=1:measure_perchannel=none:measure_overall=RMS_level+Peak_level,ametadata=print",
+        ANALYSIS_SAMPLE_RATE, samples_per_bucket
+    );
+
+    let mut command = ffmpeg::new_command();
+    command
+        .input(source)
+        .args(["-vn", "-sn", "-dn"])
+        .args(["-af", &filter])
+        .args(["-f", "null", "-"]);
+
+    let logs = ffmpeg::run(command).await?;
+
+    let mut buckets: Vec<LoudnessBucket> = Vec::new();
+    for line in &logs {
+        if let Some(pts_time) = ffmpeg::log_value(line, "pts_time:") {
+            let start = pts_time.parse().unwrap_or(buckets.len() as f64 * bucket_seconds);
+            buckets.push(LoudnessBucket {
+                start,
+                duration: bucket_seconds,
+                rms_db: None,
+                peak_db: None,
+            });
+        } else if let Some(bucket) = buckets.last_mut() {
+            if let Some(value) = ffmpeg::log_value(line, "lavfi.astats.Overall.RMS_level=") {
+                bucket.rms_db = parse_db(value);
+            } else if let Some(value) = ffmpeg::log_value(line, "lavfi.astats.Overall.Peak_level=") {
+                bucket.peak_db = parse_db(value);
+            }
+        }
+    }
+
+    if buckets.is_empty() {
+        return Err("No audio could be analyzed. The source may not contain an audio track.".to_string());
+    }
+    Ok(buckets)
+}
+
+fn parse_db(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|db| db.is_finite())
+}
+
+// Motion is measured on a downscaled, frame-rate-reduced copy; absolute
+// detail doesn't matter for "is anything happening here".
+const MOTION_SAMPLE_FPS: u32 = 5;
+const MOTION_SAMPLE_WIDTH: u32 = 320;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MotionBucket {
+    pub start: f64,
+    pub duration: f64,
+    // Mean luma difference between consecutive sampled frames (0-255)
+    pub motion: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrozenRange {
+    pub start: f64,
+    // None when the video is still frozen at the end of the source
+    pub end: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MotionTimeline {
+    pub buckets: Vec<MotionBucket>,
+    pub frozen: Vec<FrozenRange>,
+}
+
+pub async fn analyze_motion(source: &str, bucket_seconds: f64) -> Result<MotionTimeline, String> {
+    if !bucket_seconds.is_finite() || bucket_seconds <= 0.0 {
+        return Err("Bucket size must be a positive number of seconds".to_string());
+    }
+
+    let filter = format!(
+        "fps={},scale={}:-2,freezedetect=n=-60dB:d=2,signalstats,metadata=print",
+        MOTION_SAMPLE_FPS, MOTION_SAMPLE_WIDTH
+    );
+
+    let mut command = ffmpeg::new_command();
+    command
+        .input(source)
+        .args(["-an", "-sn", "-dn"])
+        .args(["-vf", &filter])
+        .args(["-f", "null", "-"]);
+
+    let logs = ffmpeg::run(command).await?;
+
+    // (sum, count) of YDIF per bucket
+    let mut totals: Vec<(f64, u32)> = Vec::new();
+    let mut frozen: Vec<FrozenRange> = Vec::new();
+    let mut current_bucket = 0usize;
+    for line in &logs {
+        if let Some(pts_time) = ffmpeg::log_value(line, "pts_time:") {
+            let time: f64 = pts_time.parse().unwrap_or(0.0);
+            current_bucket = (time.max(0.0) / bucket_seconds) as usize;
+            if totals.len() <= current_bucket {
+                totals.resize(current_bucket + 1, (0.0, 0));
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "lavfi.signalstats.YDIF=") {
+            if let (Ok(ydif), Some(total)) = (value.parse::<f64>(), totals.get_mut(current_bucket)) {
+                total.0 += ydif;
+                total.1 += 1;
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "lavfi.freezedetect.freeze_start=") {
+            if let Ok(start) = value.parse() {
+                frozen.push(FrozenRange { start, end: None });
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "lavfi.freezedetect.freeze_end=") {
+            if let (Ok(end), Some(range)) = (value.parse(), frozen.last_mut()) {
+                range.end = Some(end);
+            }
+        }
+    }
+
+    if totals.is_empty() {
+        return Err("No video frames could be analyzed. The source may not contain a video track.".to_string());
+    }
+
+    let buckets = totals
+        .iter()
+        .enumerate()
+        .map(|(index, &(sum, count))| MotionBucket {
+            start: index as f64 * bucket_seconds,
+            duration: bucket_seconds,
+            motion: if count > 0 { sum / count as f64 } else { 0.0 },
+        })
+        .collect();
+
+    Ok(MotionTimeline { buckets, frozen })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SilentRange {
+    pub start: f64,
+    // None when the source is still silent at its end
+    pub end: Option<f64>,
+}
+
+// Stretches quieter than `noise_db` for at least `min_duration` seconds, for
+// snapping cuts to pauses or cutting dead air out of a clip
+pub async fn detect_silence(source: &str, noise_db: f64, min_duration: f64) -> Result<Vec<SilentRange>, String> {
+    if !noise_db.is_finite() || noise_db >= 0.0 {
+        return Err("The noise threshold must be below 0 dB".to_string());
+    }
+    if !min_duration.is_finite() || min_duration <= 0.0 {
+        return Err("The minimum silence length must be a positive number of seconds".to_string());
+    }
+
+    let filter = format!(
+        "aresample={},silencedetect=noise={}dB:d={}",
+        ANALYSIS_SAMPLE_RATE, noise_db, min_duration
+    );
+
+    let mut command = ffmpeg::new_command();
+    command
+        .input(source)
+        .args(["-vn", "-sn", "-dn"])
+        .args(["-af", &filter])
+        .args(["-f", "null", "-"]);
+
+    let logs = ffmpeg::run(command).await?;
+
+    // Without an audio track the output has no streams and FFmpeg fails above
+    let mut silences: Vec<SilentRange> = Vec::new();
+    for line in &logs {
+        if let Some(value) = ffmpeg::log_value(line, "silence_start:") {
+            if let Ok(start) = value.parse::<f64>() {
+                silences.push(SilentRange { start: start.max(0.0), end: None });
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "silence_end:") {
+            if let (Ok(end), Some(range)) = (value.parse(), silences.last_mut()) {
+                range.end = Some(end);
+            }
+        }
+    }
+
+    Ok(silences)
+}
+
+// Defects are found on the same reduced copy as motion. Shorter stretches
+// are usually a static scene or a fade rather than a broken capture.
+const FREEZE_MIN_SECONDS: f64 = 2.0;
+const BLACK_MIN_SECONDS: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Defect {
+    pub start: f64,
+    // None when the defect runs to the end of the source
+    pub end: Option<f64>,
+    pub kind: DefectKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefectKind {
+    // The picture stopped changing, e.g. a capture card that hung
+    Frozen,
+    Black,
+}
+
+// Stretches where the capture froze or went black, in order, for cutting
+// them out of a clip
+pub async fn detect_defects(source: &str) -> Result<Vec<Defect>, String> {
+    let filter = format!(
+        "fps={},scale={}:-2,freezedetect=n=-60dB:d={},blackdetect=d={}:pix_th=0.10,metadata=print",
+        MOTION_SAMPLE_FPS, MOTION_SAMPLE_WIDTH, FREEZE_MIN_SECONDS, BLACK_MIN_SECONDS
+    );
+
+    let mut command = ffmpeg::new_command();
+    command
+        .input(source)
+        .args(["-an", "-sn", "-dn"])
+        .args(["-vf", &filter])
+        .args(["-f", "null", "-"]);
+
+    let logs = ffmpeg::run(command).await?;
+
+    let mut defects: Vec<Defect> = Vec::new();
+    for line in &logs {
+        if let Some(value) = ffmpeg::log_value(line, "lavfi.freezedetect.freeze_start=") {
+            if let Ok(start) = value.parse() {
+                defects.push(Defect {
+                    start,
+                    end: None,
+                    kind: DefectKind::Frozen,
+                });
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "lavfi.freezedetect.freeze_end=") {
+            let open = defects
+                .iter_mut()
+                .rev()
+                .find(|defect| defect.kind == DefectKind::Frozen && defect.end.is_none());
+            if let (Ok(end), Some(defect)) = (value.parse(), open) {
+                defect.end = Some(end);
+            }
+        } else if let Some(value) = ffmpeg::log_value(line, "black_start:") {
+            // blackdetect reports each stretch on one line once it ends
+            if let Ok(start) = value.parse::<f64>() {
+                defects.push(Defect {
+                    start: start.max(0.0),
+                    end: ffmpeg::log_value(line, "black_end:").and_then(|end| end.parse().ok()),
+                    kind: DefectKind::Black,
+                });
+            }
+        }
+    }
+
+    defects.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(defects)
+}
+
+// A candidate range worth clipping, shared by every detector that suggests
+// cut points so the UI can merge them onto one timeline.
+#[derive(Debug, Clone, Serialize)]
+pub struct SuggestedCut {
+    pub start: f64,
+    pub end: f64,
+    // Detector-specific strength; higher is more notable
+    pub score: f64,
+    pub kind: SuggestionKind,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    ChatSpike,
+    Marker,
+}
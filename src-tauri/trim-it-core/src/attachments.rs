@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::probe::ProbeResult;
+use crate::{ffmpeg, filename, probe};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentKind {
+    // Cover art or an embedded thumbnail, stored as a one-frame video stream
+    CoverArt,
+    // A file carried by an MKV, e.g. a font or a poster image
+    File,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub index: u32,
+    pub kind: AttachmentKind,
+    pub codec: Option<String>,
+    pub filename: Option<String>,
+    pub mimetype: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+pub fn list(source_probe: &ProbeResult) -> Vec<Attachment> {
+    source_probe
+        .streams
+        .iter()
+        .filter_map(|stream| {
+            let kind = match stream.codec_type.as_deref() {
+                Some("video") if stream.disposition.get("attached_pic").is_some_and(|d| *d != 0) => {
+                    AttachmentKind::CoverArt
+                }
+                Some("attachment") => AttachmentKind::File,
+                _ => return None,
+            };
+            Some(Attachment {
+                index: stream.index,
+                kind,
+                codec: stream.codec_name.clone(),
+                filename: stream.tags.get("filename").cloned(),
+                mimetype: stream.tags.get("mimetype").cloned(),
+                width: stream.width,
+                height: stream.height,
+            })
+        })
+        .collect()
+}
+
+pub async fn list_attachments(source: &Path) -> Result<Vec<Attachment>, String> {
+    Ok(list(&probe::probe(source).await?))
+}
+
+// Saves the attachments of `source` with the given stream indices (all of
+// them when empty) into `dir`, returning the paths written. Files keep their
+// own names; cover art is saved as "cover_<index>.jpg" or ".png".
+pub async fn extract_attachments(source: &Path, indices: &[u32], dir: &Path) -> Result<Vec<String>, String> {
+    let source_probe = probe::probe(source).await?;
+    let attachments: Vec<Attachment> = list(&source_probe)
+        .into_iter()
+        .filter(|attachment| indices.is_empty() || indices.contains(&attachment.index))
+        .collect();
+    if attachments.is_empty() {
+        return Err("The source has no cover art or attachments to extract".to_string());
+    }
+    if let Some(missing) = indices.iter().find(|index| !attachments.iter().any(|a| a.index == **index)) {
+        return Err(format!("Stream {} is not cover art or an attachment", missing));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create the attachments folder: {}", e))?;
+
+    let mut written = Vec::new();
+    for attachment in &attachments {
+        let path = match attachment.kind {
+            AttachmentKind::CoverArt => {
+                let extension = if attachment.codec.as_deref() == Some("mjpeg") { "jpg" } else { "png" };
+                let path = dir.join(format!("cover_{}.{}", attachment.index, extension));
+                let mut command = ffmpeg::new_command();
+                command.input(source.to_string_lossy()).args(["-map", &format!("0:{}", attachment.index)]);
+                // Stored JPEG and PNG art is copied as is; anything else becomes a PNG
+                if matches!(attachment.codec.as_deref(), Some("mjpeg" | "png")) {
+                    command.args(["-c", "copy"]);
+                }
+                command.args(["-frames:v", "1"]).output(path.to_string_lossy()).overwrite();
+                ffmpeg::run(command).await
+                    .map_err(|e| format!("Failed to extract cover art {}: {}", attachment.index, e))?;
+                path
+            }
+            AttachmentKind::File => {
+                let name = attachment
+                    .filename
+                    .as_deref()
+                    .map(filename::sanitize_filename)
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| format!("attachment_{}", attachment.index));
+                let path = dir.join(name);
+                // Attachments are dumped while the input is opened, so no
+                // real output is needed
+                let dump_option = format!("-dump_attachment:{}", attachment.index);
+                let mut command = ffmpeg::new_command();
+                command
+                    .args([dump_option.as_str(), path.to_string_lossy().as_ref()])
+                    .input(source.to_string_lossy())
+                    .args(["-t", "0", "-f", "null"])
+                    .output("-")
+                    .overwrite();
+                // FFmpeg may still complain about the null output after the
+                // attachment is written, so success is judged by the file
+                if let Err(e) = ffmpeg::run(command).await {
+                    if !path.is_file() {
+                        return Err(format!("Failed to extract attachment {}: {}", attachment.index, e));
+                    }
+                }
+                path
+            }
+        };
+        written.push(path.display().to_string());
+    }
+    Ok(written)
+}
@@ -0,0 +1,35 @@
+use std::sync::OnceLock;
+
+use tokio::sync::Semaphore;
+
+use crate::{ffmpeg, joblog};
+
+// Blocking work runs on tokio's blocking threads, not the async workers that
+// also answer probe and settings commands. Its pool is large, so this caps
+// how much of it jobs use at once; the rest queue here.
+fn workers() -> &'static Semaphore {
+    static WORKERS: OnceLock<Semaphore> = OnceLock::new();
+    WORKERS.get_or_init(|| {
+        let threads = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Semaphore::new(threads.max(2))
+    })
+}
+
+// Runs `work` off the async runtime, e.g. waiting on FFmpeg or encrypting a
+// file. The job log and FFmpeg build of the calling job carry over, so
+// helpers behave as if they ran on the job's own task.
+pub async fn run<T, F>(work: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let _permit = workers()
+        .acquire()
+        .await
+        .map_err(|e| format!("Failed to start background work: {}", e))?;
+    let log = joblog::current();
+    let build = ffmpeg::current_build();
+    tokio::task::spawn_blocking(move || joblog::sync_scope(log, || ffmpeg::sync_scope(build, work)))
+        .await
+        .map_err(|e| format!("Background work failed: {}", e))
+}
@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{privacy, timecode, ytdlp};
+
+// Where downloaded YouTube segments are kept, set once at startup. Without
+// one nothing is cached.
+static CACHE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_cache_dir(dir: PathBuf) {
+    *CACHE_DIR.write().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheInfo {
+    pub path: String,
+    pub entries: usize,
+    pub size_bytes: u64,
+    pub limit_bytes: u64,
+}
+
+// Names the segment of `url` between the two times as yt-dlp downloads it,
+// so re-trimming the same range with other options finds it again. None for
+// links that aren't to a single YouTube video.
+pub fn segment_key(url: &str, start_seconds: f64, end_seconds: f64) -> Option<String> {
+    let video_id = youtube_video_id(url)?;
+    // The format changes when the download quality does, which would
+    // otherwise serve old downloads in place of new ones
+    let format: String = Sha256::digest(ytdlp::DOWNLOAD_FORMAT.as_bytes())
+        .iter()
+        .take(4)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Some(format!(
+        "{}_{}_{}-{}",
+        video_id,
+        format,
+        timecode::to_millis(start_seconds),
+        timecode::to_millis(end_seconds)
+    ))
+}
+
+// youtube.com/watch?v=ID, youtube.com/shorts/ID (also /live/ and /embed/)
+// and youtu.be/ID
+fn youtube_video_id(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let mut segments = parsed.path_segments()?;
+    let id = if host == "youtu.be" {
+        segments.next().map(str::to_string)
+    } else if host == "youtube.com" || host.ends_with(".youtube.com") {
+        match segments.next() {
+            Some("watch") => parsed.query_pairs().find(|(key, _)| key == "v").map(|(_, id)| id.to_string()),
+            Some("shorts" | "live" | "embed") => segments.next().map(str::to_string),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    id.filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.mp4", key))
+}
+
+pub fn contains(key: &str) -> bool {
+    cache_dir().is_some_and(|dir| entry_path(&dir, key).is_file())
+}
+
+// Links the cached segment into `work_dir` for a job to use, marking it as
+// recently used. Being a link, it survives the entry being evicted while the
+// job still reads it.
+pub fn restore(key: &str, work_dir: &Path) -> Option<PathBuf> {
+    let entry = entry_path(&cache_dir()?, key);
+    if !entry.is_file() {
+        return None;
+    }
+    let path = work_dir.join("video.mp4");
+    if let Err(e) = link_or_copy(&entry, &path) {
+        log::warn!("Failed to use the cached download: {}", e);
+        return None;
+    }
+    if let Err(e) = std::fs::File::options()
+        .write(true)
+        .open(&entry)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+    {
+        log::warn!("Failed to mark the cached download as used: {}", e);
+    }
+    Some(path)
+}
+
+// Keeps a finished download under `key`, then drops the least recently used
+// entries until the cache fits in `limit_bytes`. Failures only cost a
+// re-download later, so they're logged rather than failing the job.
+pub fn store(key: &str, download: &Path, limit_bytes: u64) {
+    let Some(dir) = cache_dir() else {
+        return;
+    };
+    if limit_bytes == 0 {
+        return;
+    }
+    if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| link_or_copy(download, &entry_path(&dir, key))) {
+        log::warn!("Failed to cache the download: {}", privacy::scrub(&e.to_string()));
+        return;
+    }
+    evict(&dir, limit_bytes);
+}
+
+fn link_or_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        std::fs::remove_file(to)?;
+    }
+    // Linking fails across drives, e.g. with the temp folder on another one
+    std::fs::hard_link(from, to).or_else(|_| std::fs::copy(from, to).map(|_| ()))
+}
+
+// Oldest first
+fn entries(dir: &Path) -> Vec<(SystemTime, u64, PathBuf)> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)
+        .map(|read_dir| {
+            read_dir
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "mp4"))
+                .filter_map(|entry| {
+                    let metadata = entry.metadata().ok()?;
+                    Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn evict(dir: &Path, limit_bytes: u64) {
+    let entries = entries(dir);
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    for (_, size, path) in entries {
+        if total <= limit_bytes {
+            break;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => total -= size,
+            Err(e) => log::warn!("Failed to evict a cached download: {}", e),
+        }
+    }
+}
+
+pub fn info(limit_bytes: u64) -> Result<CacheInfo, String> {
+    let dir = cache_dir().ok_or("The download cache isn't set up")?;
+    let entries = entries(&dir);
+    Ok(CacheInfo {
+        path: dir.display().to_string(),
+        entries: entries.len(),
+        size_bytes: entries.iter().map(|(_, size, _)| size).sum(),
+        limit_bytes,
+    })
+}
+
+// Deletes every cached download, returning how many bytes were freed
+pub fn clear() -> Result<u64, String> {
+    let dir = cache_dir().ok_or("The download cache isn't set up")?;
+    let mut freed = 0;
+    for (_, size, path) in entries(&dir) {
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to clear the download cache: {}", e))?;
+        freed += size;
+    }
+    Ok(freed)
+}
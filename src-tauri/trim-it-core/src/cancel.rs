@@ -0,0 +1,96 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Notify;
+
+pub const CANCELLED: &str = "The job was cancelled";
+
+// Shared by a job and whoever may stop it. Each stage races its waits
+// (requests, retry backoff, yt-dlp, ffprobe, FFmpeg) against it and stops
+// the tools it started, rather than the job being killed from outside.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    // Resolves once the token is cancelled
+    pub async fn cancelled(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        // Registered before checking, so a cancel in between isn't missed
+        notified.as_mut().enable();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: CancellationToken;
+}
+
+// Runs `job` with `token` as the one the stages it calls check
+pub async fn scope<F: Future>(token: CancellationToken, job: F) -> F::Output {
+    CURRENT.scope(token, job).await
+}
+
+// The running job's token; outside a job, one that is never cancelled
+pub fn current() -> CancellationToken {
+    CURRENT.try_with(CancellationToken::clone).unwrap_or_default()
+}
+
+// For the points between stages
+pub fn check() -> Result<(), String> {
+    if current().is_cancelled() {
+        return Err(CANCELLED.to_string());
+    }
+    Ok(())
+}
+
+// Waits for `work` unless the job is cancelled first, in which case `work`
+// is dropped. Child processes it started need kill_on_drop to stop with it.
+pub async fn until_cancelled<F: Future>(work: F) -> Result<F::Output, String> {
+    let token = current();
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(CANCELLED.to_string()),
+        output = work => Ok(output),
+    }
+}
+
+// Hands every job started from the app the same token, so one call stops
+// all of them; jobs started afterwards get a fresh one
+#[derive(Default)]
+pub struct JobCanceller(Mutex<CancellationToken>);
+
+impl JobCanceller {
+    pub fn token(&self) -> CancellationToken {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn cancel_all(&self) {
+        let mut token = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        token.cancel();
+        *token = CancellationToken::new();
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::job::{CaptionMode, Denoise, PadFill, SubtitleMode, TrimOptions};
+use crate::{ffmpeg, tools};
+
+// Options that only work when FFmpeg was built with a particular filter or
+// encoder. Distro and "essentials" builds often leave some of these out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    Encoding,
+    BurnedInSubtitles,
+    TextOverlay,
+    HdrToneMapping,
+    ColorLut,
+    BlurredPadding,
+    Denoise,
+    StrongDenoise,
+    LoudnessNormalization,
+    MusicDucking,
+    MotionAnalysis,
+    SilenceDetection,
+    DefectDetection,
+}
+
+const FEATURES: &[Feature] = &[
+    Feature::Encoding,
+    Feature::BurnedInSubtitles,
+    Feature::TextOverlay,
+    Feature::HdrToneMapping,
+    Feature::ColorLut,
+    Feature::BlurredPadding,
+    Feature::Denoise,
+    Feature::StrongDenoise,
+    Feature::LoudnessNormalization,
+    Feature::MusicDucking,
+    Feature::MotionAnalysis,
+    Feature::SilenceDetection,
+    Feature::DefectDetection,
+];
+
+enum Requirement {
+    Filter(&'static str),
+    Encoder(&'static str),
+}
+
+impl Feature {
+    fn label(self) -> &'static str {
+        match self {
+            Feature::Encoding => "Re-encoding",
+            Feature::BurnedInSubtitles => "Burned-in subtitles and captions",
+            Feature::TextOverlay => "Text overlays",
+            Feature::HdrToneMapping => "HDR to SDR conversion",
+            Feature::ColorLut => "Color LUTs",
+            Feature::BlurredPadding => "Blurred padding",
+            Feature::Denoise => "Denoising",
+            Feature::StrongDenoise => "Strong denoising",
+            Feature::LoudnessNormalization => "Loudness normalization",
+            Feature::MusicDucking => "Music ducking",
+            Feature::MotionAnalysis => "Motion analysis",
+            Feature::SilenceDetection => "Silence detection",
+            Feature::DefectDetection => "Freeze and black frame detection",
+        }
+    }
+
+    fn requirements(self) -> &'static [Requirement] {
+        use Requirement::{Encoder, Filter};
+        match self {
+            Feature::Encoding => &[Encoder("libx264"), Encoder("aac")],
+            // libass
+            Feature::BurnedInSubtitles => &[Filter("subtitles")],
+            // freetype
+            Feature::TextOverlay => &[Filter("drawtext")],
+            // zimg
+            Feature::HdrToneMapping => &[Filter("zscale"), Filter("tonemap")],
+            Feature::ColorLut => &[Filter("lut3d")],
+            // GPL-only filters
+            Feature::BlurredPadding => &[Filter("boxblur")],
+            Feature::Denoise => &[Filter("hqdn3d")],
+            Feature::StrongDenoise => &[Filter("nlmeans")],
+            Feature::LoudnessNormalization => &[Filter("loudnorm")],
+            Feature::MusicDucking => &[Filter("sidechaincompress")],
+            Feature::MotionAnalysis => &[Filter("freezedetect"), Filter("signalstats")],
+            Feature::SilenceDetection => &[Filter("silencedetect")],
+            Feature::DefectDetection => &[Filter("freezedetect"), Filter("blackdetect")],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureSupport {
+    pub feature: Feature,
+    pub label: String,
+    pub supported: bool,
+    // Filters and encoders this build lacks, e.g. "drawtext" or "libx264"
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityReport {
+    pub ffmpeg_path: String,
+    pub features: Vec<FeatureSupport>,
+}
+
+// What one FFmpeg binary was built with
+#[derive(Debug, Clone)]
+struct Capabilities {
+    ffmpeg_path: PathBuf,
+    filters: HashSet<String>,
+    encoders: HashSet<String>,
+}
+
+impl Capabilities {
+    fn missing(&self, feature: Feature) -> Vec<String> {
+        feature
+            .requirements()
+            .iter()
+            .filter_map(|requirement| match requirement {
+                Requirement::Filter(name) if !self.filters.contains(*name) => Some(name.to_string()),
+                Requirement::Encoder(name) if !self.encoders.contains(*name) => Some(name.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// Listing filters and encoders takes a couple of FFmpeg runs, so it's done
+// once per binary; switching FFmpeg builds probes again.
+static CACHE: RwLock<Option<Capabilities>> = RwLock::new(None);
+
+async fn capabilities(refresh: bool) -> Result<Capabilities, String> {
+    let path = ffmpeg::ffmpeg_path();
+    if !refresh {
+        let cached = CACHE.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(cached) = cached.filter(|c| c.ffmpeg_path == path) {
+            return Ok(cached);
+        }
+    }
+    let filters = list(&path, "-filters").await?;
+    let encoders = list(&path, "-encoders").await?;
+    let (video_encoders, audio_encoders) = tools::parse_encoders(&encoders);
+    let capabilities = Capabilities {
+        filters: parse_filters(&filters),
+        encoders: video_encoders.into_iter().chain(audio_encoders).collect(),
+        ffmpeg_path: path,
+    };
+    *CACHE.write().unwrap_or_else(|e| e.into_inner()) = Some(capabilities.clone());
+    Ok(capabilities)
+}
+
+async fn list(path: &Path, option: &str) -> Result<String, String> {
+    let output = Command::new(path)
+        .args(["-hide_banner", option])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("FFmpeg {} failed: {}", option, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// `ffmpeg -filters` lines look like " TSC zscale  V->V  Apply resizing, ...";
+// the legend above them has no "->" column
+fn parse_filters(listing: &str) -> HashSet<String> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (_flags, name, io) = (fields.next()?, fields.next()?, fields.next()?);
+            io.contains("->").then(|| name.to_string())
+        })
+        .collect()
+}
+
+// Checks the current FFmpeg for everything the app's options rely on, so the
+// UI can disable what this build can't do
+pub async fn self_test(refresh: bool) -> Result<CapabilityReport, String> {
+    let capabilities = capabilities(refresh).await?;
+    let features = FEATURES
+        .iter()
+        .map(|&feature| {
+            let missing = capabilities.missing(feature);
+            FeatureSupport {
+                feature,
+                label: feature.label().to_string(),
+                supported: missing.is_empty(),
+                missing,
+            }
+        })
+        .collect();
+    Ok(CapabilityReport {
+        ffmpeg_path: capabilities.ffmpeg_path.display().to_string(),
+        features,
+    })
+}
+
+// Assumed supported when FFmpeg can't be asked; the job then fails on its own
+pub async fn supports(feature: Feature) -> bool {
+    match capabilities(false).await {
+        Ok(capabilities) => capabilities.missing(feature).is_empty(),
+        Err(e) => {
+            log::warn!("Failed to check FFmpeg capabilities: {}", e);
+            true
+        }
+    }
+}
+
+// Fails with what's missing when the job asks for something this FFmpeg
+// build can't do, before anything is downloaded or encoded. Encoders are
+// left to the job, which can fall back to another one.
+pub async fn check_options(options: &TrimOptions) -> Result<(), String> {
+    let mut requested = Vec::new();
+    if options.subtitles.mode == SubtitleMode::Burn || options.captions == CaptionMode::Burn {
+        requested.push(Feature::BurnedInSubtitles);
+    }
+    if options.text_overlay.is_some() {
+        requested.push(Feature::TextOverlay);
+    }
+    if options.color.lut.is_some() {
+        requested.push(Feature::ColorLut);
+    }
+    if options.pad_fill == PadFill::Blur {
+        requested.push(Feature::BlurredPadding);
+    }
+    match options.denoise {
+        Denoise::Off => {}
+        Denoise::Light | Denoise::Medium => requested.push(Feature::Denoise),
+        Denoise::Strong => requested.push(Feature::StrongDenoise),
+    }
+    if options.normalize_loudness && !options.mute {
+        requested.push(Feature::LoudnessNormalization);
+    }
+    if options.background_music.as_ref().is_some_and(|music| music.duck) {
+        requested.push(Feature::MusicDucking);
+    }
+    if requested.is_empty() {
+        return Ok(());
+    }
+
+    let capabilities = match capabilities(false).await {
+        Ok(capabilities) => capabilities,
+        Err(e) => {
+            log::warn!("Failed to check FFmpeg capabilities: {}", e);
+            return Ok(());
+        }
+    };
+    for feature in requested {
+        let missing = capabilities.missing(feature);
+        if !missing.is_empty() {
+            return Err(format!(
+                "{} needs {} in FFmpeg, which this build ({}) doesn't include. Turn the option off or switch to the managed FFmpeg.",
+                feature.label(),
+                missing.join(" and "),
+                capabilities.ffmpeg_path.display()
+            ));
+        }
+    }
+    Ok(())
+}
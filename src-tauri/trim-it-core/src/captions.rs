@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{ffmpeg, timecode};
+
+// A local whisper.cpp build (`whisper-cli`, or `main` in older releases) and
+// the ggml model file it should load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhisperConfig {
+    pub command: String,
+    pub model: String,
+    // Spoken language, e.g. "en"; whisper detects it when unset
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+// Transcribes `duration` seconds of `source` from `start` into an SRT in
+// `work_dir`. Cue times are relative to `start`.
+pub async fn transcribe(
+    source: &Path,
+    start: f64,
+    duration: f64,
+    config: &WhisperConfig,
+    work_dir: &Path,
+) -> Result<PathBuf, String> {
+    if !Path::new(&config.model).is_file() {
+        return Err(format!("Whisper model not found: {}", config.model));
+    }
+
+    // whisper.cpp only reads 16 kHz mono WAV
+    let audio_path = work_dir.join("captions.wav");
+    let mut extract = ffmpeg::new_command();
+    extract
+        .args(["-ss", &start.to_string()])
+        .input(source.to_string_lossy())
+        .args(["-t", &duration.to_string()])
+        .args(["-vn", "-sn", "-dn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .output(audio_path.to_string_lossy())
+        .overwrite();
+    ffmpeg::run(extract).await.map_err(|e| format!("Failed to extract audio for captions: {}", e))?;
+
+    let output_base = work_dir.join("captions");
+    let mut whisper = Command::new(&config.command);
+    whisper
+        .args(&config.args)
+        .arg("-m")
+        .arg(&config.model)
+        .arg("-f")
+        .arg(&audio_path)
+        .arg("-osrt")
+        .arg("-of")
+        .arg(&output_base);
+    if let Some(language) = config.language.as_deref().filter(|l| !l.is_empty()) {
+        whisper.arg("-l").arg(language);
+    }
+    let output = whisper
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run whisper '{}': {}", config.command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Whisper exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let srt_path = output_base.with_extension("srt");
+    if srt_path.exists() {
+        Ok(srt_path)
+    } else {
+        Err("Whisper ran, but no captions file was written.".to_string())
+    }
+}
+
+// Maps cue times onto an output clip: divided by the playback speed, then
+// moved `shift` seconds earlier (for the later parts of a split export).
+// Cues that end up entirely before the start are dropped.
+pub fn retime_srt(srt: &str, speed: f64, shift: f64) -> String {
+    let mut cues = Vec::new();
+    for block in srt.replace("\r\n", "\n").split("\n\n") {
+        let lines: Vec<&str> = block.lines().filter(|line| !line.trim().is_empty()).collect();
+        let Some(timing_index) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let Some((start, end)) = lines[timing_index].split_once("-->") else {
+            continue;
+        };
+        let (Some(start), Some(end)) = (parse_srt_time(start), parse_srt_time(end)) else {
+            continue;
+        };
+        let (start, end) = (start / speed - shift, end / speed - shift);
+        if end <= 0.0 {
+            continue;
+        }
+        cues.push(format!(
+            "{}\n{} --> {}\n{}",
+            cues.len() + 1,
+            format_srt_time(start.max(0.0)),
+            format_srt_time(end),
+            lines[timing_index + 1..].join("\n")
+        ));
+    }
+    let mut out = cues.join("\n\n");
+    out.push('\n');
+    out
+}
+
+// "00:01:02,345"
+fn parse_srt_time(value: &str) -> Option<f64> {
+    timecode::time_to_seconds(&value.trim().replace(',', ".")).ok()
+}
+
+fn format_srt_time(seconds: f64) -> String {
+    let millis = timecode::to_millis(seconds);
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        (millis / 60_000) % 60,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
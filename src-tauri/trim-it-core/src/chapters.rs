@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::{ErrorCode, TrimError};
+use crate::job::TrimOptions;
+use crate::settings::Settings;
+use crate::trim::{TrimJob, TrimJobBuilder};
+use crate::{probe, timecode, ytdlp};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Chapter {
+    // Position in the source's chapter list, from 0
+    pub index: usize,
+    pub title: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+// Chapters from the container (MP4, MKV) or, for YouTube, from the video's
+// description timestamps as yt-dlp reads them. Untitled ones are numbered.
+pub async fn get_chapters(source: &str, settings: &Settings) -> Result<Vec<Chapter>, TrimError> {
+    let is_youtube = source.contains("youtube.com") || source.contains("youtu.be");
+    let raw: Vec<(Option<String>, Option<f64>, Option<f64>)> = if is_youtube {
+        let output = ytdlp::command(settings)
+            .map_err(TrimError::download)?
+            .args(["--dump-single-json", "--skip-download", "--no-playlist", "--no-warnings"])
+            .arg(source)
+            .output()
+            .await
+            .map_err(|e| TrimError::download(ytdlp::spawn_error(e)))?;
+        if !output.status.success() {
+            return Err(ErrorCode::DownloadFailed.error(format!(
+                "yt-dlp couldn't read the video's chapters: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let info: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| ErrorCode::DownloadFailed.error(format!("Unexpected yt-dlp output: {}", e)))?;
+        info.get("chapters")
+            .and_then(|c| c.as_array())
+            .map(|chapters| {
+                chapters
+                    .iter()
+                    .map(|chapter| {
+                        (
+                            chapter.get("title").and_then(|t| t.as_str()).map(str::to_string),
+                            chapter.get("start_time").and_then(|t| t.as_f64()),
+                            chapter.get("end_time").and_then(|t| t.as_f64()),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else {
+        probe::probe(Path::new(source))
+            .await
+            .map_err(TrimError::probe)?
+            .chapters
+            .into_iter()
+            .map(|chapter| {
+                let start = chapter.start_time.as_deref().and_then(|t| t.parse().ok());
+                let end = chapter.end_time.as_deref().and_then(|t| t.parse().ok());
+                (chapter.tags.get("title").cloned(), start, end)
+            })
+            .collect()
+    };
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|(title, start, end)| Some((title, start?, end?)))
+        .filter(|(_, start, end)| end > start)
+        .enumerate()
+        .map(|(index, (title, start, end))| Chapter {
+            index,
+            title: title
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| format!("Chapter {}", index + 1)),
+            start,
+            end,
+        })
+        .collect())
+}
+
+// One job per chapter, named "<number>_<title>" so the files sort in
+// chapter order. `configure` adds what every job needs, as for multicam.
+pub fn chapter_jobs(
+    source: &str,
+    chapters: &[Chapter],
+    ratio: &str,
+    options: &TrimOptions,
+    configure: impl Fn(TrimJobBuilder) -> Result<TrimJobBuilder, String>,
+) -> Result<Vec<TrimJob>, TrimError> {
+    if chapters.is_empty() {
+        return Err(ErrorCode::InvalidOptions.error("No chapters were chosen"));
+    }
+    chapters
+        .iter()
+        .map(|chapter| {
+            let builder = TrimJob::builder(
+                source,
+                timecode::format_timecode(chapter.start),
+                timecode::format_timecode(chapter.end),
+            )
+            .ratio(ratio)
+            .options(options.clone())
+            .output_name(format!("{:02}_{}", chapter.index + 1, chapter.title));
+            configure(builder)?.build()
+        })
+        .collect()
+}
@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::analysis::{SuggestedCut, SuggestionKind};
+use crate::settings::Settings;
+use crate::{paths, ytdlp};
+
+// A bucket counts as a spike when its message rate is this many standard
+// deviations above the VOD's average.
+const SPIKE_Z_SCORE: f64 = 2.0;
+const MIN_SPIKE_MESSAGES: usize = 3;
+
+// Downloads the chat replay for a YouTube or Twitch VOD via yt-dlp and returns
+// the offset (seconds into the video) of every message.
+pub async fn fetch_chat_offsets(url: &str, settings: &Settings) -> Result<Vec<f64>, String> {
+    let temp_dir = paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    let status = ytdlp::command(settings)?
+        .arg("--skip-download")
+        .arg("--write-subs")
+        .arg("--sub-langs")
+        .arg("live_chat,rechat")
+        .arg("-o")
+        .arg(temp_dir.path().join("chat.%(ext)s"))
+        .arg(url)
+        .status()
+        .await
+        .map_err(ytdlp::spawn_error)?;
+
+    if !status.success() {
+        return Err("yt-dlp failed to fetch the chat replay. The VOD might not have one, or it may be private.".to_string());
+    }
+
+    let chat_file = find_chat_file(temp_dir.path())
+        .ok_or("This video has no chat replay available.")?;
+    let contents = tokio::fs::read_to_string(&chat_file)
+        .await
+        .map_err(|e| format!("Failed to read chat replay: {}", e))?;
+
+    let offsets = parse_chat_offsets(&contents);
+    if offsets.is_empty() {
+        return Err("The chat replay did not contain any timestamped messages.".to_string());
+    }
+    Ok(offsets)
+}
+
+fn find_chat_file(dir: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.ends_with(".live_chat.json") || name.ends_with(".rechat.json")
+        })
+}
+
+// YouTube live_chat files are JSON lines carrying `videoOffsetTimeMsec`;
+// Twitch rechat files are one document with `comments[].content_offset_seconds`.
+fn parse_chat_offsets(contents: &str) -> Vec<f64> {
+    if let Ok(document) = serde_json::from_str::<Value>(contents) {
+        if let Some(comments) = document.get("comments").and_then(Value::as_array) {
+            return comments
+                .iter()
+                .filter_map(|c| c.get("content_offset_seconds").and_then(Value::as_f64))
+                .collect();
+        }
+    }
+
+    contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|entry| {
+            let offset = entry.get("replayChatItemAction")?.get("videoOffsetTimeMsec")?;
+            let millis = match offset {
+                Value::String(s) => s.parse::<f64>().ok()?,
+                other => other.as_f64()?,
+            };
+            Some(millis / 1000.0)
+        })
+        .collect()
+}
+
+pub fn detect_spikes(offsets: &[f64], bucket_seconds: f64) -> Vec<SuggestedCut> {
+    let last = offsets.iter().cloned().fold(0.0_f64, f64::max);
+    let bucket_count = (last / bucket_seconds).floor() as usize + 1;
+    let mut counts = vec![0usize; bucket_count];
+    for &offset in offsets.iter().filter(|o| **o >= 0.0) {
+        counts[((offset / bucket_seconds) as usize).min(bucket_count - 1)] += 1;
+    }
+
+    let mean = counts.iter().sum::<usize>() as f64 / bucket_count as f64;
+    let variance = counts.iter().map(|&c| (c as f64 - mean).powi(2)).sum::<f64>() / bucket_count as f64;
+    let std_dev = variance.sqrt();
+    if std_dev == 0.0 {
+        return Vec::new();
+    }
+
+    // Adjacent spiking buckets are merged into one suggestion, scored by the
+    // strongest bucket in the run.
+    let mut suggestions: Vec<SuggestedCut> = Vec::new();
+    let mut previous_spiked = false;
+    for (index, &count) in counts.iter().enumerate() {
+        let z_score = (count as f64 - mean) / std_dev;
+        let spiked = z_score >= SPIKE_Z_SCORE && count >= MIN_SPIKE_MESSAGES;
+        if spiked {
+            let start = index as f64 * bucket_seconds;
+            let end = start + bucket_seconds;
+            match suggestions.last_mut() {
+                Some(current) if previous_spiked => {
+                    current.end = end;
+                    current.score = current.score.max(z_score);
+                }
+                _ => suggestions.push(SuggestedCut {
+                    start,
+                    end,
+                    score: z_score,
+                    kind: SuggestionKind::ChatSpike,
+                    label: None,
+                }),
+            }
+        }
+        previous_spiked = spiked;
+    }
+
+    for suggestion in &mut suggestions {
+        let messages = offsets
+            .iter()
+            .filter(|&&o| o >= suggestion.start && o < suggestion.end)
+            .count();
+        suggestion.label = Some(format!("{} chat messages", messages));
+    }
+    suggestions
+}
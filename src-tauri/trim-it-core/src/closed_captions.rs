@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use crate::probe::ProbeResult;
+use crate::{captions, ffmpeg, filters, paths};
+
+// Reads the CEA-608/708 captions that TV recordings carry inside the video
+// stream, from `start` to `end` seconds, as SRT with cue times relative to
+// `start`. None when the range has no caption text.
+pub async fn extract_closed_captions(source: &Path, probe: &ProbeResult, start: f64, end: f64) -> Result<Option<String>, String> {
+    let work_dir = paths::tempdir().map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let srt_path = work_dir.path().join("captions.srt");
+    // Transport streams rarely start at 0, and -copyts keeps their
+    // timestamps, so the range is offset by the file's start time
+    let file_start = probe.start_time().unwrap_or(0.0);
+
+    // The movie source's "+subcc" output exposes the captions as a
+    // subtitle stream, which only works through lavfi
+    let graph = format!(
+        "movie={}:seek_point={}[out0+subcc]",
+        filters::escape_filter_value(&source.to_string_lossy()),
+        start
+    );
+    let mut command = ffmpeg::new_command();
+    command
+        .args(["-copyts", "-f", "lavfi"])
+        .input(&graph)
+        .args(["-map", "0:s:0", "-c:s", "srt"])
+        .args(["-to", &(file_start + end).to_string()])
+        .output(srt_path.to_string_lossy())
+        .overwrite();
+    ffmpeg::run(command).await.map_err(|e| format!("Failed to extract closed captions: {}", e))?;
+
+    let srt = std::fs::read_to_string(&srt_path).map_err(|e| format!("Failed to read closed captions: {}", e))?;
+    let srt = captions::retime_srt(&srt, 1.0, file_start + start);
+    Ok(Some(srt).filter(|srt| !srt.trim().is_empty()))
+}
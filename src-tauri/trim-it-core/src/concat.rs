@@ -0,0 +1,208 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::error::{ErrorCode, TrimError};
+use crate::estimate::BEST_QUALITY_AUDIO_KBPS;
+use crate::job::{Quality, VideoCodec};
+use crate::probe::ProbeResult;
+use crate::settings::Settings;
+use crate::trim::{self, TrimJob, TrimJobBuilder};
+use crate::{ffmpeg, filename, mirror, paths, probe};
+
+// One piece of a joined video: a file used whole, or a range of any source a
+// trim job accepts. A bare string is a file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ConcatItem {
+    File(PathBuf),
+    Range {
+        source: String,
+        start_time: String,
+        end_time: String,
+    },
+}
+
+// What has to match for the pieces to be joined without re-encoding
+#[derive(Debug, PartialEq)]
+struct StreamLayout {
+    video: Option<VideoLayout>,
+    audio: Option<AudioLayout>,
+}
+
+#[derive(Debug, PartialEq)]
+struct VideoLayout {
+    codec: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    pix_fmt: Option<String>,
+    frame_rate: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct AudioLayout {
+    codec: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+}
+
+impl StreamLayout {
+    fn of(probe: &ProbeResult) -> Self {
+        let audio = probe.streams.iter().find(|s| s.codec_type.as_deref() == Some("audio"));
+        StreamLayout {
+            video: probe.video_stream().map(|v| VideoLayout {
+                codec: v.codec_name.clone(),
+                width: v.width,
+                height: v.height,
+                pix_fmt: v.pix_fmt.clone(),
+                frame_rate: v.r_frame_rate.clone(),
+            }),
+            audio: audio.map(|a| AudioLayout {
+                codec: a.codec_name.clone(),
+                sample_rate: a.sample_rate.clone(),
+                channels: a.channels,
+            }),
+        }
+    }
+}
+
+// Joins `items` in order into one file in the settings' output folder.
+// Ranges are trimmed first; `configure` adds what every trim job needs, as
+// for multicam. Matching pieces are joined with stream copy, anything else
+// is re-encoded to the first piece's frame size and rate.
+pub async fn concat_videos(
+    items: &[ConcatItem],
+    output_name: Option<&str>,
+    settings: &Settings,
+    configure: impl Fn(TrimJobBuilder) -> Result<TrimJobBuilder, String>,
+) -> Result<PathBuf, TrimError> {
+    if items.len() < 2 {
+        return Err(ErrorCode::InvalidOptions.error("Pick at least two clips to join"));
+    }
+    let work_dir = paths::tempdir()
+        .map_err(|e| ErrorCode::TempDirUnavailable.error(format!("Failed to create temp directory: {}", e)))?;
+
+    let mut files = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        match item {
+            ConcatItem::File(path) => {
+                if !path.is_file() {
+                    return Err(ErrorCode::FileNotFound.error(format!("Clip not found: {}", path.display())));
+                }
+                files.push(path.clone());
+            }
+            ConcatItem::Range { source, start_time, end_time } => {
+                let part = configure(TrimJob::builder(source, start_time, end_time))?
+                    .without_mirror()
+                    .output_dir(work_dir.path())
+                    .output_name(format!("part_{:03}", index))
+                    .build()?
+                    .run(None)
+                    .await?;
+                files.extend(part.outputs);
+            }
+        }
+    }
+
+    let mut probes = Vec::new();
+    for file in &files {
+        probes.push(probe::probe(file).await.map_err(TrimError::probe)?);
+    }
+    let first_layout = StreamLayout::of(&probes[0]);
+    let stream_copy = probes.iter().all(|p| StreamLayout::of(p) == first_layout);
+
+    let output_dir = settings.resolved_output_dir();
+    std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create output directory: {}", e))?;
+    let stem = output_name
+        .map(filename::sanitize_filename)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("joined_{}", chrono::Utc::now().format("%Y%m%d%H%M%S")));
+    let extension = if stream_copy {
+        files[0].extension().map_or("mp4".to_string(), |e| e.to_string_lossy().to_lowercase())
+    } else {
+        "mp4".to_string()
+    };
+    let output_path = output_dir.join(format!("{}.{}", stem, extension));
+
+    let mut command = ffmpeg::new_command();
+    if stream_copy {
+        let list_path = work_dir.path().join("concat.txt");
+        let list: String = files
+            .iter()
+            .map(|file| format!("file '{}'\n", file.display().to_string().replace('\'', "'\\''")))
+            .collect();
+        std::fs::write(&list_path, list).map_err(|e| format!("Failed to write the concat list: {}", e))?;
+        command
+            .args(["-f", "concat", "-safe", "0"])
+            .input(list_path.to_string_lossy())
+            .args(["-map", "0:v?", "-map", "0:a?", "-c", "copy"]);
+    } else {
+        for file in &files {
+            command.input(file.to_string_lossy());
+        }
+        let (graph, size) = reencode_graph(&probes).map_err(|e| ErrorCode::InvalidOptions.error(e))?;
+        command
+            .args(["-filter_complex", &graph])
+            .args(["-map", "[v]", "-map", "[a]"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"]);
+        let crf = Quality::High.crf(VideoCodec::H264);
+        trim::apply_best_quality_h264_encoding(&mut command, Some(size), None, crf, None);
+        trim::apply_best_quality_audio_encoding(&mut command, BEST_QUALITY_AUDIO_KBPS);
+    }
+    command.args(settings.mux.args(&extension));
+    command.output(output_path.to_string_lossy()).overwrite();
+    ffmpeg::run(command)
+        .await
+        .map_err(|e| ErrorCode::EncodeFailed.error(format!("Failed to join the clips: {}", e)))?;
+
+    if let Some(config) = &settings.mirror {
+        if let Err(e) = mirror::mirror_file(&output_path, config).await {
+            log::warn!("Joined video was saved but not mirrored: {}", e);
+        }
+    }
+    Ok(output_path)
+}
+
+// Scales and pads every piece into the first one's frame, and fills pieces
+// without audio with silence so the concat filter sees matching streams
+fn reencode_graph(probes: &[ProbeResult]) -> Result<(String, (u32, u32)), String> {
+    let (width, height) = probes[0]
+        .video_size()
+        .ok_or("The first clip has no video to match the others to")?;
+    // x264 needs even dimensions
+    let (width, height) = (width / 2 * 2, height / 2 * 2);
+    let rate = probes[0]
+        .video_stream()
+        .and_then(|v| v.constant_frame_rate())
+        .unwrap_or_else(|| "30".to_string());
+
+    let mut graph = Vec::new();
+    let mut inputs = String::new();
+    for (index, probe) in probes.iter().enumerate() {
+        if probe.video_stream().is_none() {
+            return Err(format!("Clip {} has no video", index + 1));
+        }
+        graph.push(format!(
+            "[{i}:v:0]scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={r},format=yuv420p[v{i}]",
+            i = index,
+            w = width,
+            h = height,
+            r = rate
+        ));
+        if probe.streams.iter().any(|s| s.codec_type.as_deref() == Some("audio")) {
+            graph.push(format!(
+                "[{i}:a:0]aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo[a{i}]",
+                i = index
+            ));
+        } else {
+            let duration = probe.duration().ok_or_else(|| format!("Couldn't read the length of clip {}", index + 1))?;
+            graph.push(format!(
+                "anullsrc=r=48000:cl=stereo,atrim=duration={},aformat=sample_fmts=fltp[a{}]",
+                duration, index
+            ));
+        }
+        inputs.push_str(&format!("[v{i}][a{i}]", i = index));
+    }
+    graph.push(format!("{}concat=n={}:v=1:a=1[v][a]", inputs, probes.len()));
+    Ok((graph.join(";"), (width, height)))
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use zip::write::SimpleFileOptions;
+
+use crate::settings::Settings;
+use crate::{joblog, tools};
+
+// Zips the job logs, tool versions and settings into `dir` for attaching to
+// a bug report. Proxy credentials are left out of the settings.
+pub async fn export_bundle(dir: &Path, settings: &Settings) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create output directory: {}", e))?;
+    let path = dir.join(format!(
+        "trim-it-diagnostics_{}.zip",
+        chrono::Utc::now().format("%Y%m%d%H%M%S")
+    ));
+
+    let mut settings = settings.clone();
+    if let Some(proxy) = settings.proxy.as_mut() {
+        proxy.username = proxy.username.as_ref().map(|_| "<removed>".to_string());
+        proxy.password = proxy.password.as_ref().map(|_| "<removed>".to_string());
+    }
+    let settings_json =
+        serde_json::to_string_pretty(&settings).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let tools_json = serde_json::to_string_pretty(&tools::get_tool_status().await)
+        .map_err(|e| format!("Failed to serialize tool status: {}", e))?;
+    let system = format!(
+        "trim-it-core {}\nos: {}\narch: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    let file = std::fs::File::create(&path).map_err(|e| format!("Failed to create the diagnostics bundle: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut add = |name: &str, contents: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options)
+            .and_then(|_| zip.write_all(contents).map_err(zip::result::ZipError::from))
+            .map_err(|e| format!("Failed to write {} to the diagnostics bundle: {}", name, e))
+    };
+    add("system.txt", system.as_bytes())?;
+    add("settings.json", settings_json.as_bytes())?;
+    add("tools.json", tools_json.as_bytes())?;
+    if let Some(log_dir) = joblog::log_dir() {
+        for log in joblog::job_logs(&log_dir) {
+            let name = log.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let contents = std::fs::read(&log).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            add(&format!("logs/{}", name), &contents)?;
+        }
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to finish the diagnostics bundle: {}", e))?;
+    Ok(path)
+}
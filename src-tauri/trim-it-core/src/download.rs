@@ -0,0 +1,595 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE};
+use reqwest::StatusCode;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+
+use crate::settings::{ProxyConfig, Settings};
+use crate::telemetry::DownloadListener;
+use crate::{cancel, paths, privacy, timecode, ytdlp};
+
+// reqwest hands over chunks of a few KB to 16 KB, so this many in flight
+// stays within a megabyte or two
+const CHUNKS_IN_FLIGHT: usize = 64;
+const WRITE_BUFFER: usize = 1024 * 1024;
+const FLUSH_EVERY: u64 = 32 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadProgress {
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+// How often a direct download is retried after a network drop, 5xx or
+// throttling, and how long to wait in between
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    // Doubles after every failed attempt, up to max_backoff_secs. Each wait
+    // is a random half to all of that, so clients that failed together
+    // don't all retry together.
+    pub initial_backoff_secs: f64,
+    pub max_backoff_secs: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff_secs: 1.0,
+            max_backoff_secs: 30.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        for (label, secs) in [("initial", self.initial_backoff_secs), ("longest", self.max_backoff_secs)] {
+            if !secs.is_finite() || secs < 0.0 {
+                return Err(format!("The {} retry wait must be zero or more seconds", label));
+            }
+        }
+        Ok(())
+    }
+
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        self.jittered_backoff(attempt, fastrand::f64())
+    }
+
+    // `jitter` is in 0..1
+    fn jittered_backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31) as i32;
+        let secs = (self.initial_backoff_secs * 2f64.powi(exponent)).min(self.max_backoff_secs);
+        Duration::try_from_secs_f64(secs * (0.5 + jitter / 2.0)).unwrap_or(Duration::ZERO)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    pub retry: RetryPolicy,
+    pub max_bytes_per_sec: Option<u64>,
+    pub listener: Option<DownloadListener>,
+}
+
+// Token bucket allowing up to one second of burst. Chunks that overdraw the
+// bucket put it into debt and we sleep until it is paid back, which keeps the
+// average rate at the limit regardless of chunk size.
+struct Throttle {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Throttle {
+    fn new(bytes_per_sec: u64) -> Self {
+        let rate = bytes_per_sec.max(1) as f64;
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    async fn consume(&mut self, bytes: usize) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-self.tokens / self.rate)).await;
+        }
+    }
+}
+
+// Errors that are worth retrying (network drops, 5xx, throttling) versus ones
+// that will fail the same way every time (404, 403, bad URL).
+enum AttemptError {
+    Transient(String),
+    Permanent(String),
+}
+
+// Partial downloads live outside the per-job temp dir so they survive an app
+// restart and the next attempt at the same URL picks up where it left off.
+pub fn partial_download_dir() -> PathBuf {
+    paths::temp_dir().join("trim-it").join("partial")
+}
+
+// Named after the URL so the same download is found again after a restart,
+// with a hash that stays the same across builds of the app
+fn part_path_for(url: &str) -> PathBuf {
+    let hash: String = Sha256::digest(url.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    partial_download_dir().join(format!("{}.part", hash))
+}
+
+// Jobs downloading the same URL would write into the same partial file, so
+// each holds a lock on it for the whole download and the others wait. The
+// lock file is left behind; removing it could let a waiting job and a new
+// one both lock it.
+async fn lock_part(part_path: &Path) -> Result<std::fs::File, String> {
+    let lock = std::fs::File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(part_path.with_extension("part.lock"))
+        .map_err(|e| format!("Failed to lock the partial download: {}", e))?;
+    let mut waiting = false;
+    while lock.try_lock_exclusive().is_err() {
+        if !waiting {
+            log::info!("Waiting for another job downloading the same URL");
+            waiting = true;
+        }
+        cancel::until_cancelled(tokio::time::sleep(Duration::from_millis(500))).await?;
+    }
+    Ok(lock)
+}
+
+fn validator_path_for(part_path: &Path) -> PathBuf {
+    part_path.with_extension("part.validator")
+}
+
+pub fn build_client(proxy: Option<&ProxyConfig>) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        let proxy_url = proxy.url_with_auth()?;
+        builder = builder.proxy(reqwest::Proxy::all(&proxy_url).map_err(|e| format!("Invalid proxy: {}", e))?);
+    }
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+// Roughly what a job would download: the trimmed section of a YouTube video
+// at the quality jobs download, or all of a direct link. None when the site
+// doesn't say.
+pub async fn estimate_size(source: &str, start_time: &str, end_time: &str, settings: &Settings) -> Result<Option<u64>, String> {
+    if source.contains("youtube.com") || source.contains("youtu.be") {
+        let start_seconds = timecode::time_to_seconds(start_time)?;
+        let end_seconds = timecode::time_to_seconds(end_time)?;
+        ytdlp::estimate_section_size(source, start_seconds, end_seconds, settings).await
+    } else if source.starts_with("http") {
+        let client = build_client(settings.proxy.as_ref())?;
+        Ok(remaining_bytes(&client, source).await)
+    } else {
+        Err("Only links are downloaded; local files have no download cost".to_string())
+    }
+}
+
+// What downloading `url` would still transfer: the server's Content-Length
+// less what an earlier attempt left in the partial file. None when the
+// server doesn't say.
+async fn remaining_bytes(client: &reqwest::Client, url: &str) -> Option<u64> {
+    let response = client.head(url).send().await.ok()?.error_for_status().ok()?;
+    let total: u64 = response.headers().get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()?;
+    let partial = tokio::fs::metadata(part_path_for(url)).await.map(|m| m.len()).unwrap_or(0);
+    Some(total.saturating_sub(partial))
+}
+
+pub async fn download_video_from_url(
+    client: &reqwest::Client,
+    url: &str,
+    output_path: &Path,
+    options: &DownloadOptions,
+) -> Result<(), String> {
+    let policy = &options.retry;
+    let part_path = part_path_for(url);
+    tokio::fs::create_dir_all(partial_download_dir())
+        .await
+        .map_err(|e| format!("Failed to create partial download directory: {}", e))?;
+    let _lock = lock_part(&part_path).await?;
+
+    let mut throttle = options.max_bytes_per_sec.map(Throttle::new);
+    let mut attempt = 0;
+
+    loop {
+        // Dropping the attempt leaves what it received in the partial file
+        let attempt_result =
+            download_attempt(client, url, &part_path, throttle.as_mut(), options.listener.as_ref());
+        match cancel::until_cancelled(attempt_result).await? {
+            Ok(()) => break,
+            Err(AttemptError::Permanent(e)) => return Err(e),
+            Err(AttemptError::Transient(e)) => {
+                attempt += 1;
+                if attempt > policy.max_retries {
+                    return Err(format!("{} (gave up after {} retries)", e, policy.max_retries));
+                }
+                let delay = policy.backoff_for(attempt);
+                log::warn!("Download attempt {} failed: {}. Retrying in {:?}", attempt, privacy::scrub(&e), delay);
+                cancel::until_cancelled(tokio::time::sleep(delay)).await?;
+            }
+        }
+    }
+
+    move_into_place(&part_path, output_path).await?;
+    let _ = tokio::fs::remove_file(validator_path_for(&part_path)).await;
+    Ok(())
+}
+
+async fn download_attempt(
+    client: &reqwest::Client,
+    url: &str,
+    part_path: &Path,
+    mut throttle: Option<&mut Throttle>,
+    listener: Option<&DownloadListener>,
+) -> Result<(), AttemptError> {
+    let resume_from = tokio::fs::metadata(part_path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let validator_path = validator_path_for(part_path);
+    let validator = tokio::fs::read_to_string(&validator_path).await.ok();
+
+    let mut request = client.get(url);
+    if resume_from > 0 {
+        request = request.header(RANGE, format!("bytes={}-", resume_from));
+        // If-Range makes the server send the whole file again if it changed
+        // since the partial was written, instead of splicing two versions.
+        if let Some(validator) = &validator {
+            request = request.header(IF_RANGE, validator.trim());
+        }
+    }
+
+    let response = request.send().await.map_err(|e| {
+        if e.is_builder() {
+            AttemptError::Permanent(format!("Invalid download URL: {}", e))
+        } else {
+            AttemptError::Transient(format!("Failed to fetch URL: {}", e))
+        }
+    })?;
+
+    let status = response.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && resume_from > 0 {
+        // The partial file may already hold every byte the server has, which
+        // it says as "bytes */<length>". Any other length means the file
+        // changed or the partial is damaged, so it is downloaded again.
+        let server_len = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("bytes */"))
+            .and_then(|len| len.trim().parse::<u64>().ok());
+        if server_len == Some(resume_from) {
+            return Ok(());
+        }
+        let _ = tokio::fs::remove_file(part_path).await;
+        let _ = tokio::fs::remove_file(&validator_path).await;
+        return Err(AttemptError::Transient(format!(
+            "The partial download ({} bytes) doesn't match the file on the server; starting over",
+            resume_from
+        )));
+    }
+    if !status.is_success() {
+        let message = format!("Failed to download video: HTTP status {}", status);
+        return if status.is_server_error()
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+        {
+            Err(AttemptError::Transient(message))
+        } else {
+            Err(AttemptError::Permanent(message))
+        };
+    }
+
+    let resuming = status == StatusCode::PARTIAL_CONTENT;
+    if !resuming {
+        let new_validator = response
+            .headers()
+            .get(ETAG)
+            .or_else(|| response.headers().get(LAST_MODIFIED))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        match new_validator {
+            Some(value) => {
+                let _ = tokio::fs::write(&validator_path, value).await;
+            }
+            None => {
+                let _ = tokio::fs::remove_file(&validator_path).await;
+            }
+        }
+    }
+
+    let expected_len = response.content_length();
+    let already_written = if resuming { resume_from } else { 0 };
+    let progress = listener.cloned().map(|listener| WriteProgress {
+        listener,
+        downloaded_bytes: already_written,
+        total_bytes: expected_len.map(|len| already_written + len),
+    });
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resuming)
+        .truncate(!resuming)
+        .open(part_path)
+        .await
+        .map_err(|e| AttemptError::Permanent(format!("Failed to create temporary file: {}", e)))?;
+
+    // The network and the disk run side by side, with at most
+    // CHUNKS_IN_FLIGHT chunks between them: a slow disk holds the download
+    // back rather than letting chunks pile up in memory
+    let (sender, receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    let writer = tokio::spawn(write_chunks(file, receiver, progress));
+
+    let mut received: u64 = 0;
+    let mut stream = response.bytes_stream();
+    let mut stream_error = None;
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                stream_error = Some(AttemptError::Transient(format!("Error while downloading chunk: {}", e)));
+                break;
+            }
+        };
+        let len = chunk.len();
+        received += len as u64;
+        // Only fails once the writer has stopped, whose error is reported below
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+        if let Some(throttle) = throttle.as_deref_mut() {
+            throttle.consume(len).await;
+        }
+    }
+    // Whatever arrived is written out even when the connection dropped, so
+    // the next attempt resumes after it
+    drop(sender);
+    writer
+        .await
+        .map_err(|e| AttemptError::Permanent(format!("Failed to write chunk to file: {}", e)))??;
+    if let Some(e) = stream_error {
+        return Err(e);
+    }
+
+    if let Some(expected) = expected_len {
+        if received < expected {
+            return Err(AttemptError::Transient(format!(
+                "Connection closed early ({} of {} bytes)",
+                received, expected
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// Where the writer reports the bytes it has written
+struct WriteProgress {
+    listener: DownloadListener,
+    downloaded_bytes: u64,
+    total_bytes: Option<u64>,
+}
+
+impl WriteProgress {
+    fn report(&self) {
+        self.listener.notify(DownloadProgress {
+            downloaded_bytes: self.downloaded_bytes,
+            total_bytes: self.total_bytes,
+        });
+    }
+}
+
+// Batches chunks into large writes, and flushes every FLUSH_EVERY bytes so
+// an interrupted download keeps most of what it received. Progress is
+// reported once per WRITE_BUFFER bytes, about as often as the buffer
+// reaches the file, and once more when everything is flushed.
+async fn write_chunks(
+    file: tokio::fs::File,
+    mut receiver: mpsc::Receiver<impl AsRef<[u8]>>,
+    mut progress: Option<WriteProgress>,
+) -> Result<(), AttemptError> {
+    let write_error = |e: std::io::Error| AttemptError::Permanent(format!("Failed to write chunk to file: {}", e));
+    let mut writer = BufWriter::with_capacity(WRITE_BUFFER, file);
+    let mut unflushed: u64 = 0;
+    let mut unreported: u64 = 0;
+    if let Some(progress) = &progress {
+        progress.report();
+    }
+    while let Some(chunk) = receiver.recv().await {
+        let chunk = chunk.as_ref();
+        writer.write_all(chunk).await.map_err(write_error)?;
+        unflushed += chunk.len() as u64;
+        unreported += chunk.len() as u64;
+        if unflushed >= FLUSH_EVERY {
+            writer.flush().await.map_err(write_error)?;
+            unflushed = 0;
+        }
+        if let Some(progress) = progress.as_mut().filter(|_| unreported >= WRITE_BUFFER as u64) {
+            progress.downloaded_bytes += unreported;
+            unreported = 0;
+            progress.report();
+        }
+    }
+    writer.flush().await.map_err(write_error)?;
+    if let Some(progress) = progress.as_mut() {
+        progress.downloaded_bytes += unreported;
+        progress.report();
+    }
+    Ok(())
+}
+
+async fn move_into_place(part_path: &Path, output_path: &Path) -> Result<(), String> {
+    if tokio::fs::rename(part_path, output_path).await.is_ok() {
+        return Ok(());
+    }
+    // rename fails across filesystems (e.g. a tmpfs temp dir); fall back to copy
+    tokio::fs::copy(part_path, output_path)
+        .await
+        .map_err(|e| format!("Failed to move downloaded file into place: {}", e))?;
+    let _ = tokio::fs::remove_file(part_path).await;
+    Ok(())
+}
+
+// Plain (non-resumable) download used for tool binaries, reporting
+// (downloaded, total) bytes as it goes.
+pub async fn download_with_progress(
+    client: &reqwest::Client,
+    url: &str,
+    output_path: &Path,
+    on_progress: &impl Fn(u64, Option<u64>),
+) -> Result<(), String> {
+    // Reporting every chunk floods the event channel; 1 MiB steps are plenty.
+    const REPORT_EVERY: u64 = 1024 * 1024;
+
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to download {}: HTTP status {}", url, response.status()));
+    }
+
+    let total = response.content_length();
+    let mut file = tokio::fs::File::create(output_path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", output_path.display(), e))?;
+    let mut downloaded: u64 = 0;
+    let mut last_report: u64 = 0;
+    let mut stream = response.bytes_stream();
+
+    on_progress(0, total);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Error while downloading chunk: {}", e))?;
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write chunk to file: {}", e))?;
+        downloaded += chunk.len() as u64;
+        if downloaded - last_report >= REPORT_EVERY {
+            last_report = downloaded;
+            on_progress(downloaded, total);
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write chunk to file: {}", e))?;
+    on_progress(downloaded, total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            initial_backoff_secs: 1.0,
+            max_backoff_secs: 30.0,
+        };
+        let longest = |attempt| policy.jittered_backoff(attempt, 1.0).as_secs_f64();
+        assert_eq!(longest(0), 1.0);
+        assert_eq!(longest(1), 1.0);
+        assert_eq!(longest(2), 2.0);
+        assert_eq!(longest(5), 16.0);
+        assert_eq!(longest(6), 30.0);
+        assert_eq!(longest(u32::MAX), 30.0);
+
+        let never = RetryPolicy {
+            initial_backoff_secs: 0.0,
+            ..policy.clone()
+        };
+        assert_eq!(never.backoff_for(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn backoff_jitter_stays_between_half_and_all_of_the_wait() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.jittered_backoff(3, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.jittered_backoff(3, 0.5), Duration::from_secs(3));
+        for attempt in 1..=10 {
+            let full = policy.jittered_backoff(attempt, 1.0);
+            for _ in 0..100 {
+                let wait = policy.backoff_for(attempt);
+                assert!(wait >= full / 2 && wait <= full, "attempt {}: {:?} of {:?}", attempt, wait, full);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn throttle_allows_a_second_of_burst_then_holds_to_the_rate() {
+        let mut throttle = Throttle::new(10_000);
+        let started = Instant::now();
+        throttle.consume(10_000).await;
+        assert!(started.elapsed() < Duration::from_millis(100));
+
+        // 2,000 bytes over the bucket is 0.2 s of debt at 10,000 bytes/s
+        let started = Instant::now();
+        throttle.consume(2_000).await;
+        let waited = started.elapsed();
+        assert!(waited >= Duration::from_millis(190), "{:?}", waited);
+        assert!(waited < Duration::from_secs(1), "{:?}", waited);
+    }
+
+    #[tokio::test]
+    async fn throttle_refills_no_more_than_a_second() {
+        let mut throttle = Throttle::new(10_000);
+        throttle.last_refill -= Duration::from_secs(5);
+        throttle.consume(0).await;
+        assert!(throttle.tokens <= 10_000.0);
+
+        // A zero limit is treated as one byte per second
+        assert_eq!(Throttle::new(0).rate, 1.0);
+    }
+
+    #[tokio::test]
+    async fn writer_reports_progress_as_it_writes() {
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let progress = WriteProgress {
+            listener: DownloadListener::new(move |progress| seen.lock().unwrap().push(progress.downloaded_bytes)),
+            downloaded_bytes: 100,
+            total_bytes: Some(100 + 5 * WRITE_BUFFER as u64 / 2),
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("video.part");
+        let file = tokio::fs::File::create(&path).await.unwrap();
+
+        let (sender, receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+        let writer = tokio::spawn(write_chunks(file, receiver, Some(progress)));
+        for _ in 0..10 {
+            sender.send(vec![0u8; WRITE_BUFFER / 4]).await.unwrap();
+        }
+        drop(sender);
+        writer.await.unwrap().map_err(|_| "write failed").unwrap();
+
+        let written = 10 * WRITE_BUFFER as u64 / 4;
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), written);
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.first(), Some(&100));
+        assert_eq!(reports.last(), Some(&(100 + written)));
+        // One report per full buffer, between the first and the last
+        assert_eq!(reports.len(), 4);
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", reports);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use age::secrecy::Secret;
+
+const MIN_PASSPHRASE_LEN: usize = 8;
+
+// Held only for the duration of a job and never serialized, so it can't end
+// up in settings, history or an exported job spec.
+#[derive(Clone)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Result<Self, String> {
+        let passphrase = passphrase.into();
+        if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+            return Err(format!("The passphrase must be at least {} characters", MIN_PASSPHRASE_LEN));
+        }
+        Ok(Self(passphrase))
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(<redacted>)")
+    }
+}
+
+// Writes `<output_dir>/<file name>.age`, encrypted with age's passphrase
+// (scrypt) mode, so it opens with the standard `age -d` / rage tools.
+pub fn encrypt_file(input: &Path, output_dir: &Path, passphrase: &Passphrase) -> Result<PathBuf, String> {
+    let file_name = input
+        .file_name()
+        .ok_or_else(|| format!("Not a file: {}", input.display()))?;
+    let output_path = output_dir.join(format!("{}.age", file_name.to_string_lossy()));
+
+    let result = (|| -> Result<(), String> {
+        let mut reader = BufReader::new(File::open(input).map_err(|e| format!("Failed to open {}: {}", input.display(), e))?);
+        let output = File::create(&output_path).map_err(|e| format!("Failed to create {}: {}", output_path.display(), e))?;
+        let encryptor = age::Encryptor::with_user_passphrase(Secret::new(passphrase.0.clone()));
+        let mut writer = encryptor
+            .wrap_output(BufWriter::new(output))
+            .map_err(|e| format!("Failed to start encryption: {}", e))?;
+        std::io::copy(&mut reader, &mut writer).map_err(|e| format!("Failed to encrypt {}: {}", input.display(), e))?;
+        writer
+            .finish()
+            .and_then(|mut output| output.flush())
+            .map_err(|e| format!("Failed to finish encrypting {}: {}", input.display(), e))
+    })();
+
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&output_path);
+        return Err(e);
+    }
+    Ok(output_path)
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{probe, ytdlp};
+
+// What went wrong, for callers that react differently to different failures
+// (offering to install FFmpeg, highlighting the time inputs, ...) without
+// matching on message text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorCode {
+    FfmpegMissing,
+    YtDlpMissing,
+    DownloadFailed,
+    DrmProtected,
+    EncodeFailed,
+    InvalidRange,
+    InvalidOptions,
+    // The local FFmpeg build lacks a filter an option needs
+    UnsupportedFeature,
+    // A large download on a metered connection; run again with
+    // allow_metered_download once the user agrees
+    ConfirmationRequired,
+    // The temp folder's drive is too small for the download
+    NotEnoughSpace,
+    FileNotFound,
+    // ffprobe ran but couldn't read a source
+    ProbeFailed,
+    // No temp folder could be created for intermediate files
+    TempDirUnavailable,
+    DuplicateClip,
+    // The output's name is taken and the job's collision policy doesn't
+    // allow renaming or replacing it
+    OutputExists,
+    // Stopped through the job's cancellation token
+    Cancelled,
+    Other,
+}
+
+impl ErrorCode {
+    pub fn error(self, message: impl Into<String>) -> TrimError {
+        TrimError {
+            code: self,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrimError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl TrimError {
+    // yt-dlp reports a missing binary the same way for every call
+    pub fn download(message: String) -> Self {
+        if message == ytdlp::NOT_FOUND_ERROR {
+            ErrorCode::YtDlpMissing.error(message)
+        } else {
+            ErrorCode::DownloadFailed.error(message)
+        }
+    }
+
+    // The same for ffprobe, which comes with FFmpeg
+    pub fn probe(message: String) -> Self {
+        if message == probe::NOT_FOUND_ERROR {
+            ErrorCode::FfmpegMissing.error(message)
+        } else {
+            ErrorCode::ProbeFailed.error(message)
+        }
+    }
+}
+
+impl fmt::Display for TrimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TrimError {}
+
+// The helpers the job is built from still report plain messages
+impl From<String> for TrimError {
+    fn from(message: String) -> Self {
+        ErrorCode::Other.error(message)
+    }
+}
+
+impl From<&str> for TrimError {
+    fn from(message: &str) -> Self {
+        ErrorCode::Other.error(message)
+    }
+}
+
+impl From<TrimError> for String {
+    fn from(error: TrimError) -> Self {
+        error.message
+    }
+}
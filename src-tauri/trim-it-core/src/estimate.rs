@@ -0,0 +1,173 @@
+use serde::Serialize;
+
+use crate::filters::{self, ClipRange};
+use crate::job::{CaptionMode, Denoise, SubtitleMode, TrimOptions, VideoCodec, Vocals};
+use crate::probe::ProbeResult;
+
+pub const BEST_QUALITY_AUDIO_KBPS: u32 = 256;
+// Leaves more of a small size budget to the picture
+pub const TARGET_SIZE_AUDIO_KBPS: u32 = 128;
+// Below this the picture falls apart, so a size target is refused instead
+const MIN_TARGET_VIDEO_KBPS: f64 = 150.0;
+
+// Rough rates for the estimates below: bits per pixel at each codec's
+// default quality on typical footage, and pixels encoded per second per CPU
+// core. Both vary a lot with the content and machine.
+fn codec_rates(codec: VideoCodec) -> (f64, f64) {
+    match codec {
+        VideoCodec::H264 => (0.1, 4_000_000.0),
+        VideoCodec::H265 => (0.05, 1_200_000.0),
+        // The GPU does the work, so this is per machine rather than per core
+        VideoCodec::HevcNvenc => (0.07, 250_000_000.0),
+        VideoCodec::SvtAv1 => (0.045, 3_000_000.0),
+        VideoCodec::AomAv1 => (0.04, 500_000.0),
+        // Intra-only at fixed quality, around 150-220 Mbit/s for 1080p30
+        VideoCodec::ProRes => (2.4, 40_000_000.0),
+        VideoCodec::Dnxhr => (3.5, 40_000_000.0),
+    }
+}
+// How much longer an encode takes with each denoiser in front of it
+fn denoise_time_factor(denoise: Denoise) -> f64 {
+    match denoise {
+        Denoise::Off => 1.0,
+        Denoise::Light | Denoise::Medium => 1.2,
+        Denoise::Strong => 6.0,
+    }
+}
+// A stream copy is bound by disk speed
+const COPY_BYTES_PER_SEC: f64 = 200_000_000.0;
+// The first pass of a two-pass encode runs faster than the second
+const TWO_PASS_TIME_FACTOR: f64 = 1.5;
+const CONTAINER_OVERHEAD: f64 = 1.02;
+
+// What an export is expected to produce, for warning before a long encode or
+// one that won't fit. Sizes and times are approximate.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputEstimate {
+    pub duration_seconds: f64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub reencode: bool,
+    pub two_pass: bool,
+    pub video_kbps: f64,
+    pub audio_kbps: f64,
+    pub bytes: u64,
+    pub encode_seconds: f64,
+}
+
+// 16-bit 48 kHz stereo
+pub const PCM_AUDIO_KBPS: u32 = 1536;
+
+pub fn encoded_audio_kbps(options: &TrimOptions) -> u32 {
+    if options.video_codec.is_some_and(VideoCodec::is_intermediate) {
+        PCM_AUDIO_KBPS
+    } else if options.max_size_mb.is_some() {
+        TARGET_SIZE_AUDIO_KBPS
+    } else {
+        BEST_QUALITY_AUDIO_KBPS
+    }
+}
+
+// Video bitrate that fits the clip in `max_size_mb` alongside the audio,
+// leaving a few percent for the container
+pub fn target_video_kbps(max_size_mb: f64, duration: f64, audio_kbps: u32) -> Result<u32, String> {
+    let total_kbps = max_size_mb * 8_000.0 * 0.97 / duration;
+    let video_kbps = total_kbps - audio_kbps as f64;
+    if video_kbps < MIN_TARGET_VIDEO_KBPS {
+        return Err(format!(
+            "A {:.0}s clip can't fit in {} MB at a watchable quality. Shorten the range or raise the size limit.",
+            duration, max_size_mb
+        ));
+    }
+    Ok(video_kbps.floor() as u32)
+}
+
+// Predicts an export of `clip_duration` seconds from `start_seconds` of the
+// probed source, following the same copy/re-encode decisions as the trim
+pub fn estimate_output(
+    source: &ProbeResult,
+    start_seconds: f64,
+    clip_duration: f64,
+    ratio: &str,
+    options: &TrimOptions,
+) -> Result<OutputEstimate, String> {
+    let range = ClipRange {
+        start: start_seconds,
+        end: start_seconds + clip_duration,
+        source_offset: 0.0,
+    };
+    let source_size = source.video_size();
+    let video_filters = filters::video_filters(ratio, options, source_size, range, None)?;
+    let audio_filters = filters::audio_filters(options, range)?;
+    let duration = clip_duration / options.speed() * options.passes() as f64;
+
+    let burns_text = options.captions == CaptionMode::Burn || options.subtitles.mode == SubtitleMode::Burn;
+    let variable_frame_rate = source.video_stream().is_some_and(|s| s.is_variable_frame_rate());
+    let hdr = source.video_stream().is_some_and(|s| s.hdr_format().is_some());
+    let reencode = !video_filters.chain.is_empty()
+        || burns_text
+        || options.max_size_mb.is_some()
+        || options.video_codec.is_some()
+        || (options.constant_frame_rate && variable_frame_rate)
+        || (options.tone_map_hdr && hdr);
+    let codec = options.video_codec.unwrap_or_default();
+    let (bits_per_pixel, pixels_per_core_sec) = codec_rates(codec);
+    let (width, height) = video_filters.output_size.or(source_size).unzip();
+    let frame_rate = source.video_stream().and_then(|s| s.frame_rate()).unwrap_or(30.0);
+    let pixels_per_sec = width.zip(height).map_or(0.0, |(w, h)| w as f64 * h as f64) * frame_rate;
+
+    let copies_audio = !reencode
+        && audio_filters.is_empty()
+        && options.replacement_audio.is_none()
+        && options.background_music.is_none()
+        && options.vocals == Vocals::Keep
+        && !options.normalize_loudness;
+    let source_audio_kbps = source
+        .streams
+        .iter()
+        .find(|s| s.codec_type.as_deref() == Some("audio"))
+        .map_or(0.0, |s| s.bit_rate_kbps().unwrap_or(BEST_QUALITY_AUDIO_KBPS as f64));
+    let audio_kbps = if options.mute {
+        0.0
+    } else if copies_audio {
+        source_audio_kbps
+    } else {
+        encoded_audio_kbps(options) as f64
+    };
+
+    let video_kbps = match options.max_size_mb {
+        Some(max_size_mb) => target_video_kbps(max_size_mb, duration, audio_kbps as u32)? as f64,
+        None if reencode => pixels_per_sec * bits_per_pixel / 1000.0,
+        None => source
+            .video_stream()
+            .and_then(|s| s.bit_rate_kbps())
+            .or_else(|| source.bytes_per_second().map(|rate| rate * 8.0 / 1000.0 - source_audio_kbps))
+            .unwrap_or(0.0)
+            .max(0.0),
+    };
+
+    let bytes = (video_kbps + audio_kbps) * 1000.0 / 8.0 * duration * CONTAINER_OVERHEAD;
+    let two_pass = options.max_size_mb.is_some() && codec.supports_two_pass();
+    let encode_seconds = if reencode {
+        let cores = match codec {
+            VideoCodec::HevcNvenc => 1.0,
+            _ => std::thread::available_parallelism().map_or(4, |n| n.get()) as f64,
+        };
+        let seconds = pixels_per_sec * duration / (pixels_per_core_sec * cores) * denoise_time_factor(options.denoise);
+        if two_pass { seconds * TWO_PASS_TIME_FACTOR } else { seconds }
+    } else {
+        bytes / COPY_BYTES_PER_SEC
+    };
+
+    Ok(OutputEstimate {
+        duration_seconds: duration,
+        width,
+        height,
+        reencode,
+        two_pass,
+        video_kbps,
+        audio_kbps,
+        bytes: bytes.round() as u64,
+        encode_seconds,
+    })
+}
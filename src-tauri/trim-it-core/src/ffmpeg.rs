@@ -0,0 +1,426 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use ffmpeg_sidecar::child::FfmpegChild;
+use ffmpeg_sidecar::command::FfmpegCommand;
+use ffmpeg_sidecar::event::FfmpegEvent;
+use ffmpeg_sidecar::iter::FfmpegIterator;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+
+use crate::{blocking, cancel, download, joblog, privacy};
+
+// Managed builds are pinned so every install runs the same FFmpeg, instead of
+// whatever "latest" happened to be on the day the app was first started.
+pub const PINNED_FFMPEG_VERSION: &str = "7.0.2";
+
+// Releases that can be installed and pinned instead, e.g. for a filter the
+// default one lacks. Each is kept in its own folder, so switching back and
+// forth doesn't download again.
+pub const AVAILABLE_BUILDS: &[&str] = &["7.1", PINNED_FFMPEG_VERSION, "6.1.1"];
+
+// Root of the managed FFmpeg installs, set once at startup from the app data dir.
+static MANAGED_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_managed_dir(dir: PathBuf) {
+    *MANAGED_DIR.write().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+}
+
+// A preinstalled FFmpeg chosen in settings, e.g. by IT on managed machines
+static CONFIGURED_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_configured_dir(dir: Option<PathBuf>) {
+    *CONFIGURED_DIR.write().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+fn configured_binary(name: &str) -> Option<PathBuf> {
+    CONFIGURED_DIR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|dir| dir.join(binary_name(name)))
+        .filter(|path| path.exists())
+}
+
+pub fn configured_ffmpeg_path() -> Option<PathBuf> {
+    configured_binary("ffmpeg")
+}
+
+// The managed release pinned in settings, when it isn't the default one
+static SELECTED_VERSION: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn set_managed_version(version: Option<String>) {
+    *SELECTED_VERSION.write().unwrap_or_else(|e| e.into_inner()) = version;
+}
+
+pub fn managed_version() -> String {
+    SELECTED_VERSION
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| PINNED_FFMPEG_VERSION.to_string())
+}
+
+// Which FFmpeg a job runs with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FfmpegBuild {
+    // The configured FFmpeg, then the managed build, then PATH
+    #[default]
+    Auto,
+    // The configured FFmpeg or the one on PATH, never the managed build
+    System,
+    // The pinned managed build
+    Managed,
+}
+
+tokio::task_local! {
+    static JOB_BUILD: FfmpegBuild;
+}
+
+// Runs `job` with every FFmpeg and ffprobe it starts taken from `build`
+pub async fn scope<F: Future>(build: FfmpegBuild, job: F) -> F::Output {
+    JOB_BUILD.scope(build, job).await
+}
+
+// The same for blocking work running on behalf of a job
+pub fn sync_scope<R>(build: FfmpegBuild, work: impl FnOnce() -> R) -> R {
+    JOB_BUILD.sync_scope(build, work)
+}
+
+pub fn current_build() -> FfmpegBuild {
+    JOB_BUILD.try_with(|build| *build).unwrap_or_default()
+}
+
+fn managed_dir() -> Option<PathBuf> {
+    MANAGED_DIR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn managed_binary_dir() -> Option<PathBuf> {
+    managed_dir().map(|dir| dir.join(managed_version()))
+}
+
+fn binary_name(name: &str) -> String {
+    format!("{}{}", name, std::env::consts::EXE_SUFFIX)
+}
+
+pub fn managed_ffmpeg_path() -> Option<PathBuf> {
+    managed_binary_dir()
+        .map(|dir| dir.join(binary_name("ffmpeg")))
+        .filter(|path| path.exists())
+}
+
+// A configured FFmpeg wins, then the managed build; PATH is only the
+// fallback when the download failed or hasn't happened yet.
+pub fn ffmpeg_path() -> PathBuf {
+    binary_path("ffmpeg")
+}
+
+// ffprobe is installed next to ffmpeg, so it follows the same rule
+pub fn ffprobe_path() -> PathBuf {
+    binary_path("ffprobe")
+}
+
+fn binary_path(name: &str) -> PathBuf {
+    let managed = || {
+        managed_binary_dir()
+            .map(|dir| dir.join(binary_name(name)))
+            .filter(|path| path.exists())
+    };
+    let found = match current_build() {
+        FfmpegBuild::Auto => configured_binary(name).or_else(managed),
+        FfmpegBuild::System => configured_binary(name),
+        FfmpegBuild::Managed => managed(),
+    };
+    found.unwrap_or_else(|| PathBuf::from(binary_name(name)))
+}
+
+pub fn new_command() -> FfmpegCommand {
+    FfmpegCommand::new_with_path(ffmpeg_path())
+}
+
+// The archives making up a build. The macOS builds package ffmpeg and
+// ffprobe separately; elsewhere one archive has both.
+fn download_urls(v: &str) -> Result<Vec<String>, String> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("windows", "x86_64") => Ok(vec![format!(
+            "https://www.gyan.dev/ffmpeg/builds/packages/ffmpeg-{v}-essentials_build.zip"
+        )]),
+        ("macos", _) => Ok(vec![
+            format!("https://evermeet.cx/ffmpeg/ffmpeg-{v}.zip"),
+            format!("https://evermeet.cx/ffmpeg/ffprobe-{v}.zip"),
+        ]),
+        ("linux", "x86_64") => Ok(vec![format!(
+            "https://johnvansickle.com/ffmpeg/old-releases/ffmpeg-{v}-amd64-static.tar.xz"
+        )]),
+        ("linux", "aarch64") => Ok(vec![format!(
+            "https://johnvansickle.com/ffmpeg/old-releases/ffmpeg-{v}-arm64-static.tar.xz"
+        )]),
+        (os, arch) => Err(format!("No managed FFmpeg build is available for {} {}", os, arch)),
+    }
+}
+
+// The SHA-256 the publisher lists for an archive, from the ".sha256" file
+// next to it
+async fn published_checksum(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let listing = client
+        .get(format!("{}.sha256", url))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch FFmpeg checksum: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to fetch FFmpeg checksum: {}", e))?;
+    listing
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_ascii_lowercase)
+        .ok_or_else(|| format!("No checksum published for {}", url))
+}
+
+// Downloads the pinned build into the app data dir and unpacks it there,
+// reporting (downloaded, total) bytes as it goes.
+pub async fn provision_managed_ffmpeg(
+    client: &reqwest::Client,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    install_build(client, &managed_version(), on_progress).await
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedBuild {
+    pub version: String,
+    pub installed: bool,
+    // The one managed jobs run with
+    pub pinned: bool,
+    // External libraries an installed build was compiled with, e.g.
+    // "libass" or "libvmaf"; empty until installed
+    pub libraries: Vec<String>,
+}
+
+pub async fn list_available_builds() -> Vec<ManagedBuild> {
+    let pinned = managed_version();
+    let mut builds = Vec::new();
+    for version in AVAILABLE_BUILDS {
+        let binary = managed_dir()
+            .map(|dir| dir.join(version).join(binary_name("ffmpeg")))
+            .filter(|path| path.exists());
+        let libraries = match &binary {
+            Some(binary) => build_libraries(binary).await,
+            None => Vec::new(),
+        };
+        builds.push(ManagedBuild {
+            version: version.to_string(),
+            installed: binary.is_some(),
+            pinned: *version == pinned,
+            libraries,
+        });
+    }
+    builds
+}
+
+// From the "--enable-libass --enable-libvmaf ..." configuration line of
+// `ffmpeg -version`
+async fn build_libraries(binary: &Path) -> Vec<String> {
+    let Ok(output) = Command::new(binary).arg("-version").output().await else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.starts_with("configuration:"))
+        .map(|line| {
+            line.split_whitespace()
+                .filter_map(|option| option.strip_prefix("--enable-"))
+                .filter(|library| library.starts_with("lib"))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Downloads one of AVAILABLE_BUILDS next to the others. Doesn't pin it.
+pub async fn install_build(
+    client: &reqwest::Client,
+    version: &str,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    if !AVAILABLE_BUILDS.contains(&version) {
+        return Err(format!(
+            "FFmpeg {} isn't available; choose one of {}",
+            version,
+            AVAILABLE_BUILDS.join(", ")
+        ));
+    }
+    let binary_dir = managed_dir()
+        .ok_or("Managed FFmpeg directory is not configured")?
+        .join(version);
+    let urls = download_urls(version)?;
+
+    tokio::fs::create_dir_all(&binary_dir)
+        .await
+        .map_err(|e| format!("Failed to create FFmpeg directory: {}", e))?;
+
+    for url in &urls {
+        let expected = published_checksum(client, url).await?;
+        let archive_name = url.rsplit('/').next().unwrap_or("ffmpeg-archive");
+        let archive_path = binary_dir.join(archive_name);
+        download::download_with_progress(client, url, &archive_path, &on_progress).await?;
+
+        let bytes = tokio::fs::read(&archive_path)
+            .await
+            .map_err(|e| format!("Failed to read downloaded FFmpeg: {}", e))?;
+        let actual: String = Sha256::digest(&bytes).iter().map(|b| format!("{:02x}", b)).collect();
+        if actual != expected {
+            let _ = tokio::fs::remove_file(&archive_path).await;
+            return Err(format!("Downloaded {} failed checksum verification", archive_name));
+        }
+
+        let unpack_archive = archive_path.clone();
+        let unpack_dir = binary_dir.clone();
+        let ffprobe_only = archive_name.starts_with("ffprobe");
+        tokio::task::spawn_blocking(move || {
+            if ffprobe_only {
+                unpack_single_binary(&unpack_archive, &unpack_dir, "ffprobe")
+            } else {
+                ffmpeg_sidecar::download::unpack_ffmpeg(&unpack_archive, &unpack_dir).map_err(|e| e.to_string())
+            }
+        })
+        .await
+        .map_err(|e| format!("FFmpeg unpack task failed: {}", e))?
+        .map_err(|e| format!("Failed to unpack FFmpeg: {}", e))?;
+        let _ = tokio::fs::remove_file(&archive_path).await;
+    }
+
+    for name in ["ffmpeg", "ffprobe"] {
+        if !binary_dir.join(binary_name(name)).exists() {
+            return Err(format!("FFmpeg archives did not contain an {} binary", name));
+        }
+    }
+    Ok(binary_dir.join(binary_name("ffmpeg")))
+}
+
+// Extracts one binary from a zip that holds only it, e.g. evermeet's ffprobe
+fn unpack_single_binary(archive_path: &Path, binary_dir: &Path, name: &str) -> Result<(), String> {
+    let file = std::fs::File::open(archive_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut entry = archive
+        .by_name(&binary_name(name))
+        .map_err(|_| format!("{} is missing from the archive", name))?;
+    let binary_path = binary_dir.join(binary_name(name));
+    let mut out = std::fs::File::create(&binary_path).map_err(|e| e.to_string())?;
+    std::io::copy(&mut entry, &mut out).map_err(|e| e.to_string())?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// FFmpeg errors that mean a stream copy can't work but a re-encode will,
+// with the reason in words for the user
+const STREAM_COPY_FAILURES: &[(&str, &str)] = &[
+    ("could not find tag for codec", "the source's codec can't be stored in this format"),
+    ("not currently supported in container", "the source's codec can't be stored in this format"),
+    ("non monotonically increasing dts", "the source has broken timestamps"),
+    ("non-monotonous dts", "the source has broken timestamps"),
+    ("negative dts", "the source has negative timestamps"),
+    ("pts < dts", "the source has broken timestamps"),
+    ("timestamps are unset", "the source is missing timestamps"),
+];
+
+pub fn stream_copy_failure(errors: &[String]) -> Option<&'static str> {
+    errors.iter().find_map(|error| {
+        let error = error.to_ascii_lowercase();
+        STREAM_COPY_FAILURES
+            .iter()
+            .find(|(pattern, _)| error.contains(pattern))
+            .map(|(_, reason)| *reason)
+    })
+}
+
+// Runs an FFmpeg command to completion and returns every log line it wrote,
+// for analysis passes whose results are printed by filters to stderr.
+pub async fn run(mut command: FfmpegCommand) -> Result<Vec<String>, String> {
+    record_command(&mut command);
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+    drain(&mut child, collect_logs).await?
+}
+
+// Reads the child's events with `handle` off the async runtime, since that
+// blocks until FFmpeg exits. FFmpeg is killed if the job is cancelled first.
+pub(crate) async fn drain<T: Send + 'static>(
+    child: &mut FfmpegChild,
+    handle: impl FnOnce(FfmpegIterator) -> T + Send + 'static,
+) -> Result<T, String> {
+    let events = child.iter().map_err(|e| e.to_string())?;
+    let token = cancel::current();
+    let drained = blocking::run(move || handle(events));
+    tokio::pin!(drained);
+    tokio::select! {
+        result = &mut drained => result,
+        _ = token.cancelled() => {
+            let _ = child.kill();
+            let _ = drained.await;
+            let _ = child.wait();
+            Err(cancel::CANCELLED.to_string())
+        }
+    }
+}
+
+fn collect_logs(events: FfmpegIterator) -> Result<Vec<String>, String> {
+    let mut success = false;
+    let mut logs: Vec<String> = Vec::new();
+    let mut ffmpeg_errors: Vec<String> = Vec::new();
+    for event in events {
+        record_event(&event);
+        match event {
+            FfmpegEvent::Log(_, line) => logs.push(line),
+            FfmpegEvent::Error(e) => ffmpeg_errors.push(e),
+            FfmpegEvent::Done => success = true,
+            _ => {}
+        }
+    }
+
+    if success {
+        Ok(logs)
+    } else if !ffmpeg_errors.is_empty() {
+        Err(format!("FFmpeg failed: {}", ffmpeg_errors.join("; ")))
+    } else {
+        Err("FFmpeg did not finish successfully.".to_string())
+    }
+}
+
+// Writes the command line to the running job's log, if any
+pub(crate) fn record_command(command: &mut FfmpegCommand) {
+    let args: Vec<String> = command
+        .as_inner()
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    joblog::record(&format!("$ ffmpeg {}", privacy::redact_args(&args).join(" ")));
+}
+
+// Everything FFmpeg writes to stderr, apart from the progress lines
+pub(crate) fn record_event(event: &FfmpegEvent) {
+    match event {
+        FfmpegEvent::Log(_, line) => joblog::record(line),
+        FfmpegEvent::Error(e) => joblog::record(&format!("error: {}", e)),
+        _ => {}
+    }
+}
+
+// Pulls the value out of a filter log line such as
+// "[Parsed_ametadata_2 @ 0x55d] lavfi.astats.Overall.RMS_level=-23.5".
+pub fn log_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let start = line.find(key)? + key.len();
+    line[start..].split_whitespace().next()
+}
@@ -0,0 +1,169 @@
+// Filename sanitation shared by everything that turns user- or site-provided
+// text (URL path segments, video titles, chapter names, templates) into a
+// file name on disk.
+//
+// Names are made before anyone knows which volume they land on, and a
+// FAT32/exFAT stick or SMB share follows Windows rules on every OS, so those
+// rules apply everywhere.
+
+// Most filesystems cap a single path component at 255 bytes (ext4, APFS) or
+// 255 UTF-16 code units (NTFS, exFAT). No character takes more UTF-16 units
+// than UTF-8 bytes, so staying within the bytes covers both.
+const MAX_COMPONENT_LEN: usize = 255;
+
+const FALLBACK_NAME: &str = "untitled";
+
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn is_forbidden(c: char) -> bool {
+    // ':' is also the one character HFS+ rejects
+    c == '\0' || c.is_control() || matches!(c, '/' | '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*')
+}
+
+// Sanitizes a single file name (not a path) so it can be written to any of
+// the filesystems a clip might be saved to.
+pub fn sanitize_filename(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if is_forbidden(c) { '_' } else { c })
+        .collect();
+
+    // Leading/trailing whitespace is never intentional, and Windows silently
+    // strips trailing dots and spaces, which breaks later lookups by name.
+    let mut cleaned = cleaned.trim().trim_end_matches(['.', ' ']).to_string();
+
+    if cleaned.is_empty() {
+        return FALLBACK_NAME.to_string();
+    }
+
+    if is_windows_reserved(&cleaned) {
+        cleaned.insert(0, '_');
+    }
+
+    truncate_preserving_extension(&cleaned)
+}
+
+// "CON", "con.txt" and "Com1.tar.gz" are all reserved: Windows only looks at
+// the part before the first dot.
+fn is_windows_reserved(name: &str) -> bool {
+    let base = name.split('.').next().unwrap_or(name).trim_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+fn truncate_preserving_extension(name: &str) -> String {
+    if name.len() <= MAX_COMPONENT_LEN {
+        return name.to_string();
+    }
+
+    let truncated = match name.rfind('.') {
+        // Only treat short suffixes as extensions; a dot in the middle of a
+        // long title shouldn't protect half of it from truncation.
+        Some(dot) if dot > 0 && name.len() - dot <= 16 => {
+            let (stem, extension) = name.split_at(dot);
+            let stem = truncate_to(stem, MAX_COMPONENT_LEN - extension.len());
+            format!("{}{}", stem.trim_end_matches(['.', ' ']), extension)
+        }
+        _ => truncate_to(name, MAX_COMPONENT_LEN).trim_end_matches(['.', ' ']).to_string(),
+    };
+    if truncated.is_empty() {
+        FALLBACK_NAME.to_string()
+    } else {
+        truncated
+    }
+}
+
+// Truncates on a char boundary so multi-byte titles never produce invalid UTF-8.
+fn truncate_to(s: &str, max_len: usize) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if out.len() + c.len_utf8() > max_len {
+            break;
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_reserved_names() {
+        assert_eq!(sanitize_filename("con"), "_con");
+        assert_eq!(sanitize_filename("con.txt"), "_con.txt");
+        assert_eq!(sanitize_filename("Com1.tar.gz"), "_Com1.tar.gz");
+        assert_eq!(sanitize_filename("LPT9 .mp4"), "_LPT9 .mp4");
+        // Only the exact device names are reserved
+        assert_eq!(sanitize_filename("console.txt"), "console.txt");
+        assert_eq!(sanitize_filename("com10"), "com10");
+    }
+
+    #[test]
+    fn strips_trailing_dots_and_spaces() {
+        assert_eq!(sanitize_filename("clip. . "), "clip");
+        assert_eq!(sanitize_filename("  clip.mp4  "), "clip.mp4");
+        assert_eq!(sanitize_filename("clip..."), "clip");
+    }
+
+    #[test]
+    fn falls_back_for_empty_names() {
+        assert_eq!(sanitize_filename(""), "untitled");
+        assert_eq!(sanitize_filename("   "), "untitled");
+        assert_eq!(sanitize_filename("."), "untitled");
+        assert_eq!(sanitize_filename(".."), "untitled");
+    }
+
+    #[test]
+    fn replaces_forbidden_characters() {
+        assert_eq!(sanitize_filename("a/b\\c:d*e?f\"g<h>i|j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_filename("tab\there\0"), "tab_here_");
+    }
+
+    #[test]
+    fn truncates_multibyte_names_to_255_bytes() {
+        // 4 bytes and 2 UTF-16 units each
+        let name = sanitize_filename(&"😀".repeat(100));
+        assert!(name.len() <= MAX_COMPONENT_LEN);
+        assert_eq!(name, "😀".repeat(63));
+        // 3 bytes and 1 UTF-16 unit each
+        let name = sanitize_filename(&"界".repeat(300));
+        assert!(name.len() <= MAX_COMPONENT_LEN);
+        assert!(name.encode_utf16().count() <= MAX_COMPONENT_LEN);
+        assert_eq!(name, "界".repeat(85));
+    }
+
+    #[test]
+    fn truncates_to_255_utf16_units() {
+        let name = sanitize_filename(&"a".repeat(300));
+        assert_eq!(name.encode_utf16().count(), MAX_COMPONENT_LEN);
+        let name = sanitize_filename(&"é".repeat(300));
+        assert!(name.encode_utf16().count() <= MAX_COMPONENT_LEN);
+        assert_eq!(name, "é".repeat(127));
+    }
+
+    #[test]
+    fn keeps_the_extension_when_truncating() {
+        let name = sanitize_filename(&format!("{}.mp4", "界".repeat(100)));
+        assert!(name.len() <= MAX_COMPONENT_LEN);
+        assert!(name.ends_with(".mp4"));
+        assert_eq!(name, format!("{}.mp4", "界".repeat(83)));
+
+        // A long suffix after a dot is part of the title, not an extension
+        let name = sanitize_filename(&format!("a.{}", "b".repeat(300)));
+        assert_eq!(name.len(), MAX_COMPONENT_LEN);
+        assert!(name.starts_with("a.b"));
+    }
+
+    #[test]
+    fn does_not_leave_trailing_dots_after_truncating() {
+        let name = sanitize_filename(&format!("{}. .{}", "a".repeat(252), "b".repeat(40)));
+        assert_eq!(name, "a".repeat(252));
+    }
+}
@@ -0,0 +1,700 @@
+use std::path::Path;
+
+use crate::job::{
+    BackgroundMusic, ColorAdjustment, Corner, Denoise, CropRect, Loop, PadFill, Resolution, TextOverlay, TextPosition, TrimOptions,
+    VoicePreset, Watermark,
+};
+
+const STANDARD_SHORT_SIDE: u32 = 1080;
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4.0;
+
+// The trimmed range on the timeline the filters see: source seconds for a
+// local file, but starting at 0 for a YouTube download that was already cut
+// and for a retimed clip cut on the input. `source_offset` maps it back to
+// the original video's time.
+#[derive(Debug, Clone, Copy)]
+pub struct ClipRange {
+    pub start: f64,
+    pub end: f64,
+    pub source_offset: f64,
+}
+
+// Subtitles to draw onto the picture: an external file (shifted by
+// `time_offset` seconds onto the filters' timeline) or one of the source's
+// own subtitle streams.
+#[derive(Debug, Clone, Copy)]
+pub struct BurnIn<'a> {
+    pub path: &'a Path,
+    pub stream_index: Option<u32>,
+    pub time_offset: f64,
+}
+
+pub struct VideoFilters {
+    pub chain: Vec<String>,
+    // Final frame size when the filters change it
+    pub output_size: Option<(u32, u32)>,
+}
+
+// What the `ratio` parameter asks for: keep the frame, any W:H aspect ratio
+// (sized by the resolution policy), or an exact WIDTHxHEIGHT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameTarget {
+    Original,
+    Aspect(u32, u32),
+    Exact(u32, u32),
+}
+
+pub fn validate_ratio(ratio: &str) -> Result<(), String> {
+    parse_ratio(ratio).map(|_| ())
+}
+
+// Builds the -vf chain for a job: crop first (so the chosen region is what
+// gets scaled), then the aspect-ratio scale/pad. `source_size` is only
+// needed to honour Resolution::Source.
+pub fn video_filters(
+    ratio: &str,
+    options: &TrimOptions,
+    source_size: Option<(u32, u32)>,
+    range: ClipRange,
+    burn_in: Option<BurnIn>,
+) -> Result<VideoFilters, String> {
+    let mut chain = Vec::new();
+    let mut output_size = None;
+    let mut picture_size = source_size;
+
+    if let Some(crop) = &options.crop {
+        let (filter, size) = crop_filter(crop)?;
+        chain.push(filter);
+        output_size = Some(size);
+        picture_size = Some(size);
+    }
+
+    // FFmpeg has already applied the source's own rotation metadata by the
+    // time these run, so rotation here is relative to what the user sees.
+    let transforms = transform_filters(options)?;
+    if !transforms.is_empty() {
+        chain.extend(transforms);
+        if options.rotate % 180 == 90 {
+            picture_size = picture_size.map(|(w, h)| (h, w));
+            output_size = output_size.map(|(w, h)| (h, w));
+        }
+    }
+
+    // Before scaling and overlays, so only the footage itself is cleaned
+    // up and graded
+    chain.extend(denoise_filter(options.denoise));
+    chain.extend(color_filters(&options.color)?);
+
+    let frame = match parse_ratio(ratio)? {
+        FrameTarget::Original => None,
+        FrameTarget::Exact(width, height) => Some((width, height)),
+        FrameTarget::Aspect(aspect_w, aspect_h) => {
+            let short_side = match options.resolution {
+                Resolution::Standard => STANDARD_SHORT_SIDE,
+                Resolution::Source => picture_size
+                    .map(|(w, h)| w.min(h))
+                    .unwrap_or(STANDARD_SHORT_SIDE),
+                Resolution::Target(short_side) => short_side,
+            };
+            Some(frame_size((aspect_w, aspect_h), short_side)?)
+        }
+    };
+    if let Some((width, height)) = frame {
+        chain.push(scale_pad_filter(width, height, options.pad_fill));
+        output_size = Some((width, height));
+    }
+
+    if let Some(watermark) = &options.watermark {
+        let frame = output_size
+            .or(picture_size)
+            .ok_or("Couldn't read the video size needed to place the watermark")?;
+        chain.push(watermark_filter(watermark, frame)?);
+    }
+
+    // After scaling, so subtitle text is sized for the output frame
+    if let Some(burn_in) = burn_in {
+        chain.extend(subtitle_filters(burn_in));
+    }
+
+    if let Some(overlay) = &options.text_overlay {
+        chain.extend(text_overlay_filters(overlay, range.source_offset)?);
+    }
+
+    chain.extend(fade_filters("fade", options, range)?);
+
+    let speed = validated_speed(options)?;
+    if speed != 1.0 {
+        chain.push(format!("setpts=PTS/{}", speed));
+    }
+
+    // Last, so frames are buffered at output size rather than source size
+    if options.reverse {
+        chain.push("reverse".to_string());
+    }
+    if let Some(looping) = options.looping {
+        chain.extend(loop_filter(looping, false)?);
+    }
+
+    Ok(VideoFilters { chain, output_size })
+}
+
+// Maps HDR to SDR BT.709 through linear light, with the Hable curve so
+// highlights roll off instead of clipping. Runs before any other filter so
+// overlays and burned-in text are drawn in SDR.
+pub const HDR_TO_SDR_FILTER: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
+// Builds the -af chain for a job. Voice processing comes first, so a music
+// mix can apply it to the voice alone.
+pub fn audio_filters(options: &TrimOptions, range: ClipRange) -> Result<Vec<String>, String> {
+    let mut chain = Vec::new();
+    if options.mute {
+        return Ok(chain);
+    }
+
+    chain.extend(voice_filters(options.voice_preset));
+
+    if let Some(gain) = options.volume_db.filter(|gain| *gain != 0.0) {
+        if !gain.is_finite() || gain.abs() > 60.0 {
+            return Err(format!("Unsupported volume change: {} dB", gain));
+        }
+        chain.push(format!("volume={}dB", gain));
+    }
+
+    if options.sample_accurate_audio {
+        chain.extend(sample_accurate_trim(options, range));
+    }
+
+    chain.extend(fade_filters("afade", options, range)?);
+
+    let speed = validated_speed(options)?;
+    if speed != 1.0 {
+        chain.extend(atempo_chain(speed));
+    }
+
+    if options.reverse {
+        chain.push("areverse".to_string());
+    }
+    if let Some(looping) = options.looping {
+        chain.extend(loop_filter(looping, true)?);
+    }
+
+    Ok(chain)
+}
+
+const SAMPLE_ACCURATE_RATE: u32 = 48000;
+// Long enough to hide the step a hard cut leaves in the waveform, too short
+// to hear as a fade
+const MICRO_FADE_SECONDS: f64 = 0.005;
+
+// Cuts the range on whole samples at a fixed rate rather than on the
+// output's timestamps, then fades the first and last few milliseconds.
+// Samples count from the first one decoded: the source's start, or the
+// trim start for replacement audio, which is shifted there.
+fn sample_accurate_trim(options: &TrimOptions, range: ClipRange) -> Vec<String> {
+    let first_sample_at = if options.replacement_audio.is_some() { range.start } else { 0.0 };
+    let to_sample = |seconds: f64| ((seconds - first_sample_at).max(0.0) * SAMPLE_ACCURATE_RATE as f64).round() as u64;
+    let mut chain = vec![
+        format!("aresample={}", SAMPLE_ACCURATE_RATE),
+        format!("atrim=start_sample={}:end_sample={}", to_sample(range.start), to_sample(range.end)),
+    ];
+    // Fades of the job's own already cover the cut
+    if options.fade_in.unwrap_or(0.0) <= 0.0 {
+        chain.push(format!("afade=t=in:st={}:d={}", range.start, MICRO_FADE_SECONDS));
+    }
+    if options.fade_out.unwrap_or(0.0) <= 0.0 {
+        chain.push(format!("afade=t=out:st={}:d={}", range.end - MICRO_FADE_SECONDS, MICRO_FADE_SECONDS));
+    }
+    chain
+}
+
+const MAX_LOOP_COUNT: u32 = 20;
+
+// Plays the clip again by splitting it and joining the copies, which holds
+// the first copy's frames in memory until it has played, as reverse does.
+// Still a single-input, single-output graph, so it can end a -vf chain.
+fn loop_filter(looping: Loop, audio: bool) -> Result<Option<String>, String> {
+    let (prefix, split, reverse, streams) = if audio {
+        ("a", "asplit", "areverse", "v=0:a=1")
+    } else {
+        ("", "split", "reverse", "v=1:a=0")
+    };
+    match looping {
+        Loop::Repeat { count } if count == 0 || count > MAX_LOOP_COUNT => Err(format!(
+            "Loop count must be between 1 and {}",
+            MAX_LOOP_COUNT
+        )),
+        Loop::Repeat { count: 1 } => Ok(None),
+        Loop::Repeat { count } => {
+            let labels: String = (0..count).map(|i| format!("[{}loop{}]", prefix, i)).collect();
+            Ok(Some(format!("{}={}{};{}concat=n={}:{}", split, count, labels, labels, count, streams)))
+        }
+        Loop::Boomerang => Ok(Some(format!(
+            "{split}[{p}fwd][{p}back];[{p}back]{reverse}[{p}rev];[{p}fwd][{p}rev]concat=n=2:{streams}",
+            split = split,
+            reverse = reverse,
+            streams = streams,
+            p = prefix
+        ))),
+    }
+}
+
+pub fn voice_filters(preset: VoicePreset) -> Vec<String> {
+    let filters: &[&str] = match preset {
+        VoicePreset::Off => &[],
+        VoicePreset::Clean => &["highpass=f=80", "deesser=i=0.4"],
+        VoicePreset::Interview => &[
+            "highpass=f=100",
+            "equalizer=f=3000:t=q:w=1:g=2",
+            "deesser=i=0.5",
+            "acompressor=threshold=-20dB:ratio=3:attack=10:release=200:makeup=2",
+        ],
+        VoicePreset::Broadcast => &[
+            "highpass=f=100",
+            "equalizer=f=250:t=q:w=1:g=-2",
+            "equalizer=f=3500:t=q:w=1:g=3",
+            "deesser=i=0.6",
+            "acompressor=threshold=-24dB:ratio=4:attack=5:release=150:makeup=4",
+        ],
+    };
+    filters.iter().map(|filter| filter.to_string()).collect()
+}
+
+// A -filter_complex graph mixing `music_input` under the `voice` stream
+// (after `voice_filters`) and then applying the rest of the job's audio
+// filters (fades, speed) to the mix. The mix is labelled [aout]. Ducking
+// compresses the music whenever the voice is above the threshold, so it
+// dips under speech and comes back up in pauses.
+pub fn music_mix_graph(
+    voice: &str,
+    music_input: usize,
+    music: &BackgroundMusic,
+    voice_filters: &[String],
+    audio_filters: &[String],
+) -> Result<String, String> {
+    if !music.volume_db.is_finite() || music.volume_db.abs() > 60.0 {
+        return Err(format!("Unsupported music volume: {} dB", music.volume_db));
+    }
+    let mut graph = format!("[{}:a:0]volume={}dB[music];", music_input, music.volume_db);
+    let mut voice_chain = vec!["anull".to_string()];
+    voice_chain.extend(voice_filters.iter().cloned());
+    graph.push_str(&format!("[{}]{}[voice];", voice, voice_chain.join(",")));
+    if music.duck {
+        graph.push_str(
+            "[voice]asplit=2[speech][sidechain];[music][sidechain]sidechaincompress=threshold=0.05:ratio=8:attack=20:release=400[ducked];[speech][ducked]",
+        );
+    } else {
+        graph.push_str("[voice][music]");
+    }
+    graph.push_str("amix=inputs=2:duration=first:normalize=0");
+    for filter in audio_filters {
+        graph.push(',');
+        graph.push_str(filter);
+    }
+    graph.push_str("[aout]");
+    Ok(graph)
+}
+
+fn validated_speed(options: &TrimOptions) -> Result<f64, String> {
+    let speed = options.speed();
+    if !speed.is_finite() || !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+        return Err(format!(
+            "Unsupported speed: {}x. Use a value between {}x and {}x.",
+            speed, MIN_SPEED, MAX_SPEED
+        ));
+    }
+    Ok(speed)
+}
+
+// Older atempo builds only accept 0.5-2.0, so larger changes are chained
+// (e.g. 4x = atempo=2.0,atempo=2.0).
+fn atempo_chain(speed: f64) -> Vec<String> {
+    let mut remaining = speed;
+    let mut chain = Vec::new();
+    while remaining > 2.0 {
+        chain.push("atempo=2.0".to_string());
+        remaining /= 2.0;
+    }
+    while remaining < 0.5 {
+        chain.push("atempo=0.5".to_string());
+        remaining /= 0.5;
+    }
+    if (remaining - 1.0).abs() > f64::EPSILON {
+        chain.push(format!("atempo={}", remaining));
+    }
+    chain
+}
+
+// Fades run before the speed change and reversal, on the range's timestamps,
+// so output durations are scaled by the speed and a reversed clip's fade-in
+// sits at the end of the source range.
+fn fade_filters(filter: &str, options: &TrimOptions, ClipRange { start, end, .. }: ClipRange) -> Result<Vec<String>, String> {
+    let fade_in = options.fade_in.unwrap_or(0.0);
+    let fade_out = options.fade_out.unwrap_or(0.0);
+    if !fade_in.is_finite() || !fade_out.is_finite() || fade_in < 0.0 || fade_out < 0.0 {
+        return Err("Fade durations must be non-negative numbers of seconds".to_string());
+    }
+    let speed = validated_speed(options)?;
+    let (fade_in, fade_out) = (fade_in * speed, fade_out * speed);
+    if fade_in + fade_out > end - start {
+        return Err("Fade-in and fade-out together are longer than the clip".to_string());
+    }
+
+    let (first, last) = if options.reverse { (fade_out, fade_in) } else { (fade_in, fade_out) };
+    let mut chain = Vec::new();
+    if first > 0.0 {
+        chain.push(format!("{}=t=in:st={}:d={}", filter, start, first));
+    }
+    if last > 0.0 {
+        chain.push(format!("{}=t=out:st={}:d={}", filter, end - last, last));
+    }
+    Ok(chain)
+}
+
+fn transform_filters(options: &TrimOptions) -> Result<Vec<String>, String> {
+    let mut filters: Vec<String> = match options.rotate {
+        0 => Vec::new(),
+        90 => vec!["transpose=clock".to_string()],
+        180 => vec!["hflip".to_string(), "vflip".to_string()],
+        270 => vec!["transpose=cclock".to_string()],
+        other => return Err(format!("Unsupported rotation: {} degrees. Use 90, 180 or 270.", other)),
+    };
+    if options.flip_horizontal {
+        filters.push("hflip".to_string());
+    }
+    if options.flip_vertical {
+        filters.push("vflip".to_string());
+    }
+    Ok(filters)
+}
+
+fn denoise_filter(denoise: Denoise) -> Option<String> {
+    let filter = match denoise {
+        Denoise::Off => return None,
+        Denoise::Light => "hqdn3d=2:1.5:3:2.5",
+        Denoise::Medium => "hqdn3d=4:3:6:4.5",
+        Denoise::Strong => "nlmeans=s=4:p=7:r=15",
+    };
+    Some(filter.to_string())
+}
+
+fn color_filters(color: &ColorAdjustment) -> Result<Vec<String>, String> {
+    let mut filters = Vec::new();
+    if let Some(lut) = &color.lut {
+        if !Path::new(lut).is_file() {
+            return Err(format!("LUT file not found: {}", lut));
+        }
+        filters.push(format!("lut3d=file={}", escape_filter_value(lut)));
+    }
+
+    let mut eq = Vec::new();
+    for (name, value, range) in [
+        ("brightness", color.brightness, -1.0..=1.0),
+        ("contrast", color.contrast, 0.0..=3.0),
+        ("saturation", color.saturation, 0.0..=3.0),
+    ] {
+        if let Some(value) = value {
+            if !value.is_finite() || !range.contains(&value) {
+                return Err(format!(
+                    "Unsupported {}: {}. Use a value between {} and {}.",
+                    name,
+                    value,
+                    range.start(),
+                    range.end()
+                ));
+            }
+            eq.push(format!("{}={}", name, value));
+        }
+    }
+    if !eq.is_empty() {
+        filters.push(format!("eq={}", eq.join(":")));
+    }
+    Ok(filters)
+}
+
+fn crop_filter(crop: &CropRect) -> Result<(String, (u32, u32)), String> {
+    // yuv420p needs even dimensions; round down rather than fail on odd
+    // sizes coming from a freehand selection.
+    let width = crop.width & !1;
+    let height = crop.height & !1;
+    if width == 0 || height == 0 {
+        return Err("Crop rectangle must be at least 2x2 pixels".to_string());
+    }
+    Ok((format!("crop={}:{}:{}:{}", width, height, crop.x, crop.y), (width, height)))
+}
+
+// The image is loaded with the movie source so it fits in -vf without a
+// second input; overlay keeps showing a still image's only frame for the
+// whole clip.
+fn watermark_filter(watermark: &Watermark, (frame_w, frame_h): (u32, u32)) -> Result<String, String> {
+    if !Path::new(&watermark.path).is_file() {
+        return Err(format!("Watermark image not found: {}", watermark.path));
+    }
+    if !watermark.opacity.is_finite() || !(0.0..=1.0).contains(&watermark.opacity) {
+        return Err("Watermark opacity must be between 0 and 1".to_string());
+    }
+    if !watermark.scale.is_finite() || watermark.scale <= 0.0 || watermark.scale > 1.0 {
+        return Err("Watermark scale must be between 0 and 1".to_string());
+    }
+
+    let width = ((frame_w as f64 * watermark.scale).round() as u32).max(1);
+    let margin = (frame_w.min(frame_h) as f64 * 0.03).round() as u32;
+    let (x, y) = match watermark.position {
+        Corner::TopLeft => (margin.to_string(), margin.to_string()),
+        Corner::TopRight => (format!("W-w-{}", margin), margin.to_string()),
+        Corner::BottomLeft => (margin.to_string(), format!("H-h-{}", margin)),
+        Corner::BottomRight => (format!("W-w-{}", margin), format!("H-h-{}", margin)),
+    };
+
+    let mut image = format!("movie=filename={},format=rgba", escape_filter_value(&watermark.path));
+    if watermark.opacity < 1.0 {
+        image.push_str(&format!(",colorchannelmixer=aa={}", watermark.opacity));
+    }
+    image.push_str(&format!(",scale={}:-1", width));
+    Ok(format!(
+        "null[wm_base];{}[wm];[wm_base][wm]overlay=x={}:y={}",
+        image, x, y
+    ))
+}
+
+// The subtitles filter has no offset option, so frames are shifted onto the
+// subtitle file's timeline for drawing and shifted back afterwards.
+fn subtitle_filters(burn_in: BurnIn) -> Vec<String> {
+    let mut filter = format!("subtitles=filename={}", escape_filter_value(&burn_in.path.to_string_lossy()));
+    if let Some(index) = burn_in.stream_index {
+        filter.push_str(&format!(":si={}", index));
+    }
+    if burn_in.time_offset == 0.0 {
+        return vec![filter];
+    }
+    vec![
+        format!("setpts=PTS{:+}/TB", burn_in.time_offset),
+        filter,
+        format!("setpts=PTS{:+}/TB", -burn_in.time_offset),
+    ]
+}
+
+// The caption is drawn without expansion so '%' in user text is literal. The
+// timecode line uses drawtext's pts expansion, which runs before any speed
+// change, so it shows the original video's time even in slow motion.
+fn text_overlay_filters(overlay: &TextOverlay, source_offset: f64) -> Result<Vec<String>, String> {
+    if overlay.font_size == 0 {
+        return Err("Text size must be at least 1".to_string());
+    }
+    let mut style = format!(
+        "fontsize={}:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw={}",
+        overlay.font_size,
+        overlay.font_size / 4
+    );
+    if let Some(font_file) = overlay.font_file.as_deref().filter(|f| !f.is_empty()) {
+        if !Path::new(font_file).is_file() {
+            return Err(format!("Font file not found: {}", font_file));
+        }
+        style.push_str(&format!(":fontfile={}", escape_filter_value(font_file)));
+    }
+
+    let margin = overlay.font_size / 2;
+    let line = overlay.font_size * 3 / 2;
+    let x = match overlay.position {
+        TextPosition::TopLeft | TextPosition::BottomLeft => margin.to_string(),
+        TextPosition::Top | TextPosition::Bottom => "(w-text_w)/2".to_string(),
+        TextPosition::TopRight | TextPosition::BottomRight => format!("w-text_w-{}", margin),
+    };
+    let at_top = matches!(overlay.position, TextPosition::Top | TextPosition::TopLeft | TextPosition::TopRight);
+    // Lines stack inwards from the chosen edge
+    let y_for_line = |index: u32| {
+        if at_top {
+            (margin + index * line).to_string()
+        } else {
+            format!("h-text_h-{}", margin + index * line)
+        }
+    };
+
+    let mut chain = Vec::new();
+    let caption = overlay.text.trim();
+    if !caption.is_empty() {
+        chain.push(format!(
+            "drawtext=text={}:expansion=none:{}:x={}:y={}",
+            escape_filter_value(caption),
+            style,
+            x,
+            y_for_line(0)
+        ));
+    }
+    if overlay.burn_timecode {
+        let timecode = format!("%{{pts:hms:{}}}", source_offset);
+        chain.push(format!(
+            "drawtext=text={}:{}:x={}:y={}",
+            escape_filter_value(&timecode),
+            style,
+            x,
+            y_for_line(chain.len() as u32)
+        ));
+    }
+    Ok(chain)
+}
+
+// Escapes a filter option value for both levels FFmpeg unescapes inside a
+// filtergraph (see "Notes on filtergraph escaping" in ffmpeg-filters), so
+// paths and text with ':', quotes or commas survive.
+pub(crate) fn escape_filter_value(value: &str) -> String {
+    let escape = |input: &str, special: &[char]| {
+        let mut out = String::with_capacity(input.len());
+        for c in input.chars() {
+            if special.contains(&c) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+        out
+    };
+    let option_level = escape(value, &['\\', '\'', ':']);
+    escape(&option_level, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+// Accepts "Original", "W:H" (e.g. "4:5", "21:9") or "WIDTHxHEIGHT"
+// (e.g. "1280x720"). Exact sizes are rounded to even dimensions.
+fn parse_ratio(ratio: &str) -> Result<FrameTarget, String> {
+    let ratio = ratio.trim();
+    if ratio.eq_ignore_ascii_case("original") {
+        return Ok(FrameTarget::Original);
+    }
+
+    let parse_pair = |separator: char| -> Option<(u32, u32)> {
+        let (a, b) = ratio.split_once(separator)?;
+        let a: u32 = a.trim().parse().ok()?;
+        let b: u32 = b.trim().parse().ok()?;
+        (a > 0 && b > 0).then_some((a, b))
+    };
+
+    if let Some((aspect_w, aspect_h)) = parse_pair(':') {
+        let longer = aspect_w.max(aspect_h) as f64;
+        let shorter = aspect_w.min(aspect_h) as f64;
+        if longer / shorter > 10.0 {
+            return Err(format!("Unsupported ratio: {} is too extreme", ratio));
+        }
+        return Ok(FrameTarget::Aspect(aspect_w, aspect_h));
+    }
+
+    if let Some((width, height)) = parse_pair('x').or_else(|| parse_pair('X')) {
+        let even = |value: u32| (value + 1) & !1;
+        let (width, height) = (even(width), even(height));
+        if width > 8192 || height > 8192 {
+            return Err(format!("Unsupported resolution: {} exceeds 8192 pixels", ratio));
+        }
+        return Ok(FrameTarget::Exact(width, height));
+    }
+
+    Err(format!(
+        "Unsupported ratio: {}. Use \"Original\", an aspect ratio like \"4:5\", or a size like \"1280x720\".",
+        ratio
+    ))
+}
+
+// Frame size for an aspect ratio with the given short side, rounded to even
+// dimensions for yuv420p.
+fn frame_size((aspect_w, aspect_h): (u32, u32), short_side: u32) -> Result<(u32, u32), String> {
+    if !(2..=8192).contains(&short_side) {
+        return Err(format!("Unsupported target resolution: {}p", short_side));
+    }
+    let even = |value: f64| ((value / 2.0).round() as u32 * 2).max(2);
+    let short_side = even(short_side as f64);
+    if aspect_w >= aspect_h {
+        Ok((even(short_side as f64 * aspect_w as f64 / aspect_h as f64), short_side))
+    } else {
+        Ok((short_side, even(short_side as f64 * aspect_h as f64 / aspect_w as f64)))
+    }
+}
+
+fn scale_pad_filter(width: u32, height: u32, fill: PadFill) -> String {
+    match fill {
+        PadFill::Black => format!(
+            "scale={w}:{h}:flags=lanczos:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:black,setsar=1",
+            w = width,
+            h = height
+        ),
+        // The background is scaled to cover the frame, cropped and blurred
+        // (at quarter size, which is much cheaper and looks the same), then
+        // the fitted foreground is overlaid in the centre. -vf accepts the
+        // labelled sub-graph as long as it has one input and one output.
+        PadFill::Blur => format!(
+            "split=2[bg_in][fg_in];\
+             [bg_in]scale={qw}:{qh}:force_original_aspect_ratio=increase,crop={qw}:{qh},boxblur=10:2,scale={w}:{h}[bg];\
+             [fg_in]scale={w}:{h}:flags=lanczos:force_original_aspect_ratio=decrease[fg];\
+             [bg][fg]overlay=(W-w)/2:(H-h)/2,setsar=1",
+            w = width,
+            h = height,
+            qw = width / 4,
+            qh = height / 4
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ratios_and_sizes() {
+        assert_eq!(parse_ratio("Original"), Ok(FrameTarget::Original));
+        assert_eq!(parse_ratio(" original "), Ok(FrameTarget::Original));
+        assert_eq!(parse_ratio("4:5"), Ok(FrameTarget::Aspect(4, 5)));
+        assert_eq!(parse_ratio("10:1"), Ok(FrameTarget::Aspect(10, 1)));
+        assert_eq!(parse_ratio("1280x720"), Ok(FrameTarget::Exact(1280, 720)));
+        assert_eq!(parse_ratio("1280X720"), Ok(FrameTarget::Exact(1280, 720)));
+        assert_eq!(parse_ratio("1281x721"), Ok(FrameTarget::Exact(1282, 722)));
+        assert_eq!(parse_ratio("8191x1"), Ok(FrameTarget::Exact(8192, 2)));
+    }
+
+    #[test]
+    fn rejects_zero_extreme_and_garbage_ratios() {
+        for ratio in ["", "0:1", "16:0", "0x720", "1280x0", "11:1", "8193x100", "-4:5", "4:5:6", "16/9", "wide", "1.5:1"] {
+            assert!(parse_ratio(ratio).is_err(), "{:?} was accepted", ratio);
+        }
+    }
+
+    #[test]
+    fn frame_sizes_are_even() {
+        assert_eq!(frame_size((16, 9), 720), Ok((1280, 720)));
+        assert_eq!(frame_size((9, 16), 720), Ok((720, 1280)));
+        assert_eq!(frame_size((21, 9), 1080), Ok((2520, 1080)));
+        assert_eq!(frame_size((4, 3), 481), Ok((642, 482)));
+        assert_eq!(frame_size((1, 1), 2), Ok((2, 2)));
+        for (aspect, short_side) in [((16, 9), 481), ((5, 4), 333), ((3, 7), 99)] {
+            let (width, height) = frame_size(aspect, short_side).unwrap();
+            assert!(width % 2 == 0 && height % 2 == 0, "{}x{}", width, height);
+        }
+        assert!(frame_size((16, 9), 0).is_err());
+        assert!(frame_size((16, 9), 1).is_err());
+        assert!(frame_size((16, 9), 8193).is_err());
+    }
+
+    #[test]
+    fn atempo_chain_splits_speeds_outside_its_range() {
+        assert!(atempo_chain(1.0).is_empty());
+        assert_eq!(atempo_chain(1.5), ["atempo=1.5"]);
+        assert_eq!(atempo_chain(2.0), ["atempo=2"]);
+        assert_eq!(atempo_chain(0.5), ["atempo=0.5"]);
+        assert_eq!(atempo_chain(3.0), ["atempo=2.0", "atempo=1.5"]);
+        assert_eq!(atempo_chain(4.0), ["atempo=2.0", "atempo=2"]);
+        assert_eq!(atempo_chain(0.25), ["atempo=0.5", "atempo=0.5"]);
+        for speed in [0.1, 0.3, 0.75, 2.5, 7.0, 16.0] {
+            let factors: Vec<f64> = atempo_chain(speed)
+                .iter()
+                .map(|filter| filter.strip_prefix("atempo=").unwrap().parse().unwrap())
+                .collect();
+            assert!(factors.iter().all(|f| (0.5..=2.0).contains(f)), "{}: {:?}", speed, factors);
+            let product: f64 = factors.iter().product();
+            assert!((product - speed).abs() < 1e-9, "{}: {:?}", speed, factors);
+        }
+    }
+
+    #[test]
+    fn escapes_filter_values_at_both_levels() {
+        assert_eq!(escape_filter_value("plain text"), "plain text");
+        assert_eq!(escape_filter_value("12:30"), r"12\\:30");
+        assert_eq!(escape_filter_value("it's"), r"it\\\'s");
+        assert_eq!(escape_filter_value(r"C:\fonts"), r"C\\:\\\\fonts");
+        assert_eq!(escape_filter_value("[a],b;c"), r"\[a\]\,b\;c");
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::ffmpeg;
+
+// Frames are reduced to 9x8 grey for a 64-bit difference hash (dHash)
+const HASH_WIDTH: usize = 9;
+const HASH_HEIGHT: usize = 8;
+const MAX_HASHED_FRAMES: usize = 64;
+
+// Two clips match when their durations agree and sampled frames differ by
+// at most this many bits on average; re-encodes land well under it.
+const MAX_MEAN_HAMMING_DISTANCE: f64 = 6.0;
+const DURATION_TOLERANCE_SECONDS: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipFingerprint {
+    pub duration: f64,
+    pub frame_hashes: Vec<u64>,
+}
+
+// Hashes the keyframes of `duration` seconds starting at `start`. Only
+// keyframes are decoded, which keeps this cheap even for long ranges.
+pub async fn compute(source: &Path, start: f64, duration: f64) -> Result<ClipFingerprint, String> {
+    let output = Command::new(ffmpeg::ffmpeg_path())
+        .args(["-hide_banner", "-loglevel", "error", "-skip_frame", "nokey"])
+        .args(["-ss", &start.to_string(), "-t", &duration.to_string()])
+        .arg("-i")
+        .arg(source)
+        .args(["-an", "-sn", "-dn", "-fps_mode", "vfr"])
+        .args(["-vf", &format!("scale={}:{}:flags=area,format=gray", HASH_WIDTH, HASH_HEIGHT)])
+        .args(["-frames:v", &MAX_HASHED_FRAMES.to_string()])
+        .args(["-f", "rawvideo", "-"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to fingerprint clip: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let frame_hashes: Vec<u64> = output
+        .stdout
+        .chunks_exact(HASH_WIDTH * HASH_HEIGHT)
+        .map(difference_hash)
+        .collect();
+    if frame_hashes.is_empty() {
+        return Err("No video frames found to fingerprint".to_string());
+    }
+
+    Ok(ClipFingerprint { duration, frame_hashes })
+}
+
+// One bit per horizontally adjacent pixel pair: set when brightness increases
+fn difference_hash(frame: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for row in frame.chunks_exact(HASH_WIDTH) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[1] > pair[0]);
+        }
+    }
+    hash
+}
+
+pub fn is_near_duplicate(a: &ClipFingerprint, b: &ClipFingerprint) -> bool {
+    if (a.duration - b.duration).abs() > DURATION_TOLERANCE_SECONDS {
+        return false;
+    }
+    // Allow one frame of slack for keyframes that land right on a boundary
+    if a.frame_hashes.len().abs_diff(b.frame_hashes.len()) > 1 {
+        return false;
+    }
+
+    let compared = a.frame_hashes.len().min(b.frame_hashes.len());
+    if compared == 0 {
+        return false;
+    }
+    let total_distance: u32 = a
+        .frame_hashes
+        .iter()
+        .zip(&b.frame_hashes)
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum();
+    total_distance as f64 / compared as f64 <= MAX_MEAN_HAMMING_DISTANCE
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ffmpeg, timecode};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl FrameFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+// Which frames of the range to save. Every frame when unset.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FrameSampling {
+    // Every nth decoded frame, starting with the first
+    EveryNth { n: u32 },
+    // Evenly spaced frames at this rate, e.g. 1 for one per second
+    Fps { fps: f64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameExport {
+    pub dir: String,
+    pub frames: usize,
+}
+
+// Saves the range from `start_time` to `end_time` of a local file or direct
+// link as "frame_000001.png" and so on in `dir`, for sprite sheets or
+// frame-by-frame analysis
+pub async fn export_frames(
+    source: &str,
+    start_time: &str,
+    end_time: &str,
+    dir: &Path,
+    format: FrameFormat,
+    sampling: Option<FrameSampling>,
+) -> Result<FrameExport, String> {
+    if source.contains("youtube.com") || source.contains("youtu.be") {
+        return Err("Frame export needs a local file or a direct video link".to_string());
+    }
+    if !source.starts_with("http") && !Path::new(source).exists() {
+        return Err(format!("Local video file not found: {}", source));
+    }
+    let start_time = &timecode::normalize_time(start_time, None)?;
+    let end_time = &timecode::normalize_time(end_time, None)?;
+    if timecode::time_to_seconds(end_time)? <= timecode::time_to_seconds(start_time)? {
+        return Err("End time must be after start time".to_string());
+    }
+    let filter = match sampling {
+        None | Some(FrameSampling::EveryNth { n: 1 }) => None,
+        Some(FrameSampling::EveryNth { n: 0 }) => return Err("Save at least every frame (n of 1 or more)".to_string()),
+        Some(FrameSampling::EveryNth { n }) => Some(format!("select=not(mod(n\\,{}))", n)),
+        Some(FrameSampling::Fps { fps }) if !fps.is_finite() || fps <= 0.0 => {
+            return Err("Frame rate must be a positive number".to_string())
+        }
+        Some(FrameSampling::Fps { fps }) => Some(format!("fps={}", fps)),
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create the frames folder: {}", e))?;
+    // Numbering restarts at 1 each export, so older frames would be mixed in
+    let has_frames = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read the frames folder: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.file_name().to_string_lossy().starts_with("frame_"));
+    if has_frames {
+        return Err(format!("{} already has exported frames; choose an empty folder", dir.display()));
+    }
+
+    let mut command = ffmpeg::new_command();
+    command
+        .args(["-ss", start_time, "-to", end_time])
+        .input(source)
+        .args(["-an", "-sn", "-dn"]);
+    if let Some(filter) = &filter {
+        command.args(["-vf", filter]);
+    }
+    // Without this, selected frames would be duplicated back up to the source rate
+    command.args(["-fps_mode", "vfr"]);
+    if format == FrameFormat::Jpeg {
+        command.args(["-q:v", "2"]);
+    }
+    let pattern = dir.join(format!("frame_%06d.{}", format.extension()));
+    command.output(pattern.to_string_lossy()).overwrite();
+    ffmpeg::run(command).await.map_err(|e| format!("Failed to export frames: {}", e))?;
+
+    let frames = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read the frames folder: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("frame_"))
+        .count();
+    Ok(FrameExport {
+        dir: dir.display().to_string(),
+        frames,
+    })
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+use tokio::process::Command;
+
+// An NVIDIA GPU as the driver numbers it; the index is what FFmpeg's -gpu
+// and -hwaccel_device take
+#[derive(Debug, Clone, Serialize)]
+pub struct Gpu {
+    pub index: u32,
+    pub name: String,
+    pub memory_mb: u64,
+}
+
+pub async fn list_gpus() -> Result<Vec<Gpu>, String> {
+    let output = Command::new("nvidia-smi")
+        .args(["--query-gpu=index,name,memory.total", "--format=csv,noheader,nounits"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute nvidia-smi: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "nvidia-smi failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_gpus(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// Lines look like "1, NVIDIA GeForce RTX 3060, 12288"
+fn parse_gpus(listing: &str) -> Vec<Gpu> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(',').map(str::trim);
+            Some(Gpu {
+                index: fields.next()?.parse().ok()?,
+                name: fields.next()?.to_string(),
+                memory_mb: fields.next().and_then(|memory| memory.parse().ok()).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+// Fails unless `index` is one of this machine's GPUs, so a job pinned to a
+// GPU that was removed says so instead of failing in the encoder
+pub async fn check_gpu(index: u32) -> Result<(), String> {
+    let gpus = list_gpus()
+        .await
+        .map_err(|e| format!("GPU {} was chosen, but the GPUs couldn't be listed: {}", index, e))?;
+    if gpus.iter().any(|gpu| gpu.index == index) {
+        return Ok(());
+    }
+    Err(format!(
+        "There's no GPU {} on this machine ({} found). Choose another in the job's options.",
+        index,
+        gpus.len()
+    ))
+}
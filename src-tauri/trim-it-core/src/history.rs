@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::fingerprint::{self, ClipFingerprint};
+use crate::job::{self, JobSpec, TrimOptions};
+use crate::privacy;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub source: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub ratio: String,
+    #[serde(default)]
+    pub options: TrimOptions,
+    pub output_path: String,
+    // All parts together, for split exports
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    // RFC 3339, UTC
+    pub created_at: String,
+    #[serde(default)]
+    pub fingerprint: Option<ClipFingerprint>,
+}
+
+impl HistoryEntry {
+    // The job that made this export, for running it again
+    pub fn to_spec(&self) -> Result<JobSpec, String> {
+        if privacy::is_redacted(&self.source) {
+            return Err("This export was recorded with redaction on, so its source wasn't kept".to_string());
+        }
+        Ok(JobSpec {
+            version: job::JOB_SPEC_VERSION,
+            source: self.source.clone(),
+            start_time: self.start_time.clone(),
+            end_time: self.end_time.clone(),
+            ratio: self.ratio.clone(),
+            options: self.options.clone(),
+        })
+    }
+}
+
+pub struct HistoryState {
+    path: RwLock<PathBuf>,
+    entries: Mutex<Vec<HistoryEntry>>,
+}
+
+impl HistoryState {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            entries: Mutex::new(read_entries(&path)),
+            path: RwLock::new(path),
+        }
+    }
+
+    // Switches to another history file, e.g. when the profile changes
+    pub fn reload(&self, path: PathBuf) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        *entries = read_entries(&path);
+        *self.path.write().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+
+    pub fn record(&self, entry: HistoryEntry) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.push(entry);
+        save(&self.path.read().unwrap_or_else(|e| e.into_inner()), &entries)
+    }
+
+    pub fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn find(&self, id: &str) -> Option<HistoryEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().find(|entry| entry.id == id).cloned()
+    }
+
+    // Forgets every export; the output files stay where they are
+    pub fn clear(&self) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.clear();
+        save(&self.path.read().unwrap_or_else(|e| e.into_inner()), &entries)
+    }
+
+    // Most recent earlier export whose fingerprint matches and whose output
+    // file is still on disk. Entries recorded with redaction on only keep a
+    // hash of the path, so they can't be checked and are skipped.
+    pub fn find_duplicate(&self, candidate: &ClipFingerprint) -> Option<HistoryEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| Path::new(&entry.output_path).exists())
+            .find(|entry| {
+                entry
+                    .fingerprint
+                    .as_ref()
+                    .is_some_and(|existing| fingerprint::is_near_duplicate(existing, candidate))
+            })
+            .cloned()
+    }
+}
+
+fn read_entries(path: &Path) -> Vec<HistoryEntry> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable history file {}: {}", privacy::scrub(&path.display().to_string()), e);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save(path: &Path, entries: &[HistoryEntry]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(entries).map_err(|e| format!("Failed to serialize history: {}", e))?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to save history: {}", e))
+}
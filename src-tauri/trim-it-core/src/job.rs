@@ -0,0 +1,608 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ffmpeg::FfmpegBuild;
+
+// Specs newer than this may rely on options this build would silently ignore
+pub const JOB_SPEC_VERSION: u32 = 1;
+
+// A complete, portable description of a trim, for saving and sharing
+// reproducible settings and for submitting jobs from outside the UI.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSpec {
+    #[serde(default = "current_spec_version")]
+    pub version: u32,
+    pub source: String,
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(default = "default_ratio")]
+    pub ratio: String,
+    #[serde(default)]
+    pub options: TrimOptions,
+}
+
+fn current_spec_version() -> u32 {
+    JOB_SPEC_VERSION
+}
+
+fn default_ratio() -> String {
+    "Original".to_string()
+}
+
+impl JobSpec {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let spec: JobSpec = serde_json::from_str(json).map_err(|e| format!("Invalid job spec: {}", e))?;
+        if spec.version > JOB_SPEC_VERSION {
+            return Err(format!(
+                "This job spec needs a newer version of trim-it (spec version {}, supported up to {})",
+                spec.version, JOB_SPEC_VERSION
+            ));
+        }
+        Ok(spec)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize job spec: {}", e))
+    }
+}
+
+// Optional per-job settings for trim_video. Every field defaults to "leave
+// the video alone", so a job with no options is a plain trim.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrimOptions {
+    pub crop: Option<CropRect>,
+    pub pad_fill: PadFill,
+    pub resolution: Resolution,
+    // Clockwise degrees: 0, 90, 180 or 270
+    pub rotate: u32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub color: ColorAdjustment,
+    pub denoise: Denoise,
+    // Export in parts when the output drive can't hold the whole file (FAT32)
+    pub split_at_filesystem_limit: bool,
+    // Export in parts of a chosen length or size
+    pub split: Option<Split>,
+    // Date the clip (file times and creation_time tag) by when the trimmed
+    // moment was recorded
+    pub preserve_creation_time: bool,
+    // Playback speed factor, 0.25 (slow motion) to 4.0 (timelapse)
+    pub speed: Option<f64>,
+    // Seconds of the exported clip to fade in from and out to black/silence
+    pub fade_in: Option<f64>,
+    pub fade_out: Option<f64>,
+    pub location: LocationPolicy,
+    // Drop every tag (GPS, creation time, device model, titles) and
+    // chapter from the output, e.g. before sharing phone footage
+    pub strip_metadata: bool,
+    // Written on the output, after any stripping
+    pub tags: OutputTags,
+    pub watermark: Option<Watermark>,
+    pub text_overlay: Option<TextOverlay>,
+    pub subtitles: SubtitleOptions,
+    // Source stream indexes (from get_streams) to include; FFmpeg's single
+    // default pick when unset
+    pub audio_streams: Option<Vec<u32>>,
+    pub subtitle_streams: Option<Vec<u32>>,
+    // ISO 639-2 codes (e.g. "eng") to tag the output's audio and subtitle
+    // streams with, in output order; blank entries keep the source's tag
+    pub audio_languages: Vec<String>,
+    pub subtitle_languages: Vec<String>,
+    // Transcribe the clip with the configured whisper.cpp
+    pub captions: CaptionMode,
+    // Language to translate the captions into with the configured
+    // translator, e.g. "de"
+    pub translate_captions_to: Option<String>,
+    // Broadcast captions embedded in the video, as in TV recordings
+    pub closed_captions: ClosedCaptions,
+    pub reverse: bool,
+    // Play the range more than once, for short reaction clips
+    pub looping: Option<Loop>,
+    // Reversing buffers every decoded frame in memory, so long ranges are
+    // refused unless this is set
+    pub allow_long_reverse: bool,
+    // Drop the audio track entirely
+    pub mute: bool,
+    pub volume_db: Option<f64>,
+    // Cut the audio on exact sample counts, with a few milliseconds of fade
+    // at each cut so music edits don't click. Re-encodes the audio.
+    pub sample_accurate_audio: bool,
+    pub voice_preset: VoicePreset,
+    // Karaoke and a cappella versions, made by the configured stem separator
+    pub vocals: Vocals,
+    // Two-pass EBU R128 normalization to -14 LUFS
+    pub normalize_loudness: bool,
+    // Audio file to use instead of the source's audio, starting from its
+    // beginning at the trim start
+    pub replacement_audio: Option<String>,
+    // Where the source's start falls in the replacement audio, in seconds
+    // (as found by find_sync_offset), for audio recorded separately from the
+    // same event. Takes the place of lining it up with the trim start.
+    pub replacement_audio_offset: Option<f64>,
+    // Music mixed under the clip's audio from the trim start, looped if it
+    // is shorter than the clip
+    pub background_music: Option<BackgroundMusic>,
+    // Also save the clip's first frame as a JPEG with the same name
+    pub poster_frame: bool,
+    // Also save each of the source's audio tracks as a WAV named after the
+    // clip and the track's title, for mixing speakers separately
+    pub audio_stems: bool,
+    // Save the outputs as passphrase-protected .age files; the passphrase is
+    // supplied with each job and never stored
+    pub encrypt: bool,
+    // Names of installed filter plugins to run, in order
+    pub filter_plugins: Vec<String>,
+    // Skip the "already exported this clip" check
+    pub allow_duplicates: bool,
+    // Keep the output under this many MB (e.g. 25 for Discord) by encoding
+    // the video in two passes at a bitrate worked out from the duration
+    pub max_size_mb: Option<f64>,
+    // Encoder for the picture. Choosing one re-encodes even a plain trim,
+    // e.g. to archive clips smaller than the source; unset copies the video
+    // when nothing changes it and uses H.264 otherwise.
+    pub video_codec: Option<VideoCodec>,
+    // The settings' default quality when unset
+    pub quality: Option<Quality>,
+    // GOP structure of re-encodes, for clips headed to an editor or a
+    // streaming server that needs fixed GOPs. Copies keep the source's.
+    pub keyframes: Keyframes,
+    // Convert a variable frame rate source to a constant rate even when the
+    // video would otherwise be copied. Encodes of such sources always are.
+    pub constant_frame_rate: bool,
+    // Convert HDR sources to SDR for players and sites that show HDR washed
+    // out. HDR is kept otherwise, except by encoders that can't carry it.
+    pub tone_map_hdr: bool,
+    // Run this job with the system FFmpeg or the managed build, e.g. when
+    // only one of them has a filter the job needs
+    pub ffmpeg_build: FfmpegBuild,
+    // Download even when the connection is metered and the download is over
+    // the configured size, after the user confirmed it
+    pub allow_metered_download: bool,
+    // Pins NVENC encoding and hardware decoding to this GPU, by the driver's
+    // numbering, e.g. to keep the primary GPU free for a game. The driver
+    // picks otherwise.
+    pub gpu: Option<u32>,
+    // When the output's name is already taken, e.g. by a clip trimmed in the
+    // same second
+    pub on_collision: OutputCollision,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputCollision {
+    // Add "_2", "_3", ... to the name
+    #[default]
+    Rename,
+    Overwrite,
+    Fail,
+    // Fail, telling the collision listener which file is in the way, so the
+    // app can ask and run the job again with Rename or Overwrite
+    Ask,
+}
+
+// What fills the space around the video when converting aspect ratio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PadFill {
+    #[default]
+    Black,
+    // A zoomed, blurred copy of the frame, as social-media editors do
+    Blur,
+}
+
+impl TrimOptions {
+    pub fn speed(&self) -> f64 {
+        self.speed.unwrap_or(1.0)
+    }
+
+    // How many times the range plays in the output
+    pub fn passes(&self) -> u32 {
+        self.looping.map_or(1, Loop::passes)
+    }
+}
+
+// Image (usually a PNG logo) overlaid in a corner of the output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watermark {
+    pub path: String,
+    #[serde(default)]
+    pub position: Corner,
+    // 0.0 (invisible) to 1.0
+    #[serde(default = "default_watermark_opacity")]
+    pub opacity: f64,
+    // Width of the image as a fraction of the frame width
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f64,
+}
+
+fn default_watermark_opacity() -> f64 {
+    1.0
+}
+
+fn default_watermark_scale() -> f64 {
+    0.15
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+// Caption and/or burned-in source timecode drawn over the picture
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextOverlay {
+    #[serde(default)]
+    pub text: String,
+    // A .ttf/.otf file; FFmpeg's default font otherwise
+    #[serde(default)]
+    pub font_file: Option<String>,
+    #[serde(default = "default_font_size")]
+    pub font_size: u32,
+    #[serde(default)]
+    pub position: TextPosition,
+    // Show each frame's time in the original video (HH:MM:SS.mmm)
+    #[serde(default)]
+    pub burn_timecode: bool,
+}
+
+fn default_font_size() -> u32 {
+    48
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextPosition {
+    Top,
+    #[default]
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundMusic {
+    pub path: String,
+    // Gain applied to the music before mixing
+    #[serde(default = "default_music_volume_db")]
+    pub volume_db: f64,
+    // Dip the music while the clip's own audio is loud, e.g. under speech
+    #[serde(default)]
+    pub duck: bool,
+}
+
+fn default_music_volume_db() -> f64 {
+    -18.0
+}
+
+// How to cut a clip into parts. Copies can only cut on keyframes, so their
+// parts come out a little longer or shorter than asked.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Split {
+    // This many parts of equal length
+    Parts { count: u32 },
+    // Parts of this many seconds, the last one shorter (e.g. 60 for Stories)
+    Chunks { seconds: f64 },
+    // Parts of about this many MB each
+    MaxSize { mb: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Loop {
+    // The range this many times in a row
+    Repeat { count: u32 },
+    // Forwards, then backwards
+    Boomerang,
+}
+
+impl Loop {
+    pub fn passes(self) -> u32 {
+        match self {
+            Loop::Repeat { count } => count,
+            Loop::Boomerang => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keyframes {
+    // Most frames between keyframes (-g), e.g. 60 for 2 s at 30 fps
+    pub interval: Option<u32>,
+    // Fewest frames between keyframes (-keyint_min); the same as the
+    // interval for a fixed GOP
+    pub min_interval: Option<u32>,
+    // Stop the encoder adding keyframes at scene changes
+    pub no_scene_cut: bool,
+}
+
+impl Keyframes {
+    pub fn is_set(&self) -> bool {
+        self.interval.is_some() || self.min_interval.is_some() || self.no_scene_cut
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval == Some(0) || self.min_interval == Some(0) {
+            return Err("Keyframe intervals must be at least 1 frame".to_string());
+        }
+        if let (Some(interval), Some(min_interval)) = (self.interval, self.min_interval) {
+            if min_interval > interval {
+                return Err("The minimum keyframe interval can't be longer than the interval".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Quick color fixes. Unset values leave the picture as it is.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorAdjustment {
+    // -1.0 to 1.0, 0 unchanged
+    pub brightness: Option<f64>,
+    // 0.0 to 3.0, 1 unchanged
+    pub contrast: Option<f64>,
+    // 0.0 (greyscale) to 3.0, 1 unchanged
+    pub saturation: Option<f64>,
+    // A .cube (or other lut3d format) LUT file, applied before the
+    // adjustments above, e.g. to convert log footage
+    pub lut: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleOptions {
+    pub mode: SubtitleMode,
+    // Which of the source's subtitle streams to burn in (0 = first)
+    pub track: u32,
+    // An .srt/.ass/.vtt file to use instead of the embedded streams, timed
+    // against the original video
+    pub file: Option<String>,
+    // For YouTube sources: the subtitles or auto-captions to fetch, e.g. "en"
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleMode {
+    // Whatever FFmpeg picks by default
+    #[default]
+    Unchanged,
+    // Carry text subtitles over as selectable mp4 (mov_text) tracks
+    Keep,
+    // Draw the subtitles onto the picture
+    Burn,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClosedCaptions {
+    // Left in the video stream. A copy keeps them as they are; an H.264
+    // re-encode carries them over too, other encoders drop them.
+    #[default]
+    Keep,
+    // Also save the range's captions as "<clip name>.cc.srt"
+    Extract,
+}
+
+// Noise reduction for grainy low-light footage. Light and medium use the
+// fast hqdn3d; strong uses nlmeans, which looks better but is much slower.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Denoise {
+    #[default]
+    Off,
+    Light,
+    Medium,
+    Strong,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Vocals {
+    #[default]
+    Keep,
+    // Instrumental only
+    Remove,
+    // Vocals only
+    Isolate,
+}
+
+// Cleanup for recorded speech, from lightest to heaviest
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoicePreset {
+    #[default]
+    Off,
+    // Rumble removed and sibilance tamed
+    Clean,
+    // Plus a presence lift and light compression for evenly loud speech
+    Interview,
+    // Firmer compression and less boxiness, for podcasts and voice-overs
+    Broadcast,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptionMode {
+    #[default]
+    Off,
+    // Save an .srt with the same name next to each output
+    Sidecar,
+    Burn,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    // About half the size of H.264 at the same quality, but slower
+    H265,
+    // H.265 on an NVIDIA GPU: fast, with somewhat larger files than libx265
+    HevcNvenc,
+    // AV1, the smallest files. SVT-AV1 is the practical encoder; libaom is
+    // the slow reference one.
+    SvtAv1,
+    AomAv1,
+    // Editing intermediates: every frame is a keyframe, so clips cut and
+    // scrub cleanly in Premiere or Resolve. Saved as MOV with PCM audio, and
+    // many times larger than the delivery codecs.
+    ProRes,
+    Dnxhr,
+}
+
+impl VideoCodec {
+    pub fn encoder(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::HevcNvenc => "hevc_nvenc",
+            VideoCodec::SvtAv1 => "libsvtav1",
+            VideoCodec::AomAv1 => "libaom-av1",
+            VideoCodec::ProRes => "prores_ks",
+            VideoCodec::Dnxhr => "dnxhd",
+        }
+    }
+
+    // The format the encoder writes, as ffprobe names it
+    pub fn codec_name(self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::H265 | VideoCodec::HevcNvenc => "hevc",
+            VideoCodec::SvtAv1 | VideoCodec::AomAv1 => "av1",
+            VideoCodec::ProRes => "prores",
+            VideoCodec::Dnxhr => "dnxhd",
+        }
+    }
+
+    pub fn is_intermediate(self) -> bool {
+        matches!(self, VideoCodec::ProRes | VideoCodec::Dnxhr)
+    }
+
+    // Whether the encoder can write 10-bit BT.2020 video, which HDR needs
+    pub fn carries_hdr(self) -> bool {
+        matches!(
+            self,
+            VideoCodec::H265 | VideoCodec::HevcNvenc | VideoCodec::SvtAv1 | VideoCodec::AomAv1 | VideoCodec::ProRes
+        )
+    }
+
+    // File extension and muxer
+    pub fn container(self) -> &'static str {
+        if self.is_intermediate() {
+            "mov"
+        } else {
+            "mp4"
+        }
+    }
+
+    // The CPU encoder for the same format, used when the GPU one can't
+    // start (no GPU, or every encoder session is taken)
+    pub fn software_fallback(self) -> Option<VideoCodec> {
+        match self {
+            VideoCodec::HevcNvenc => Some(VideoCodec::H265),
+            _ => None,
+        }
+    }
+
+    // Whether FFmpeg's -pass/-passlogfile drive a real two-pass encode
+    pub fn supports_two_pass(self) -> bool {
+        matches!(self, VideoCodec::H264 | VideoCodec::AomAv1)
+    }
+}
+
+// How hard constant-quality encodes compress. Encodes aimed at a size
+// (max_size_mb) and the intermediates don't use it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    // Visually the same as the source
+    #[default]
+    High,
+    Balanced,
+    // Noticeably softer, for quick shares
+    Small,
+}
+
+impl Quality {
+    // The encoder's CRF (CQ for NVENC) at this level; lower is better
+    pub fn crf(self, codec: VideoCodec) -> u32 {
+        let high = match codec {
+            VideoCodec::H264 => 17,
+            VideoCodec::H265 => 22,
+            VideoCodec::HevcNvenc => 24,
+            VideoCodec::SvtAv1 => 32,
+            VideoCodec::AomAv1 => 30,
+            VideoCodec::ProRes | VideoCodec::Dnxhr => 0,
+        };
+        match self {
+            Quality::High => high,
+            Quality::Balanced => high + 4,
+            Quality::Small => high + 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub comment: Option<String>,
+    // RFC 3339, e.g. "2024-05-01T18:30:00Z". Also dates the output files.
+    pub creation_time: Option<String>,
+}
+
+impl OutputTags {
+    // (key, value) for each text tag that is set
+    pub fn text_tags(&self) -> Vec<(&'static str, &str)> {
+        [("title", &self.title), ("artist", &self.artist), ("comment", &self.comment)]
+            .into_iter()
+            .filter_map(|(key, value)| value.as_deref().filter(|v| !v.trim().is_empty()).map(|v| (key, v)))
+            .collect()
+    }
+}
+
+// What happens to the GPS location phones embed in their recordings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationPolicy {
+    // Whatever FFmpeg does by default (re-encodes usually lose it)
+    #[default]
+    Unchanged,
+    // Copy it to the output so photo apps can place the clip on a map
+    Keep,
+    // Remove it, e.g. before sharing publicly
+    Strip,
+}
+
+// Size of the short side when converting aspect ratio ("1080p" is 1920x1080
+// for 16:9, 1080x1920 for 9:16 and 1080x1080 for 1:1).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    #[default]
+    Standard,
+    // Match the source (after cropping), so 4K stays 4K
+    Source,
+    Target(u32),
+}
+
+// Crop rectangle in source pixels, as picked in the UI
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
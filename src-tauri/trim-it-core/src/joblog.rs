@@ -0,0 +1,116 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::privacy;
+
+// Older logs are deleted as new jobs start
+const MAX_JOB_LOGS: usize = 20;
+
+// Set once at startup from the app data dir; without it jobs aren't logged
+static LOG_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_log_dir(dir: PathBuf) {
+    *LOG_DIR.write().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    LOG_DIR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// The full FFmpeg and yt-dlp output of one job, for bug reports. Lines go
+// through the same redaction as the app log.
+#[derive(Debug, Clone)]
+pub struct JobLog {
+    file: Arc<Mutex<File>>,
+}
+
+tokio::task_local! {
+    static CURRENT: Option<JobLog>;
+}
+
+impl JobLog {
+    // None when logging isn't set up or the file can't be created; a job is
+    // never stopped for want of a log
+    pub fn create(summary: &str) -> Option<JobLog> {
+        let dir = log_dir()?;
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::warn!("Failed to create the job log folder: {}", e);
+            return None;
+        }
+        let logs = job_logs(&dir);
+        // Room for the new one
+        let excess = (logs.len() + 1).saturating_sub(MAX_JOB_LOGS);
+        for old in &logs[..excess] {
+            let _ = std::fs::remove_file(old);
+        }
+        let name = format!("job_{}.log", chrono::Utc::now().format("%Y%m%d%H%M%S%3f"));
+        let file = match File::create(dir.join(name)) {
+            Ok(file) => file,
+            Err(e) => {
+                log::warn!("Failed to create a job log: {}", e);
+                return None;
+            }
+        };
+        let job_log = JobLog {
+            file: Arc::new(Mutex::new(file)),
+        };
+        job_log.write(summary);
+        Some(job_log)
+    }
+
+    fn write(&self, line: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(file, "{} {}", chrono::Utc::now().format("%H:%M:%S%.3f"), privacy::scrub(line));
+    }
+}
+
+// Runs `job` with `log` as the log `record` writes to, including from the
+// helpers the job calls
+pub async fn scope<F: Future>(log: Option<JobLog>, job: F) -> F::Output {
+    CURRENT.scope(log, job).await
+}
+
+// The same for blocking work running on behalf of a job
+pub fn sync_scope<R>(log: Option<JobLog>, work: impl FnOnce() -> R) -> R {
+    CURRENT.sync_scope(log, work)
+}
+
+// The log of the job running on this task, to hand to blocking work
+pub fn current() -> Option<JobLog> {
+    CURRENT.try_with(|log| log.clone()).ok().flatten()
+}
+
+// Appends a line to the log of the job running on this task, if any
+pub fn record(line: &str) {
+    let _ = CURRENT.try_with(|log| {
+        if let Some(log) = log {
+            log.write(line);
+        }
+    });
+}
+
+// Oldest first; the timestamped names sort chronologically
+pub fn job_logs(dir: &Path) -> Vec<PathBuf> {
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                    name.starts_with("job_") && name.ends_with(".log")
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort();
+    logs
+}
+
+pub fn last_job_log() -> Result<String, String> {
+    let dir = log_dir().ok_or("Job logging isn't set up")?;
+    let last = job_logs(&dir).pop().ok_or("No job has been logged yet")?;
+    std::fs::read_to_string(&last).map_err(|e| format!("Failed to read the job log: {}", e))
+}
@@ -0,0 +1,114 @@
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::plugins::PluginRegistry;
+use crate::{paths, privacy};
+
+// Lab and classroom installs drop a kiosk.json in the machine config dir (or
+// next to the settings) to lock the app down. It lives outside any profile
+// and there is no command to write it, so it can only be changed by whoever
+// manages the machine.
+const KIOSK_FILE: &str = "kiosk.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KioskPolicy {
+    pub locked: bool,
+    // Hosts remote sources may come from; subdomains are included, so
+    // "youtube.com" also allows "www.youtube.com". Local files are always
+    // allowed.
+    pub allowed_domains: Vec<String>,
+}
+
+impl KioskPolicy {
+    // A kiosk.json that exists but can't be read locks everything rather
+    // than silently unlocking the app
+    pub fn load(config_dir: &Path) -> Self {
+        let path = paths::machine_config_dir()
+            .map(|dir| dir.join(KIOSK_FILE))
+            .filter(|path| path.exists())
+            .unwrap_or_else(|| config_dir.join(KIOSK_FILE));
+        if !path.exists() {
+            return Self::default();
+        }
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+        {
+            Ok(policy) => policy,
+            Err(e) => {
+                log::warn!(
+                    "Locking the app, kiosk file {} is unreadable: {}",
+                    privacy::scrub(&path.display().to_string()),
+                    e
+                );
+                Self {
+                    locked: true,
+                    allowed_domains: Vec::new(),
+                }
+            }
+        }
+    }
+
+    pub fn ensure_unlocked(&self) -> Result<(), String> {
+        if self.locked {
+            return Err("This setting is locked by the administrator.".to_string());
+        }
+        Ok(())
+    }
+
+    // A file manager or another app would leave the kiosk, so locked
+    // installs only show the output path
+    pub fn ensure_can_open_files(&self) -> Result<(), String> {
+        if self.locked {
+            return Err("Opening files is turned off on this computer.".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn check_source(&self, source: &str) -> Result<(), String> {
+        if !self.locked || !source.contains("://") {
+            return Ok(());
+        }
+        let host = Url::parse(source)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .and_then(|url| url.host_str().map(|host| host.to_ascii_lowercase()));
+        let allowed = host.as_deref().is_some_and(|host| {
+            self.allowed_domains.iter().any(|domain| {
+                let domain = domain.trim().trim_start_matches('.').to_ascii_lowercase();
+                !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+            })
+        });
+        if allowed {
+            Ok(())
+        } else {
+            Err("Downloads from this site are not allowed on this computer.".to_string())
+        }
+    }
+
+    // A plugin fetches the sources it resolves itself, so those are checked
+    // like remote ones even without a scheme, and one that isn't a URL on an
+    // allowed site is refused
+    pub fn check_job_source(&self, source: &str, plugins: &PluginRegistry) -> Result<(), String> {
+        if self.locked && plugins.resolver_for(source).is_some() && !source.contains("://") {
+            return Err("Sources from plugins are not allowed on this computer.".to_string());
+        }
+        self.check_source(source)
+    }
+
+    // Locked installs only write inside the output folder, which only the
+    // administrator can change
+    pub fn check_output_dir(&self, dir: &Path, output_dir: &Path) -> Result<(), String> {
+        if !self.locked {
+            return Ok(());
+        }
+        let escapes = dir.components().any(|component| component == Component::ParentDir);
+        if escapes || !dir.starts_with(output_dir) {
+            return Err("Files can only be saved to the output folder on this computer.".to_string());
+        }
+        Ok(())
+    }
+}
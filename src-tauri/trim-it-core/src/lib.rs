@@ -0,0 +1,68 @@
+//! Download and FFmpeg orchestration behind trim-it, usable without Tauri.
+//!
+//! [`trim::TrimJob`] is the entry point for cutting a clip; the other modules
+//! expose the pieces it is built from (tool provisioning, probing, analysis)
+//! for callers that need them directly.
+//!
+//! FFmpeg and yt-dlp are looked up on `PATH` unless a managed copy has been
+//! installed; call [`ffmpeg::set_managed_dir`] and [`ytdlp::set_managed_dir`]
+//! once at startup to choose where those live.
+
+pub mod analysis;
+pub mod attachments;
+pub mod blocking;
+pub mod cache;
+pub mod cancel;
+pub mod capabilities;
+pub mod captions;
+pub mod chapters;
+pub mod chat;
+pub mod closed_captions;
+pub mod concat;
+pub mod diagnostics;
+pub mod download;
+pub mod encryption;
+pub mod error;
+pub mod estimate;
+pub mod ffmpeg;
+pub mod filename;
+pub mod filters;
+pub mod fingerprint;
+pub mod frames;
+pub mod gpu;
+pub mod history;
+pub mod job;
+pub mod joblog;
+pub mod kiosk;
+pub mod loudness;
+pub mod markers;
+pub mod metadata;
+pub mod metered;
+pub mod mirror;
+pub mod multicam;
+pub mod paths;
+pub mod plugins;
+pub mod politeness;
+pub mod poster;
+pub mod power;
+pub mod presets;
+pub mod privacy;
+pub mod probe;
+pub mod profiles;
+pub mod range;
+pub mod remux;
+pub mod replay;
+pub mod retention;
+pub mod reveal;
+pub mod scripting;
+pub mod separation;
+pub mod settings;
+pub mod stems;
+pub mod sync;
+pub mod telemetry;
+pub mod timecode;
+pub mod tools;
+pub mod translation;
+pub mod trim;
+pub mod volume;
+pub mod ytdlp;
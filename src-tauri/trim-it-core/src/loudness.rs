@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::ffmpeg;
+
+// Where YouTube, Spotify and most social apps normalize playback, so clips
+// exported at this level are played back as-is.
+const TARGET_LUFS: f64 = -14.0;
+const TARGET_TRUE_PEAK: f64 = -1.5;
+const TARGET_LRA: f64 = 11.0;
+
+// First-pass loudnorm statistics. loudnorm prints every value as a string,
+// and uses "-inf" for digital silence.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoudnessMeasurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl LoudnessMeasurement {
+    // Second-pass filter applying the measured correction linearly, which
+    // keeps the clip's dynamics instead of compressing it on the fly. None
+    // when the range is silent and there is nothing to normalize.
+    pub fn normalize_filter(&self) -> Option<String> {
+        let values = [
+            &self.input_i,
+            &self.input_tp,
+            &self.input_lra,
+            &self.input_thresh,
+            &self.target_offset,
+        ];
+        if values.iter().any(|v| v.parse::<f64>().map_or(true, |v| !v.is_finite())) {
+            return None;
+        }
+        Some(format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            target_filter(),
+            self.input_i,
+            self.input_tp,
+            self.input_lra,
+            self.input_thresh,
+            self.target_offset
+        ))
+    }
+}
+
+fn target_filter() -> String {
+    format!("loudnorm=I={}:TP={}:LRA={}", TARGET_LUFS, TARGET_TRUE_PEAK, TARGET_LRA)
+}
+
+// Measures the range of `source` that will be exported, after `pre_filters`
+// (gain, speed) so the measurement matches what reaches the second pass.
+pub async fn measure(source: &Path, start: f64, duration: f64, pre_filters: &[String]) -> Result<LoudnessMeasurement, String> {
+    let mut chain = pre_filters.to_vec();
+    chain.push(format!("{}:print_format=json", target_filter()));
+
+    let mut command = ffmpeg::new_command();
+    command
+        .args(["-ss", &start.to_string()])
+        .input(source.to_string_lossy())
+        .args(["-t", &duration.to_string()])
+        .args(["-vn", "-sn", "-dn"])
+        .args(["-af", &chain.join(",")])
+        .args(["-f", "null", "-"]);
+
+    let logs = ffmpeg::run(command).await?;
+
+    // The statistics are the last JSON object in the log, one field per line
+    let json_start = logs
+        .iter()
+        .rposition(|line| line.trim() == "{")
+        .ok_or("No audio could be measured. The source may not contain an audio track.")?;
+    let json_end = logs[json_start..]
+        .iter()
+        .position(|line| line.trim() == "}")
+        .map(|offset| json_start + offset)
+        .ok_or("Loudness measurement output was incomplete")?;
+    serde_json::from_str(&logs[json_start..=json_end].join("\n"))
+        .map_err(|e| format!("Failed to parse loudness measurement: {}", e))
+}
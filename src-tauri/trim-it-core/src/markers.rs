@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::analysis::{SuggestedCut, SuggestionKind};
+use crate::{ffmpeg, paths, privacy};
+
+// An external program that looks at sampled frames and reports the ones that
+// contain something worth clipping (kill feed entries, score changes, ...).
+//
+// The detector is run once with the directory of sampled frames (either in
+// place of a `{frames_dir}` argument or appended as the last argument) and
+// prints one JSON object per hit on stdout:
+//   {"file": "frame_000042.jpg", "score": 0.93, "label": "Double kill"}
+// `score` and `label` are optional; frames it doesn't mention are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkerDetectorConfig {
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_sample_interval")]
+    pub sample_interval_seconds: f64,
+}
+
+fn default_sample_interval() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct DetectorHit {
+    file: String,
+    #[serde(default)]
+    score: Option<f64>,
+    #[serde(default)]
+    label: Option<String>,
+}
+
+pub async fn detect_markers(source: &str, config: &MarkerDetectorConfig) -> Result<Vec<SuggestedCut>, String> {
+    let interval = config.sample_interval_seconds;
+    if !interval.is_finite() || interval <= 0.0 {
+        return Err("Marker sample interval must be a positive number of seconds".to_string());
+    }
+
+    let frames_dir = paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    sample_frames(source, frames_dir.path(), interval).await?;
+
+    let frames_arg = frames_dir.path().to_string_lossy().to_string();
+    let mut args: Vec<String> = config
+        .args
+        .iter()
+        .map(|arg| arg.replace("{frames_dir}", &frames_arg))
+        .collect();
+    if !config.args.iter().any(|arg| arg.contains("{frames_dir}")) {
+        args.push(frames_arg);
+    }
+
+    let output = Command::new(&config.command)
+        .args(&args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run marker detector '{}': {}", config.command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Marker detector exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut suggestions: Vec<SuggestedCut> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<DetectorHit>(line) {
+            Ok(hit) => Some(hit),
+            Err(e) => {
+                log::warn!("Ignoring malformed marker detector output {:?}: {}", privacy::scrub(line), e);
+                None
+            }
+        })
+        .filter_map(|hit| {
+            let start = frame_timestamp(&hit.file, interval)?;
+            Some(SuggestedCut {
+                start,
+                end: start + interval,
+                score: hit.score.unwrap_or(1.0),
+                kind: SuggestionKind::Marker,
+                label: hit.label,
+            })
+        })
+        .collect();
+    suggestions.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(suggestions)
+}
+
+async fn sample_frames(source: &str, frames_dir: &Path, interval: f64) -> Result<(), String> {
+    let pattern = frames_dir.join("frame_%06d.jpg");
+    let mut command = ffmpeg::new_command();
+    command
+        .input(source)
+        .args(["-an", "-sn", "-dn"])
+        .args(["-vf", &format!("fps=1/{}", interval)])
+        .args(["-q:v", "3"])
+        .output(pattern.to_string_lossy());
+    ffmpeg::run(command).await?;
+    Ok(())
+}
+
+// frame_000001.jpg is the frame sampled at t=0
+fn frame_timestamp(file: &str, interval: f64) -> Option<f64> {
+    let name = Path::new(file).file_stem()?.to_str()?;
+    let index: u64 = name.strip_prefix("frame_")?.parse().ok()?;
+    Some(index.checked_sub(1)? as f64 * interval)
+}
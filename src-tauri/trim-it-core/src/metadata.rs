@@ -0,0 +1,116 @@
+use std::fs::FileTimes;
+use std::path::Path;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+
+use crate::probe::ProbeResult;
+
+// When the source was recorded: the container's creation_time tag if it has
+// one, otherwise (for local files only) the file's modification time, which
+// cameras and phones set to the end of recording at worst.
+pub fn source_creation_time(probe: &ProbeResult, local_file: Option<&Path>) -> Option<DateTime<Utc>> {
+    probe
+        .format
+        .tags
+        .get("creation_time")
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|| {
+            let modified = std::fs::metadata(local_file?).ok()?.modified().ok()?;
+            Some(DateTime::<Utc>::from(modified))
+        })
+}
+
+// Container tags that carry the recording location. Apple devices write the
+// mdta key; FFmpeg reads the classic ©xyz atom as "location".
+const LOCATION_TAGS: &[&str] = &["location", "location-eng", "com.apple.quicktime.location.ISO6709"];
+
+// ISO 6709 location string (e.g. "+37.3349-122.0090+030.000/"), if any
+pub fn source_location(probe: &ProbeResult) -> Option<String> {
+    LOCATION_TAGS
+        .iter()
+        .find_map(|tag| probe.format.tags.get(*tag))
+        .filter(|value| !value.is_empty())
+        .cloned()
+}
+
+// `-metadata` arguments that write (Some) or clear (None) every location tag
+pub fn location_metadata_args(location: Option<&str>) -> Vec<String> {
+    LOCATION_TAGS
+        .iter()
+        .flat_map(|tag| ["-metadata".to_string(), format!("{}={}", tag, location.unwrap_or(""))])
+        .collect()
+}
+
+// Players and platforms read the three-letter ISO 639-2 codes, so two-letter
+// and made-up codes are refused rather than written
+pub fn validate_language(code: &str) -> Result<(), String> {
+    let code = code.trim();
+    if code.is_empty() || (code.len() == 3 && code.chars().all(|c| c.is_ascii_lowercase())) {
+        Ok(())
+    } else {
+        Err(format!("'{}' isn't a three-letter ISO 639-2 language code, like \"eng\"", code))
+    }
+}
+
+// `-metadata:s` arguments tagging the first `count` output streams of
+// `kind` ("a" or "s") with `languages`, in order
+pub fn language_metadata_args(kind: &str, languages: &[String], count: usize) -> Vec<String> {
+    languages
+        .iter()
+        .take(count)
+        .enumerate()
+        .filter(|(_, language)| !language.trim().is_empty())
+        .flat_map(|(index, language)| {
+            [format!("-metadata:s:{}:{}", kind, index), format!("language={}", language.trim())]
+        })
+        .collect()
+}
+
+// Stops FFmpeg copying the source's container, stream and chapter tags, and
+// writing its own encoder tags, so the output carries only what is set
+// explicitly
+pub const STRIP_METADATA_ARGS: [&str; 14] = [
+    "-map_metadata", "-1",
+    "-map_metadata:s:v", "-1",
+    "-map_metadata:s:a", "-1",
+    "-map_chapters", "-1",
+    "-fflags", "+bitexact",
+    "-flags:v", "+bitexact",
+    "-flags:a", "+bitexact",
+];
+
+pub fn parse_creation_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("'{}' isn't a date like 2024-05-01T18:30:00Z", value))
+}
+
+// Value for `-metadata creation_time=...`, in the format QuickTime expects
+pub fn creation_time_tag(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+// Sets modification (and, where the OS supports it, creation) time so the
+// clip sorts by when it was recorded rather than when it was exported.
+pub fn set_file_times(path: &Path, time: DateTime<Utc>) -> Result<(), String> {
+    let time: SystemTime = time.into();
+    let times = FileTimes::new().set_accessed(time).set_modified(time);
+    #[cfg(windows)]
+    let times = {
+        use std::os::windows::fs::FileTimesExt;
+        times.set_created(time)
+    };
+    #[cfg(target_os = "macos")]
+    let times = {
+        use std::os::macos::fs::FileTimesExt;
+        times.set_created(time)
+    };
+
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_times(times))
+        .map_err(|e| format!("Failed to set file times on {}: {}", path.display(), e))
+}
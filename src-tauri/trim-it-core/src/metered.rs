@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::settings::Settings;
+use crate::download;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeteredPolicy {
+    // Treat every connection as metered, e.g. for tethering or a data plan
+    // the OS doesn't know about
+    pub always_metered: bool,
+    // Downloads larger than this need confirming on a metered connection
+    pub confirm_above_mb: f64,
+}
+
+impl Default for MeteredPolicy {
+    fn default() -> Self {
+        Self {
+            always_metered: false,
+            confirm_above_mb: 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadEstimate {
+    // None when the site doesn't publish sizes
+    pub bytes: Option<u64>,
+    pub metered: bool,
+    // The job will be refused unless it's run with allow_metered_download
+    pub needs_confirmation: bool,
+}
+
+pub async fn is_metered(policy: &MeteredPolicy) -> bool {
+    policy.always_metered || os_reports_metered().await
+}
+
+// Windows marks cellular and user-flagged connections as Fixed or Variable
+// cost; WinRT is easiest to reach through PowerShell
+#[cfg(windows)]
+async fn os_reports_metered() -> bool {
+    const SCRIPT: &str = "[void][Windows.Networking.Connectivity.NetworkInformation, Windows.Networking.Connectivity, ContentType = WindowsRuntime]; \
+        $connection = [Windows.Networking.Connectivity.NetworkInformation]::GetInternetConnectionProfile(); \
+        if ($connection) { $connection.GetConnectionCost().NetworkCostType }";
+    match tokio::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .output()
+        .await
+    {
+        Ok(output) => matches!(String::from_utf8_lossy(&output.stdout).trim(), "Fixed" | "Variable"),
+        Err(e) => {
+            log::warn!("Failed to check whether the connection is metered: {}", e);
+            false
+        }
+    }
+}
+
+// Other platforms only have the user's toggle
+#[cfg(not(windows))]
+async fn os_reports_metered() -> bool {
+    false
+}
+
+// Roughly what running the job would download: the trimmed section of a
+// YouTube video at the quality jobs download, or all of a direct link
+pub async fn estimate_download(
+    source: &str,
+    start_time: &str,
+    end_time: &str,
+    settings: &Settings,
+) -> Result<DownloadEstimate, String> {
+    let bytes = download::estimate_size(source, start_time, end_time, settings).await?;
+    let metered = is_metered(&settings.metered).await;
+    Ok(DownloadEstimate {
+        bytes,
+        metered,
+        needs_confirmation: metered && needs_confirmation(bytes, &settings.metered),
+    })
+}
+
+// Refuses a download of `bytes` (None when unknown) over the threshold
+// while on a metered connection, saying how large it is
+pub async fn check_download(bytes: Option<u64>, settings: &Settings) -> Result<(), String> {
+    if !is_metered(&settings.metered).await {
+        return Ok(());
+    }
+    if !needs_confirmation(bytes, &settings.metered) {
+        return Ok(());
+    }
+    Err(match bytes {
+        Some(bytes) => format!(
+            "This download is about {:.0} MB and the connection is metered. Confirm to download it anyway.",
+            bytes as f64 / 1_000_000.0
+        ),
+        None => "The connection is metered and the download size is unknown. Confirm to download it anyway.".to_string(),
+    })
+}
+
+// An unknown size needs confirming too; on a data plan the surprise is what
+// this is meant to prevent
+fn needs_confirmation(bytes: Option<u64>, policy: &MeteredPolicy) -> bool {
+    bytes.map_or(true, |bytes| bytes as f64 > policy.confirm_above_mb * 1_000_000.0)
+}
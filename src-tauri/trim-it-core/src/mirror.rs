@@ -0,0 +1,76 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::privacy;
+
+// A second folder (NAS share, external drive) that every finished output is
+// copied to, as a simple backup of the output folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorConfig {
+    pub dir: String,
+    // Further attempts after a failed copy, e.g. while a NAS wakes up
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Copies `file` into the mirror folder and checks the copy's hash against the
+// original. The copy is written under a temporary name first, so an
+// interrupted copy never looks like a finished one.
+pub async fn mirror_file(file: &Path, config: &MirrorConfig) -> Result<PathBuf, String> {
+    let mut attempt = 0;
+    loop {
+        match copy_verified(file, Path::new(&config.dir)) {
+            Ok(copy) => return Ok(copy),
+            Err(e) if attempt < config.retries => {
+                attempt += 1;
+                log::warn!("Mirror attempt {} failed: {}. Retrying in {:?}", attempt, privacy::scrub(&e), RETRY_DELAY);
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn copy_verified(file: &Path, dir: &Path) -> Result<PathBuf, String> {
+    // A missing folder is an unmounted drive or share, not something to create
+    if !dir.is_dir() {
+        return Err(format!("The mirror folder {} is offline or missing", dir.display()));
+    }
+    let name = file.file_name().ok_or("Output has no file name")?;
+    let target = dir.join(name);
+    let partial = dir.join(format!("{}.partial", name.to_string_lossy()));
+
+    std::fs::copy(file, &partial).map_err(|e| format!("Failed to copy to the mirror folder: {}", e))?;
+    if file_hash(file)? != file_hash(&partial)? {
+        let _ = std::fs::remove_file(&partial);
+        return Err("The mirrored copy doesn't match the original".to_string());
+    }
+    std::fs::rename(&partial, &target).map_err(|e| format!("Failed to finish the mirrored copy: {}", e))?;
+    Ok(target)
+}
+
+fn file_hash(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
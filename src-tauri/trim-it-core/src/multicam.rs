@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorCode, TrimError};
+use crate::job::TrimOptions;
+use crate::{sync, timecode};
+use crate::trim::{TrimJob, TrimJobBuilder};
+
+// One recording of the event. `offset_seconds` is where the reference
+// recording's start falls in this one: 2.5 if this camera started rolling
+// 2.5 s before the reference, -1.0 if it started a second after. Left unset,
+// it is found by matching the audio against the reference.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MulticamSource {
+    pub source: String,
+    #[serde(default)]
+    pub offset_seconds: Option<f64>,
+}
+
+// The same moment of an event cut from several recordings, e.g. a podcast
+// with a local track per speaker. The range is on the reference timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MulticamJob {
+    pub sources: Vec<MulticamSource>,
+    pub start_time: String,
+    pub end_time: String,
+    #[serde(default = "default_ratio")]
+    pub ratio: String,
+    #[serde(default)]
+    pub options: TrimOptions,
+}
+
+fn default_ratio() -> String {
+    "Original".to_string()
+}
+
+impl MulticamJob {
+    // Fills in the offsets left unset. The first source is the reference.
+    pub async fn sync_offsets(&mut self) -> Result<(), TrimError> {
+        let Some((reference, others)) = self.sources.split_first_mut() else {
+            return Ok(());
+        };
+        reference.offset_seconds.get_or_insert(0.0);
+        for (index, camera) in others.iter_mut().enumerate() {
+            if camera.offset_seconds.is_some() {
+                continue;
+            }
+            if reference.source.contains("://") || camera.source.contains("://") {
+                return Err(ErrorCode::InvalidOptions
+                    .error("Only local files can be synced by their audio; enter the offset instead"));
+            }
+            let found = sync::find_sync_offset(Path::new(&reference.source), Path::new(&camera.source))
+                .await
+                .map_err(|e| ErrorCode::InvalidOptions.error(format!("Couldn't sync source {}: {}", index + 2, e)))?;
+            log::info!("Synced source {} at {:.3}s", index + 2, found.offset_seconds);
+            camera.offset_seconds = Some(found.offset_seconds);
+        }
+        Ok(())
+    }
+
+    // One job per source, trimmed to the aligned range and named after a
+    // shared stem so the files sort together. `configure` adds what every
+    // job needs (settings, plugins, listeners).
+    pub fn jobs(&self, configure: impl Fn(TrimJobBuilder) -> Result<TrimJobBuilder, String>) -> Result<Vec<TrimJob>, TrimError> {
+        if self.sources.len() < 2 {
+            return Err(ErrorCode::InvalidOptions.error("A multicam trim needs at least two sources"));
+        }
+        let invalid_range = |e: String| ErrorCode::InvalidRange.error(e);
+        let start = timecode::time_to_seconds(&self.start_time).map_err(invalid_range)?;
+        let end = timecode::time_to_seconds(&self.end_time).map_err(invalid_range)?;
+        let stem = format!("multicam_{}", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(index, camera)| {
+                let offset = camera
+                    .offset_seconds
+                    .ok_or_else(|| ErrorCode::InvalidOptions.error(format!("Source {} hasn't been synced", index + 1)))?;
+                if !offset.is_finite() {
+                    return Err(ErrorCode::InvalidOptions.error(format!(
+                        "Offset for source {} must be a finite number of seconds",
+                        index + 1
+                    )));
+                }
+                let aligned_start = start + offset;
+                if aligned_start < 0.0 {
+                    return Err(ErrorCode::InvalidRange.error(format!(
+                        "Source {} started recording {:.1}s after the range begins",
+                        index + 1,
+                        -aligned_start
+                    )));
+                }
+                let builder = TrimJob::builder(
+                    camera.source.clone(),
+                    timecode::format_timecode(aligned_start),
+                    timecode::format_timecode(end + offset),
+                )
+                .ratio(self.ratio.clone())
+                .options(self.options.clone())
+                .output_name(format!("{}_source{}", stem, index + 1));
+                configure(builder)?.build()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+// Where finished trims are saved. dirs asks the OS (Known Folders on
+// Windows, XDG user-dirs on Linux), so relocated and localized Downloads
+// folders are found; home/Downloads and the working dir are last resorts.
+pub fn output_dir() -> PathBuf {
+    dirs::download_dir()
+        .or_else(|| dirs::home_dir().map(|home| home.join("Downloads")))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// Machine-wide defaults IT can deploy for every user. Only administrators
+// can write here, unlike the per-user config dir.
+pub fn machine_config_dir() -> Option<PathBuf> {
+    match std::env::consts::OS {
+        "windows" => std::env::var_os("ProgramData").map(|dir| PathBuf::from(dir).join("trim-it")),
+        "macos" => Some(PathBuf::from("/Library/Application Support/trim-it")),
+        _ => Some(PathBuf::from("/etc/trim-it")),
+    }
+}
+
+// A folder chosen in settings, e.g. on a drive with more room than the
+// system one
+static CONFIGURED_TEMP_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_temp_dir(dir: Option<PathBuf>) {
+    *CONFIGURED_TEMP_DIR.write().unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+// Where downloads and intermediate files are staged while a job runs
+pub fn temp_dir() -> PathBuf {
+    CONFIGURED_TEMP_DIR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+// A scratch folder under temp_dir(), deleted when dropped
+pub fn tempdir() -> std::io::Result<tempfile::TempDir> {
+    let dir = temp_dir();
+    std::fs::create_dir_all(&dir)?;
+    tempfile::tempdir_in(dir)
+}
@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::job::TrimOptions;
+use crate::privacy;
+
+// Plugins are executables, each in its own directory under the plugins dir
+// next to a plugin.json manifest:
+//   {"name": "media-server", "command": "resolve.py", "sources": ["https://media.example.com/"]}
+//
+// A source plugin is run as `<command> <args> resolve-source <url> <dir>`,
+// downloads the video into <dir> and prints {"path": "..."} on stdout.
+// A filter plugin is run as `<command> <args> filters`, reads a FilterRequest
+// as JSON on stdin and prints {"video": [...], "audio": [...]} with FFmpeg
+// filters to append to the job's chains.
+const MANIFEST_FILE: &str = "plugin.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    // Relative to the plugin's directory, or a program on PATH
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    // URL prefixes this plugin downloads instead of the built-in resolvers
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub filters: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Plugin {
+    pub manifest: PluginManifest,
+    dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterRequest<'a> {
+    pub source: &'a str,
+    // Trimmed range in seconds of the file FFmpeg reads
+    pub start: f64,
+    pub end: f64,
+    pub ratio: &'a str,
+    pub options: &'a TrimOptions,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct FilterStage {
+    pub video: Vec<String>,
+    pub audio: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolvedSource {
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Plugin>,
+}
+
+impl PluginRegistry {
+    // Loads every plugin under `dir`. A broken manifest only disables that
+    // plugin; a missing dir is an empty registry.
+    pub fn discover(dir: &Path) -> Self {
+        let mut plugins: Vec<Plugin> = std::fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|path| path.join(MANIFEST_FILE).is_file())
+            .filter_map(|plugin_dir| match load_manifest(&plugin_dir) {
+                Ok(manifest) => Some(Plugin { manifest, dir: plugin_dir }),
+                Err(e) => {
+                    log::warn!("Skipping plugin in {}: {}", privacy::scrub(&plugin_dir.display().to_string()), privacy::scrub(&e));
+                    None
+                }
+            })
+            .collect();
+        plugins.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+        Self { plugins }
+    }
+
+    pub fn manifests(&self) -> Vec<PluginManifest> {
+        self.plugins.iter().map(|plugin| plugin.manifest.clone()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| plugin.manifest.name == name)
+    }
+
+    pub fn resolver_for(&self, source: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| {
+            plugin
+                .manifest
+                .sources
+                .iter()
+                .any(|prefix| !prefix.is_empty() && source.starts_with(prefix.as_str()))
+        })
+    }
+}
+
+fn load_manifest(plugin_dir: &Path) -> Result<PluginManifest, String> {
+    let contents = std::fs::read_to_string(plugin_dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to read {}: {}", MANIFEST_FILE, e))?;
+    let manifest: PluginManifest =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid {}: {}", MANIFEST_FILE, e))?;
+    if manifest.name.trim().is_empty() || manifest.command.trim().is_empty() {
+        return Err("Plugin manifest needs a name and a command".to_string());
+    }
+    Ok(manifest)
+}
+
+impl Plugin {
+    pub async fn resolve_source(&self, url: &str, dest_dir: &Path) -> Result<PathBuf, String> {
+        let dest_arg = dest_dir.to_string_lossy().to_string();
+        let stdout = self.run(&["resolve-source", url, &dest_arg], None).await?;
+        let resolved: ResolvedSource = serde_json::from_str(stdout.trim())
+            .map_err(|e| format!("Plugin '{}' returned an invalid source: {}", self.manifest.name, e))?;
+        if !resolved.path.exists() {
+            return Err(format!(
+                "Plugin '{}' reported a file that doesn't exist: {}",
+                self.manifest.name,
+                resolved.path.display()
+            ));
+        }
+        Ok(resolved.path)
+    }
+
+    pub async fn filter_stage(&self, request: &FilterRequest<'_>) -> Result<FilterStage, String> {
+        if !self.manifest.filters {
+            return Err(format!("Plugin '{}' does not provide filters", self.manifest.name));
+        }
+        let input = serde_json::to_vec(request).map_err(|e| format!("Failed to serialize filter request: {}", e))?;
+        let stdout = self.run(&["filters"], Some(&input)).await?;
+        serde_json::from_str(stdout.trim())
+            .map_err(|e| format!("Plugin '{}' returned invalid filters: {}", self.manifest.name, e))
+    }
+
+    async fn run(&self, action: &[&str], stdin: Option<&[u8]>) -> Result<String, String> {
+        let bundled = self.dir.join(&self.manifest.command);
+        let program = if bundled.is_file() { bundled } else { PathBuf::from(&self.manifest.command) };
+
+        let mut child = Command::new(&program)
+            .args(&self.manifest.args)
+            .args(action)
+            .current_dir(&self.dir)
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run plugin '{}': {}", self.manifest.name, e))?;
+
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input)
+                .await
+                .map_err(|e| format!("Failed to write to plugin '{}': {}", self.manifest.name, e))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| format!("Failed to run plugin '{}': {}", self.manifest.name, e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Plugin '{}' exited with {}: {}",
+                self.manifest.name,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
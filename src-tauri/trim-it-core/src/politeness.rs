@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use url::Url;
+
+// Limits on how hard downloads lean on one site, so a batch against the same
+// host doesn't get the user's IP blocked partway through
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Politeness {
+    // For hosts without a rule of their own
+    pub default: Option<HostLimits>,
+    // Keyed by domain, subdomains included ("example.com" also covers
+    // "cdn.example.com"); the most specific domain wins
+    pub domains: HashMap<String, HostLimits>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HostLimits {
+    // Minimum time between the starts of two downloads
+    #[serde(default)]
+    pub delay_seconds: f64,
+    // Downloads allowed to run at once; 0 means no cap
+    #[serde(default)]
+    pub max_concurrent: u32,
+}
+
+impl Politeness {
+    fn limits_for(&self, host: &str) -> Option<(String, HostLimits)> {
+        self.domains
+            .iter()
+            .map(|(domain, limits)| (domain.trim().trim_start_matches('.').to_ascii_lowercase(), limits))
+            .filter(|(domain, _)| !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain))))
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(domain, limits)| (domain, *limits))
+            .or_else(|| self.default.map(|limits| (host.to_string(), limits)))
+    }
+}
+
+// Shared by every job in the process, per domain the limits apply to
+struct HostGate {
+    limits: HostLimits,
+    slots: Option<Arc<Semaphore>>,
+    next_start: tokio::sync::Mutex<Instant>,
+}
+
+static GATES: Mutex<Option<HashMap<String, Arc<HostGate>>>> = Mutex::new(None);
+
+// Held for the length of a download to keep its place in the host's cap
+pub struct HostPermit {
+    _slot: Option<OwnedSemaphorePermit>,
+}
+
+// Waits until a download from `url` may start under `politeness`. Returns
+// None straight away for hosts without limits.
+pub async fn acquire(url: &str, politeness: &Politeness) -> Result<Option<HostPermit>, String> {
+    let Some(host) = Url::parse(url).ok().and_then(|url| url.host_str().map(|h| h.to_ascii_lowercase())) else {
+        return Ok(None);
+    };
+    let Some((key, limits)) = politeness.limits_for(&host) else {
+        return Ok(None);
+    };
+    if !limits.delay_seconds.is_finite() || limits.delay_seconds < 0.0 {
+        return Err(format!("The request delay for {} must be zero or more seconds", key));
+    }
+
+    let gate = {
+        let mut gates = GATES.lock().unwrap_or_else(|e| e.into_inner());
+        let gates = gates.get_or_insert_with(HashMap::new);
+        // A changed rule starts a fresh gate; downloads already running
+        // finish under the old one
+        match gates.get(&key).filter(|gate| gate.limits == limits) {
+            Some(gate) => gate.clone(),
+            None => {
+                let gate = Arc::new(HostGate {
+                    limits,
+                    slots: (limits.max_concurrent > 0)
+                        .then(|| Arc::new(Semaphore::new(limits.max_concurrent as usize))),
+                    next_start: tokio::sync::Mutex::new(Instant::now()),
+                });
+                gates.insert(key.clone(), gate.clone());
+                gate
+            }
+        }
+    };
+
+    let slot = match &gate.slots {
+        Some(slots) => Some(
+            slots
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|e| format!("Download slot unavailable: {}", e))?,
+        ),
+        None => None,
+    };
+    // Starts are spaced out one after another while the lock is held
+    let mut next_start = gate.next_start.lock().await;
+    let now = Instant::now();
+    if *next_start > now {
+        log::info!("Waiting {:?} before the next download from {}", *next_start - now, key);
+        tokio::time::sleep_until((*next_start).into()).await;
+    }
+    *next_start = Instant::now() + Duration::from_secs_f64(limits.delay_seconds);
+    Ok(Some(HostPermit { _slot: slot }))
+}
@@ -0,0 +1,19 @@
+use std::path::{Path, PathBuf};
+
+use crate::ffmpeg;
+
+// Writes the clip's first frame next to it as "<clip name>.jpg", so galleries
+// can show the still and swap in the video on hover without a visible jump.
+pub async fn write_poster_frame(clip: &Path) -> Result<PathBuf, String> {
+    let poster_path = clip.with_extension("jpg");
+    let mut command = ffmpeg::new_command();
+    command
+        .input(clip.to_string_lossy())
+        .args(["-frames:v", "1", "-q:v", "2"])
+        .output(poster_path.to_string_lossy())
+        .overwrite();
+    ffmpeg::run(command)
+        .await
+        .map_err(|e| format!("Failed to write poster frame: {}", e))?;
+    Ok(poster_path)
+}
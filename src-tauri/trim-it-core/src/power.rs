@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// What to do with an encode when a laptop is running on battery
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatteryPolicy {
+    pub action: BatteryAction,
+    // Only act once the charge drops below this; always on battery when unset
+    pub below_percent: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryAction {
+    // Wait for AC power before starting
+    #[default]
+    Defer,
+    // Encode on fewer cores, which runs cooler and drains the battery slower
+    Throttle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    pub percent: f32,
+}
+
+// None on machines without a battery, or when it can't be read
+pub fn power_status() -> Option<PowerStatus> {
+    let manager = battery::Manager::new().ok()?;
+    let batteries: Vec<battery::Battery> = manager.batteries().ok()?.filter_map(Result::ok).collect();
+    if batteries.is_empty() {
+        return None;
+    }
+    let on_battery = batteries
+        .iter()
+        .all(|b| b.state() == battery::State::Discharging);
+    let percent = batteries
+        .iter()
+        .map(|b| b.state_of_charge().value * 100.0)
+        .sum::<f32>()
+        / batteries.len() as f32;
+    Some(PowerStatus { on_battery, percent })
+}
+
+impl BatteryPolicy {
+    fn applies(&self, status: &PowerStatus) -> bool {
+        status.on_battery && self.below_percent.map_or(true, |below| status.percent < below as f32)
+    }
+
+    // Holds a deferred encode until the machine is back on AC power (or
+    // charged past the threshold). Returns the encoder thread count to use
+    // when the encode should be throttled instead.
+    pub async fn wait_for_power(&self, notify: impl Fn(String)) -> Option<usize> {
+        let status = power_status().filter(|status| self.applies(status))?;
+        match self.action {
+            BatteryAction::Throttle => {
+                let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+                let threads = (cores / 4).max(1);
+                notify(format!(
+                    "Running on battery ({:.0}%), encoding on {} of {} cores.",
+                    status.percent, threads, cores
+                ));
+                Some(threads)
+            }
+            BatteryAction::Defer => {
+                notify(format!(
+                    "Running on battery ({:.0}%). The encode will start once the charger is connected.",
+                    status.percent
+                ));
+                while power_status().is_some_and(|status| self.applies(&status)) {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                notify("Back on AC power, starting the encode.".to_string());
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::filters;
+use crate::job::{Resolution, TrimOptions, VideoCodec};
+use crate::privacy;
+
+const MAX_NAME_LEN: usize = 64;
+
+// Export settings saved under a name, e.g. "Discord under 25MB", for jobs to
+// pick instead of spelling out the ratio and options each time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default = "default_ratio")]
+    pub ratio: String,
+    #[serde(default)]
+    pub options: TrimOptions,
+    // Replaces the settings' output folder for jobs using the preset
+    #[serde(default)]
+    pub output_dir: Option<String>,
+}
+
+fn default_ratio() -> String {
+    "Original".to_string()
+}
+
+impl Preset {
+    fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() || name != self.name || name.chars().count() > MAX_NAME_LEN {
+            return Err(format!(
+                "Preset names can't be blank or start or end with spaces (up to {} characters)",
+                MAX_NAME_LEN
+            ));
+        }
+        filters::validate_ratio(&self.ratio)
+    }
+}
+
+// What a new install starts with; they can be edited or deleted like any other
+fn built_in_presets() -> Vec<Preset> {
+    vec![
+        Preset {
+            name: "YouTube Shorts".to_string(),
+            ratio: "9:16".to_string(),
+            options: TrimOptions {
+                normalize_loudness: true,
+                ..Default::default()
+            },
+            output_dir: None,
+        },
+        Preset {
+            name: "Discord under 25MB".to_string(),
+            ratio: default_ratio(),
+            options: TrimOptions {
+                max_size_mb: Some(25.0),
+                ..Default::default()
+            },
+            output_dir: None,
+        },
+        Preset {
+            name: "Archive HEVC".to_string(),
+            ratio: default_ratio(),
+            options: TrimOptions {
+                resolution: Resolution::Source,
+                video_codec: Some(VideoCodec::H265),
+                ..Default::default()
+            },
+            output_dir: None,
+        },
+    ]
+}
+
+pub struct PresetState {
+    path: RwLock<PathBuf>,
+    presets: Mutex<Vec<Preset>>,
+}
+
+impl PresetState {
+    pub fn load(path: PathBuf) -> Self {
+        Self {
+            presets: Mutex::new(read_presets(&path)),
+            path: RwLock::new(path),
+        }
+    }
+
+    // Switches to another presets file, e.g. when the profile changes
+    pub fn reload(&self, path: PathBuf) {
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        *presets = read_presets(&path);
+        *self.path.write().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+
+    pub fn list(&self) -> Vec<Preset> {
+        self.presets.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn get(&self, name: &str) -> Result<Preset, String> {
+        let presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .cloned()
+            .ok_or_else(|| format!("No preset named \"{}\"", name))
+    }
+
+    pub fn create(&self, preset: Preset) -> Result<(), String> {
+        preset.validate()?;
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        if presets.iter().any(|existing| existing.name == preset.name) {
+            return Err(format!("A preset named \"{}\" already exists", preset.name));
+        }
+        presets.push(preset);
+        save(&self.path.read().unwrap_or_else(|e| e.into_inner()), &presets)
+    }
+
+    // Replaces the preset called `name`, which `preset` may rename
+    pub fn update(&self, name: &str, preset: Preset) -> Result<(), String> {
+        preset.validate()?;
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        if preset.name != name && presets.iter().any(|existing| existing.name == preset.name) {
+            return Err(format!("A preset named \"{}\" already exists", preset.name));
+        }
+        let existing = presets
+            .iter_mut()
+            .find(|existing| existing.name == name)
+            .ok_or_else(|| format!("No preset named \"{}\"", name))?;
+        *existing = preset;
+        save(&self.path.read().unwrap_or_else(|e| e.into_inner()), &presets)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let mut presets = self.presets.lock().unwrap_or_else(|e| e.into_inner());
+        let count = presets.len();
+        presets.retain(|preset| preset.name != name);
+        if presets.len() == count {
+            return Err(format!("No preset named \"{}\"", name));
+        }
+        save(&self.path.read().unwrap_or_else(|e| e.into_inner()), &presets)
+    }
+}
+
+fn read_presets(path: &Path) -> Vec<Preset> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable presets file {}: {}", privacy::scrub(&path.display().to_string()), e);
+            Vec::new()
+        }),
+        Err(_) => built_in_presets(),
+    }
+}
+
+fn save(path: &Path, presets: &[Preset]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(presets).map_err(|e| format!("Failed to serialize presets: {}", e))?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to save presets: {}", e))
+}
@@ -0,0 +1,186 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use sha2::{Digest, Sha256};
+
+use crate::job::TrimOptions;
+
+// Process-wide so every log call site sees the active profile's setting
+// without having settings threaded through to it.
+static REDACT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_redaction(enabled: bool) {
+    REDACT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn redaction_enabled() -> bool {
+    REDACT.load(Ordering::Relaxed)
+}
+
+// A stable stand-in for a URL, path or title: the same value always gives
+// the same hash, so entries can still be correlated when diagnosing.
+pub fn hash_value(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    let hex: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}{}", REDACTED_PREFIX, hex)
+}
+
+const REDACTED_PREFIX: &str = "redacted:";
+
+pub fn is_redacted(value: &str) -> bool {
+    value.starts_with(REDACTED_PREFIX)
+}
+
+// For values known to be sensitive (sources, output paths)
+pub fn redact(value: &str) -> String {
+    if redaction_enabled() {
+        hash_value(value)
+    } else {
+        value.to_string()
+    }
+}
+
+// Tags FFmpeg lists as "title : ..." lines when it reads or writes a file
+const TAG_KEYS: &[&str] = &["title", "artist", "album", "comment", "description"];
+
+// For free-form messages such as errors, which often quote a URL or path.
+// Anything that looks like one is replaced; the rest of the text is kept so
+// the log still says what went wrong. A quoted value is judged whole, so a
+// path with spaces in it is hashed as one.
+pub fn scrub(message: &str) -> String {
+    if !redaction_enabled() {
+        return message.to_string();
+    }
+    if let Some((key, value)) = message.split_once(':') {
+        if TAG_KEYS.contains(&key.trim().to_ascii_lowercase().as_str()) && !value.trim().is_empty() {
+            return format!("{}: {}", key, hash_value(value.trim()));
+        }
+    }
+
+    let mut scrubbed = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(open) = rest.find('"') {
+        let Some(len) = rest[open + 1..].find('"') else {
+            break;
+        };
+        let quoted = &rest[open + 1..open + 1 + len];
+        scrubbed.push_str(&scrub_words(&rest[..open]));
+        scrubbed.push('"');
+        if looks_sensitive(quoted) {
+            scrubbed.push_str(&hash_value(quoted));
+        } else {
+            scrubbed.push_str(&scrub_words(quoted));
+        }
+        scrubbed.push('"');
+        rest = &rest[open + len + 2..];
+    }
+    scrubbed.push_str(&scrub_words(rest));
+    scrubbed
+}
+
+// A command line about to be logged. Each argument is judged whole, and
+// metadata values such as a title are always hashed: both are usually
+// several words, which `scrub` would otherwise see one at a time.
+pub fn redact_args(args: &[String]) -> Vec<String> {
+    if !redaction_enabled() {
+        return args.to_vec();
+    }
+    let mut redacted = Vec::with_capacity(args.len());
+    for (index, arg) in args.iter().enumerate() {
+        let after_metadata = index > 0 && args[index - 1].starts_with("-metadata");
+        let value = match arg.split_once('=') {
+            Some((key, value)) if after_metadata => format!("{}={}", key, hash_value(value)),
+            _ if looks_sensitive(arg) => hash_value(arg),
+            _ => arg.clone(),
+        };
+        redacted.push(value);
+    }
+    redacted
+}
+
+fn scrub_words(text: &str) -> String {
+    text.split(' ')
+        .map(|token| {
+            let trimmed = token
+                .trim_matches(|c: char| matches!(c, '"' | '\'' | '(' | ')' | ',' | ';'))
+                .trim_end_matches(':');
+            if looks_sensitive(trimmed) {
+                token.replace(trimmed, &hash_value(trimmed))
+            } else {
+                token.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn looks_sensitive(token: &str) -> bool {
+    const MEDIA_EXTENSIONS: &[&str] = &[
+        ".mp4", ".mkv", ".mov", ".webm", ".avi", ".m4a", ".mp3", ".wav", ".srt", ".ass", ".vtt", ".png", ".jpg", ".json",
+    ];
+    let lower = token.to_ascii_lowercase();
+    token.contains("://")
+        || token.contains('/')
+        || token.contains('\\')
+        || MEDIA_EXTENSIONS.iter().any(|ext| lower.ends_with(ext))
+}
+
+// History keeps what was done, but not which files were involved
+pub fn redact_options(options: &TrimOptions) -> TrimOptions {
+    let mut options = options.clone();
+    if !redaction_enabled() {
+        return options;
+    }
+    options.replacement_audio = options.replacement_audio.as_deref().map(hash_value);
+    options.subtitles.file = options.subtitles.file.as_deref().map(hash_value);
+    options.color.lut = options.color.lut.as_deref().map(hash_value);
+    if let Some(music) = options.background_music.as_mut() {
+        music.path = hash_value(&music.path);
+    }
+    if let Some(watermark) = options.watermark.as_mut() {
+        watermark.path = hash_value(&watermark.path);
+    }
+    for tag in [&mut options.tags.title, &mut options.tags.artist, &mut options.tags.comment] {
+        *tag = tag.as_deref().map(hash_value);
+    }
+    if let Some(overlay) = options.text_overlay.as_mut() {
+        overlay.text = hash_value(&overlay.text);
+        overlay.font_file = overlay.font_file.as_deref().map(hash_value);
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_a_multi_word_title_whole() {
+        set_redaction(true);
+        let args: Vec<String> = ["-metadata", "title=My Holiday Clip", "-c:v", "libx264"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let redacted = redact_args(&args);
+        assert_eq!(redacted[1], format!("title={}", hash_value("My Holiday Clip")));
+        assert_eq!(redacted[3], "libx264");
+
+        let line = scrub("    title           : My Holiday Clip");
+        assert!(!line.contains("Holiday"), "{}", line);
+
+        let mut options = TrimOptions::default();
+        options.tags.title = Some("My Holiday Clip".to_string());
+        let options = redact_options(&options);
+        assert_eq!(options.tags.title, Some(hash_value("My Holiday Clip")));
+    }
+
+    #[test]
+    fn hashes_a_quoted_path_with_spaces_whole() {
+        set_redaction(true);
+        let path = "/home/me/My Videos/clip one.mp4";
+        let line = scrub(&format!("Merging formats into \"{}\" (took 2s)", path));
+        assert_eq!(line, format!("Merging formats into \"{}\" (took 2s)", hash_value(path)));
+
+        let args = vec!["-i".to_string(), path.to_string()];
+        assert_eq!(redact_args(&args)[1], hash_value(path));
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::{cancel, ffmpeg};
+
+// Subset of `ffprobe -print_format json -show_format -show_streams` output.
+// Numeric fields ffprobe reports as strings are kept as strings here and
+// parsed by the helpers below.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProbeResult {
+    #[serde(default)]
+    pub streams: Vec<ProbeStream>,
+    #[serde(default)]
+    pub format: ProbeFormat,
+    #[serde(default)]
+    pub chapters: Vec<ProbeChapter>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProbeChapter {
+    // Seconds, as strings
+    pub start_time: Option<String>,
+    pub end_time: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProbeFormat {
+    pub start_time: Option<String>,
+    pub duration: Option<String>,
+    pub size: Option<String>,
+    pub bit_rate: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProbeStream {
+    #[serde(default)]
+    pub index: u32,
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    pub channels: Option<u32>,
+    pub sample_rate: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub pix_fmt: Option<String>,
+    pub bit_rate: Option<String>,
+    // Fractions such as "30000/1001". r_frame_rate is the rate the stream
+    // is timed at, avg_frame_rate the rate it actually averaged.
+    pub r_frame_rate: Option<String>,
+    pub avg_frame_rate: Option<String>,
+    pub color_transfer: Option<String>,
+    // 1 when the video carries CEA-608/708 captions (A53 side data)
+    #[serde(default)]
+    pub closed_captions: u8,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub side_data_list: Vec<SideData>,
+    // "default", "forced", ... mapped to 0 or 1
+    #[serde(default)]
+    pub disposition: HashMap<String, u8>,
+}
+
+// High dynamic range formats, told apart by their transfer function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+    // PQ, as recorded by most HDR cameras and phones outside Apple's
+    Hdr10,
+    // Hybrid log-gamma, as recorded by iPhones and broadcast cameras
+    Hlg,
+}
+
+impl HdrFormat {
+    // FFmpeg's name for the transfer characteristic
+    pub fn transfer(self) -> &'static str {
+        match self {
+            HdrFormat::Hdr10 => "smpte2084",
+            HdrFormat::Hlg => "arib-std-b67",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SideData {
+    pub rotation: Option<f64>,
+}
+
+impl ProbeStream {
+    // As opposed to bitmap subtitles such as PGS, DVB or VobSub, which only
+    // mkv can hold
+    pub fn is_text_subtitle(&self) -> bool {
+        matches!(
+            self.codec_name.as_deref(),
+            Some("subrip" | "srt" | "ass" | "ssa" | "mov_text" | "webvtt" | "text")
+        )
+    }
+
+    // Display rotation in clockwise degrees (0, 90, 180, 270). Phones record
+    // portrait video as landscape frames plus this metadata; newer FFmpeg
+    // reports it as a display matrix, older builds as a "rotate" tag.
+    pub fn rotation(&self) -> u32 {
+        let degrees = self
+            .side_data_list
+            .iter()
+            .find_map(|side_data| side_data.rotation)
+            // The display matrix rotation is counter-clockwise
+            .map(|rotation| -rotation)
+            .or_else(|| self.tags.get("rotate").and_then(|r| r.parse::<f64>().ok()))
+            .unwrap_or(0.0);
+        ((degrees.round() as i64).rem_euclid(360) as u32 + 45) / 90 % 4 * 90
+    }
+
+    pub fn hdr_format(&self) -> Option<HdrFormat> {
+        match self.color_transfer.as_deref()? {
+            "smpte2084" => Some(HdrFormat::Hdr10),
+            "arib-std-b67" => Some(HdrFormat::Hlg),
+            _ => None,
+        }
+    }
+
+    pub fn bit_rate_kbps(&self) -> Option<f64> {
+        let bit_rate: f64 = self.bit_rate.as_deref()?.parse().ok()?;
+        Some(bit_rate / 1000.0)
+    }
+
+    pub fn frame_rate(&self) -> Option<f64> {
+        parse_frame_rate(self.avg_frame_rate.as_deref()?)
+    }
+
+    // Phones and OBS vary the frame timing with load, which shows up as an
+    // average that is off the stream's nominal rate. Interlaced streams can
+    // report the field rate as nominal, so exact doubles don't count.
+    pub fn is_variable_frame_rate(&self) -> bool {
+        let nominal = self.r_frame_rate.as_deref().and_then(parse_frame_rate);
+        match nominal.zip(self.frame_rate()) {
+            Some((nominal, average)) => {
+                (nominal - average).abs() / nominal > VFR_TOLERANCE
+                    && (nominal - average * 2.0).abs() / nominal > VFR_TOLERANCE
+            }
+            None => false,
+        }
+    }
+
+    // Rate to convert a variable stream to: the standard rate nearest its
+    // average, or the average itself when it isn't close to one
+    pub fn constant_frame_rate(&self) -> Option<String> {
+        let average = self.frame_rate()?;
+        let standard = STANDARD_FRAME_RATES
+            .iter()
+            .filter_map(|rate| parse_frame_rate(rate).map(|value| (*rate, value)))
+            .min_by(|(_, a), (_, b)| (a - average).abs().total_cmp(&(b - average).abs()))
+            .filter(|(_, value)| (value - average).abs() / value <= STANDARD_RATE_SNAP)
+            .map(|(rate, _)| rate.to_string());
+        Some(standard.unwrap_or_else(|| format!("{:.3}", average)))
+    }
+}
+
+// Relative gap between the nominal and average rates that counts as variable
+const VFR_TOLERANCE: f64 = 0.005;
+const STANDARD_RATE_SNAP: f64 = 0.03;
+const STANDARD_FRAME_RATES: [&str; 8] = [
+    "24000/1001", "24", "25", "30000/1001", "30", "50", "60000/1001", "60",
+];
+
+fn parse_frame_rate(fraction: &str) -> Option<f64> {
+    let (num, den) = fraction.split_once('/').unwrap_or((fraction, "1"));
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    Some(num / den).filter(|rate| rate.is_finite() && *rate > 0.0)
+}
+
+// An audio or subtitle stream as offered for selection in the UI. `index` is
+// the stream's absolute index in the source, as used by TrimOptions.
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamInfo {
+    pub index: u32,
+    pub kind: String,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub channels: Option<u32>,
+    pub default: bool,
+}
+
+impl ProbeResult {
+    pub fn selectable_streams(&self) -> Vec<StreamInfo> {
+        let mut streams: Vec<StreamInfo> = self
+            .streams
+            .iter()
+            .filter(|s| matches!(s.codec_type.as_deref(), Some("audio" | "subtitle")))
+            .map(|s| StreamInfo {
+                index: s.index,
+                kind: s.codec_type.clone().unwrap_or_default(),
+                codec: s.codec_name.clone(),
+                // "und" is ffprobe's "undetermined"
+                language: s.tags.get("language").filter(|l| l.as_str() != "und").cloned(),
+                title: s.tags.get("title").cloned(),
+                channels: s.channels,
+                default: s.disposition.get("default").is_some_and(|d| *d != 0),
+            })
+            .collect();
+        // Embedded captions aren't a stream of their own; they're listed under
+        // the video stream's index so the UI can offer to extract them
+        if let Some(video) = self.video_stream().filter(|_| self.has_closed_captions()) {
+            streams.push(StreamInfo {
+                index: video.index,
+                kind: "closed_captions".to_string(),
+                codec: Some("eia_608".to_string()),
+                language: None,
+                title: None,
+                channels: None,
+                default: false,
+            });
+        }
+        streams
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.format.duration.as_deref()?.parse().ok()
+    }
+
+    pub fn start_time(&self) -> Option<f64> {
+        self.format.start_time.as_deref()?.parse().ok()
+    }
+
+    pub fn has_closed_captions(&self) -> bool {
+        self.video_stream().is_some_and(|video| video.closed_captions != 0)
+    }
+
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        if let Some(bit_rate) = self.format.bit_rate.as_deref().and_then(|b| b.parse::<f64>().ok()) {
+            return Some(bit_rate / 8.0);
+        }
+        let size: f64 = self.format.size.as_deref()?.parse().ok()?;
+        let duration = self.duration().filter(|d| *d > 0.0)?;
+        Some(size / duration)
+    }
+
+    pub fn video_stream(&self) -> Option<&ProbeStream> {
+        self.streams
+            .iter()
+            .find(|s| s.codec_type.as_deref() == Some("video"))
+    }
+
+    // Frame size as displayed, i.e. with rotation metadata applied
+    pub fn video_size(&self) -> Option<(u32, u32)> {
+        let video = self.video_stream()?;
+        let (width, height) = (video.width?, video.height?);
+        if video.rotation() % 180 == 90 {
+            Some((height, width))
+        } else {
+            Some((width, height))
+        }
+    }
+}
+
+pub const NOT_FOUND_ERROR: &str =
+    "ffprobe command not found. Please install FFmpeg and ensure it is in your system's PATH.";
+
+pub async fn probe(source: &Path) -> Result<ProbeResult, String> {
+    let mut ffprobe = Command::new(ffmpeg::ffprobe_path());
+    ffprobe
+        .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams", "-show_chapters"])
+        .arg(source)
+        .kill_on_drop(true);
+    let output = cancel::until_cancelled(ffprobe.output())
+        .await?
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                NOT_FOUND_ERROR.to_string()
+            } else {
+                format!("Failed to execute ffprobe: {}", e)
+            }
+        })?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe could not read the source: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected ffprobe output: {}", e))
+}
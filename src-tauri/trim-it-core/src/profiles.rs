@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::HistoryState;
+use crate::presets::PresetState;
+use crate::settings::SettingsState;
+
+// The default profile keeps using the top-level settings.json,
+// presets.json and history.json, so existing installs carry on unchanged. Other profiles live
+// in profiles/<name>/ under the config and data dirs.
+pub const DEFAULT_PROFILE: &str = "default";
+
+const PROFILES_DIR: &str = "profiles";
+const SETTINGS_FILE: &str = "settings.json";
+const PRESETS_FILE: &str = "presets.json";
+const HISTORY_FILE: &str = "history.json";
+const ACTIVE_PROFILE_FILE: &str = "profile.json";
+const MAX_NAME_LEN: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ActiveProfile {
+    active: Option<String>,
+}
+
+pub struct ProfileState {
+    config_dir: PathBuf,
+    data_dir: PathBuf,
+    active: RwLock<String>,
+}
+
+impl ProfileState {
+    // Falls back to the default profile if the remembered one was deleted
+    pub fn load(config_dir: PathBuf, data_dir: PathBuf) -> Self {
+        let remembered = std::fs::read_to_string(config_dir.join(ACTIVE_PROFILE_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<ActiveProfile>(&contents).ok())
+            .and_then(|file| file.active);
+        let state = Self {
+            config_dir,
+            data_dir,
+            active: RwLock::new(DEFAULT_PROFILE.to_string()),
+        };
+        if let Some(name) = remembered.filter(|name| state.exists(name)) {
+            *state.active.write().unwrap_or_else(|e| e.into_inner()) = name;
+        }
+        state
+    }
+
+    pub fn active(&self) -> String {
+        self.active.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn settings_path(&self, name: &str) -> PathBuf {
+        profile_dir(&self.config_dir, name).join(SETTINGS_FILE)
+    }
+
+    pub fn presets_path(&self, name: &str) -> PathBuf {
+        profile_dir(&self.config_dir, name).join(PRESETS_FILE)
+    }
+
+    pub fn history_path(&self, name: &str) -> PathBuf {
+        profile_dir(&self.data_dir, name).join(HISTORY_FILE)
+    }
+
+    pub fn list(&self) -> ProfileList {
+        let mut profiles: Vec<String> = std::fs::read_dir(self.config_dir.join(PROFILES_DIR))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+                    .filter(|name| validate_name(name).is_ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        profiles.sort();
+        profiles.insert(0, DEFAULT_PROFILE.to_string());
+        ProfileList {
+            active: self.active(),
+            profiles,
+        }
+    }
+
+    pub fn create(&self, name: &str) -> Result<(), String> {
+        validate_name(name)?;
+        if self.exists(name) {
+            return Err(format!("A profile named \"{}\" already exists", name));
+        }
+        std::fs::create_dir_all(profile_dir(&self.config_dir, name))
+            .map_err(|e| format!("Failed to create profile: {}", e))
+    }
+
+    // Points the shared settings, presets and history at the profile's files
+    pub fn switch(
+        &self,
+        name: &str,
+        settings: &SettingsState,
+        presets: &PresetState,
+        history: &HistoryState,
+    ) -> Result<(), String> {
+        if !self.exists(name) {
+            return Err(format!("No profile named \"{}\"", name));
+        }
+        let mut active = self.active.write().unwrap_or_else(|e| e.into_inner());
+        let contents = serde_json::to_string_pretty(&ActiveProfile {
+            active: Some(name.to_string()),
+        })
+        .map_err(|e| format!("Failed to serialize active profile: {}", e))?;
+        std::fs::create_dir_all(&self.config_dir).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        std::fs::write(self.config_dir.join(ACTIVE_PROFILE_FILE), contents)
+            .map_err(|e| format!("Failed to save active profile: {}", e))?;
+
+        settings.reload(self.settings_path(name));
+        presets.reload(self.presets_path(name));
+        history.reload(self.history_path(name));
+        *active = name.to_string();
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        if name == DEFAULT_PROFILE {
+            return Err("The default profile can't be deleted".to_string());
+        }
+        if name == self.active() {
+            return Err("Switch to another profile before deleting this one".to_string());
+        }
+        validate_name(name)?;
+        for dir in [profile_dir(&self.config_dir, name), profile_dir(&self.data_dir, name)] {
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete profile: {}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        name == DEFAULT_PROFILE || (validate_name(name).is_ok() && profile_dir(&self.config_dir, name).is_dir())
+    }
+}
+
+fn profile_dir(base: &Path, name: &str) -> PathBuf {
+    if name == DEFAULT_PROFILE {
+        base.to_path_buf()
+    } else {
+        base.join(PROFILES_DIR).join(name)
+    }
+}
+
+// Names become directory names, so keep them to characters that are safe
+// everywhere rather than sanitizing and risking two names colliding.
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'));
+    if name.trim().is_empty() || name.trim() != name || name.len() > MAX_NAME_LEN || !valid_chars {
+        return Err(format!(
+            "Profile names can use letters, numbers, spaces, '-' and '_' (up to {} characters)",
+            MAX_NAME_LEN
+        ));
+    }
+    Ok(())
+}
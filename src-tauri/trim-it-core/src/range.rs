@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::probe::{self, ProbeResult};
+use crate::timecode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RangeIssueKind {
+    InvalidTime,
+    EndNotAfterStart,
+    StartBeyondDuration,
+    EndBeyondDuration,
+    ShorterThanFrame,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RangeIssue {
+    pub kind: RangeIssueKind,
+    pub message: String,
+}
+
+// Errors would make FFmpeg fail or write an empty file; warnings still
+// export, just not quite what was asked for
+#[derive(Debug, Clone, Serialize)]
+pub struct RangeReport {
+    pub start_seconds: Option<f64>,
+    pub end_seconds: Option<f64>,
+    pub duration: Option<f64>,
+    pub frame_rate: Option<f64>,
+    pub errors: Vec<RangeIssue>,
+    pub warnings: Vec<RangeIssue>,
+}
+
+// Probes a local file or direct link and checks `start_time`..`end_time`
+// against its real length. Frame numbers are converted with its frame rate.
+pub async fn validate_trim_range(source: &str, start_time: &str, end_time: &str) -> Result<RangeReport, String> {
+    if source.contains("youtube.com") || source.contains("youtu.be") {
+        return Err("Range checks need a local file or a direct video link".to_string());
+    }
+    if !source.starts_with("http") && !Path::new(source).exists() {
+        return Err(format!("Local video file not found: {}", source));
+    }
+    let source_probe = probe::probe(Path::new(source)).await?;
+    let fps = source_probe.video_stream().and_then(|v| v.frame_rate());
+
+    let mut errors = Vec::new();
+    let mut parse = |time: &str| match timecode::parse_time(time, fps) {
+        Ok(seconds) => Some(seconds),
+        Err(message) => {
+            errors.push(issue(RangeIssueKind::InvalidTime, message));
+            None
+        }
+    };
+    let start = parse(start_time);
+    let end = parse(end_time);
+    if !errors.is_empty() {
+        return Ok(RangeReport {
+            start_seconds: start,
+            end_seconds: end,
+            duration: source_probe.duration(),
+            frame_rate: fps,
+            errors,
+            warnings: Vec::new(),
+        });
+    }
+    Ok(check(&source_probe, start.unwrap_or_default(), end.unwrap_or_default()))
+}
+
+pub fn check(source_probe: &ProbeResult, start: f64, end: f64) -> RangeReport {
+    let duration = source_probe.duration();
+    let fps = source_probe.video_stream().and_then(|v| v.frame_rate());
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    if end <= start {
+        errors.push(issue(RangeIssueKind::EndNotAfterStart, "End time must be after start time".to_string()));
+    } else if let Some(fps) = fps.filter(|fps| end - start < 1.0 / fps) {
+        errors.push(issue(
+            RangeIssueKind::ShorterThanFrame,
+            format!("The range is shorter than one frame ({:.3}s at {:.3} fps)", 1.0 / fps, fps),
+        ));
+    }
+    if let Some(duration) = duration {
+        if start >= duration {
+            errors.push(issue(
+                RangeIssueKind::StartBeyondDuration,
+                format!("Start time is past the end of the video ({})", timecode::format_timecode(duration)),
+            ));
+        } else if end > duration {
+            warnings.push(issue(
+                RangeIssueKind::EndBeyondDuration,
+                format!(
+                    "End time is past the end of the video; the clip will stop at {}",
+                    timecode::format_timecode(duration)
+                ),
+            ));
+        }
+    }
+
+    RangeReport {
+        start_seconds: Some(start),
+        end_seconds: Some(end),
+        duration,
+        frame_rate: fps,
+        errors,
+        warnings,
+    }
+}
+
+fn issue(kind: RangeIssueKind, message: String) -> RangeIssue {
+    RangeIssue { kind, message }
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::probe::ProbeStream;
+use crate::settings::Settings;
+use crate::{ffmpeg, mirror, probe};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Mp4,
+    Mov,
+    Mkv,
+    Webm,
+}
+
+impl Container {
+    fn extension(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::Mkv => "mkv",
+            Container::Webm => "webm",
+        }
+    }
+
+    fn accepts_video(self, codec: &str) -> bool {
+        match self {
+            Container::Mp4 => matches!(codec, "h264" | "hevc" | "av1" | "vp9" | "mpeg4" | "mpeg2video" | "mjpeg"),
+            Container::Mov => matches!(
+                codec,
+                "h264" | "hevc" | "av1" | "mpeg4" | "mpeg2video" | "mjpeg" | "prores" | "dnxhd"
+            ),
+            Container::Mkv => true,
+            Container::Webm => matches!(codec, "vp8" | "vp9" | "av1"),
+        }
+    }
+
+    fn accepts_audio(self, codec: &str) -> bool {
+        match self {
+            Container::Mp4 => matches!(codec, "aac" | "mp3" | "ac3" | "eac3" | "alac" | "opus" | "flac"),
+            Container::Mov => matches!(codec, "aac" | "mp3" | "ac3" | "eac3" | "alac" | "pcm_s16le" | "pcm_s24le"),
+            Container::Mkv => true,
+            Container::Webm => matches!(codec, "opus" | "vorbis"),
+        }
+    }
+
+    // What a text subtitle has to be converted to here, if anything. Styled
+    // subtitles stay as they are in mkv; bitmap subtitles only fit in mkv.
+    fn subtitle_conversion(self, codec: &str) -> Option<&'static str> {
+        let target = match self {
+            Container::Mp4 | Container::Mov => "mov_text",
+            Container::Mkv if codec == "mov_text" => "srt",
+            Container::Mkv => return None,
+            Container::Webm => "webvtt",
+        };
+        (target != codec).then_some(target)
+    }
+}
+
+// Muxer flags every output in a container gets, from the advanced
+// settings. The defaults suit players and upload sites; some editors and
+// ingest servers want the files laid out differently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MuxOptions {
+    // Moves the mp4/mov index to the front so playback can start before the
+    // file has fully loaded. Tools that patch files in place expect it at
+    // the end.
+    pub faststart: bool,
+    // Moves the mkv/webm seek index (cues) to the front, for players
+    // reading over a network
+    pub cues_to_front: bool,
+    // Writes webm as a live stream, with no seek index or duration, for
+    // servers that relay it as it arrives
+    pub webm_live: bool,
+}
+
+impl Default for MuxOptions {
+    fn default() -> Self {
+        Self {
+            faststart: true,
+            cues_to_front: false,
+            webm_live: false,
+        }
+    }
+}
+
+impl MuxOptions {
+    // The -movflags for mp4 and mov outputs, before any a job adds
+    pub fn movflags(&self) -> String {
+        if self.faststart {
+            "+faststart".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    // Muxer options for an output with this extension
+    pub fn args(&self, extension: &str) -> Vec<String> {
+        let args: &[&str] = match extension {
+            "mp4" | "mov" | "m4v" if self.faststart => &["-movflags", "+faststart"],
+            "webm" if self.webm_live => &["-live", "1"],
+            "mkv" | "webm" if self.cues_to_front => &["-cues_to_front", "1"],
+            _ => &[],
+        };
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemuxOutput {
+    pub path: String,
+    // Streams the new container can't hold, e.g. "subtitle 3 (hdmv_pgs_subtitle)"
+    pub dropped: Vec<String>,
+}
+
+// Moves the streams of `source` into `container` without re-encoding, into
+// the settings' output folder. Fails when the video or audio codec can't
+// go in the new container; subtitles, data tracks and attachments that
+// can't are left out and listed.
+pub async fn remux(source: &Path, container: Container, settings: &Settings) -> Result<RemuxOutput, String> {
+    if !source.is_file() {
+        return Err(format!("Local video file not found: {}", source.display()));
+    }
+    let source_probe = probe::probe(source).await?;
+
+    let mut command = ffmpeg::new_command();
+    command.input(source.to_string_lossy());
+    let mut mapped = 0;
+    let mut has_media = false;
+    let mut dropped = Vec::new();
+    for stream in &source_probe.streams {
+        let codec = stream.codec_name.as_deref().unwrap_or("unknown");
+        let kind = stream.codec_type.as_deref().unwrap_or("unknown");
+        let mut subtitle_codec = None;
+        match kind {
+            // Cover art is stored differently by every container
+            "video" if is_cover_art(stream) => {
+                dropped.push(describe(stream));
+                continue;
+            }
+            "video" | "audio" => {
+                let accepted = if kind == "video" {
+                    container.accepts_video(codec)
+                } else {
+                    container.accepts_audio(codec)
+                };
+                if !accepted {
+                    return Err(format!(
+                        "{} {} can't be put in {} without re-encoding; export it with a different codec instead",
+                        codec,
+                        kind,
+                        container.extension()
+                    ));
+                }
+                has_media = true;
+            }
+            "subtitle" if stream.is_text_subtitle() => {
+                subtitle_codec = container.subtitle_conversion(codec);
+            }
+            "subtitle" | "attachment" if container == Container::Mkv => {}
+            _ => {
+                dropped.push(describe(stream));
+                continue;
+            }
+        }
+        let codec_option = format!("-c:{}", mapped);
+        command
+            .args(["-map", &format!("0:{}", stream.index)])
+            .args([codec_option.as_str(), subtitle_codec.unwrap_or("copy")]);
+        mapped += 1;
+    }
+    if !has_media {
+        return Err("The source has no video or audio to remux".to_string());
+    }
+    match container {
+        Container::Mp4 | Container::Mov => {
+            if source_probe.video_stream().and_then(|v| v.codec_name.as_deref()) == Some("hevc") {
+                // Apple players only open HEVC in mp4 with the hvc1 tag
+                command.args(["-tag:v", "hvc1"]);
+            }
+        }
+        Container::Mkv | Container::Webm => {}
+    }
+    command.args(settings.mux.args(container.extension()));
+
+    let output_dir = settings.resolved_output_dir();
+    std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create output directory: {}", e))?;
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let mut output_path = output_dir.join(format!("{}.{}", stem, container.extension()));
+    if output_path.exists() {
+        output_path = output_dir.join(format!("{}_remux.{}", stem, container.extension()));
+    }
+    command.output(output_path.to_string_lossy()).overwrite();
+    ffmpeg::run(command).await.map_err(|e| format!("Failed to remux the video: {}", e))?;
+
+    if let Some(config) = &settings.mirror {
+        if let Err(e) = mirror::mirror_file(&output_path, config).await {
+            log::warn!("Remuxed video was saved but not mirrored: {}", e);
+        }
+    }
+    Ok(RemuxOutput {
+        path: output_path.display().to_string(),
+        dropped,
+    })
+}
+
+fn is_cover_art(stream: &ProbeStream) -> bool {
+    stream.disposition.get("attached_pic").is_some_and(|d| *d != 0)
+}
+
+fn describe(stream: &ProbeStream) -> String {
+    format!(
+        "{} {} ({})",
+        stream.codec_type.as_deref().unwrap_or("stream"),
+        stream.index,
+        stream.codec_name.as_deref().unwrap_or("unknown")
+    )
+}
@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+
+use ffmpeg_sidecar::child::FfmpegChild;
+use ffmpeg_sidecar::event::FfmpegEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::{ffmpeg, paths, probe, timecode};
+
+// What to record and how much of it to keep. `input_format` and `input` are
+// FFmpeg's, e.g. "dshow" and "video=Cam Link 4K:audio=Cam Link 4K",
+// "avfoundation" and "1:0", or "x11grab" and ":0.0".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    pub input_format: String,
+    pub input: String,
+    // Passed before the input, e.g. ["-framerate", "60", "-video_size", "1920x1080"]
+    #[serde(default)]
+    pub input_options: Vec<String>,
+    // A separate audio device, for inputs that only capture video (x11grab)
+    #[serde(default)]
+    pub audio_format: Option<String>,
+    #[serde(default)]
+    pub audio_input: Option<String>,
+    #[serde(default = "default_buffer_minutes")]
+    pub buffer_minutes: u32,
+    // Where the segments go; a folder under the temp folder otherwise
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+fn default_buffer_minutes() -> u32 {
+    5
+}
+
+// The buffer is a ring of short segments FFmpeg overwrites in turn, so
+// clips start on a segment boundary up to this much before the asked start
+const SEGMENT_SECONDS: u32 = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayStatus {
+    pub running: bool,
+    // How far back a clip can currently reach
+    pub buffered_seconds: f64,
+    // The recorder's last error, e.g. when the device was unplugged
+    pub error: Option<String>,
+}
+
+struct Recorder {
+    child: FfmpegChild,
+    dir: PathBuf,
+    buffer_seconds: f64,
+    started: Instant,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+// The last few minutes of a capture source, recorded continuously so a
+// moment can be clipped after it happened
+#[derive(Default)]
+pub struct ReplayBuffer {
+    recorder: Mutex<Option<Recorder>>,
+}
+
+// The buffered range joined into one file, ready to be trimmed. The file is
+// deleted when this is dropped.
+pub struct ReplayClip {
+    _dir: tempfile::TempDir,
+    pub path: PathBuf,
+    pub start_time: String,
+    pub end_time: String,
+    // For the output, in place of the usual "trimmed_" name
+    pub name: String,
+}
+
+impl ReplayBuffer {
+    pub fn start(&self, config: &ReplayConfig) -> Result<(), String> {
+        if config.buffer_minutes == 0 {
+            return Err("The replay buffer needs to hold at least a minute".to_string());
+        }
+        let mut recorder = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        if recorder.as_mut().is_some_and(|r| !has_exited(r)) {
+            return Err("The replay buffer is already recording".to_string());
+        }
+        let dir = config
+            .dir
+            .as_deref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| paths::temp_dir().join("trim-it").join("replay"));
+        // Segments from an earlier session would be joined onto this one
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to clear the replay folder: {}", e))?;
+        }
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create the replay folder: {}", e))?;
+
+        let buffer_seconds = config.buffer_minutes as f64 * 60.0;
+        // One more than the buffer holds, for the segment being written
+        let segment_count = config.buffer_minutes * 60 / SEGMENT_SECONDS + 1;
+        let mut command = ffmpeg::new_command();
+        command
+            .args(&config.input_options)
+            .args(["-f", &config.input_format])
+            .input(&config.input);
+        if let (Some(format), Some(input)) = (&config.audio_format, &config.audio_input) {
+            command.args(["-f", format]).input(input);
+        }
+        command
+            .args(["-c:v", "libx264", "-preset", "veryfast", "-crf", "20", "-pix_fmt", "yuv420p"])
+            // Every segment starts on a keyframe so any of them can be joined
+            .args(["-force_key_frames", &format!("expr:gte(t,n_forced*{})", SEGMENT_SECONDS)])
+            .args(["-c:a", "aac", "-b:a", "160k"])
+            .args(["-f", "segment", "-segment_format", "mpegts"])
+            .args(["-segment_time", &SEGMENT_SECONDS.to_string()])
+            .args(["-segment_wrap", &segment_count.to_string()])
+            .args(["-reset_timestamps", "1"])
+            .output(dir.join("buffer_%03d.ts").to_string_lossy());
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start recording: {}", e))?;
+
+        // FFmpeg stalls once its stderr pipe fills, so it's read for the
+        // whole session
+        let events = child.iter().map_err(|e| format!("Failed to start recording: {}", e))?;
+        let error = Arc::new(Mutex::new(None));
+        let last_error = error.clone();
+        std::thread::spawn(move || {
+            for event in events {
+                if let FfmpegEvent::Error(e) = event {
+                    log::warn!("Replay buffer: {}", e);
+                    *last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                }
+            }
+        });
+
+        *recorder = Some(Recorder {
+            child,
+            dir,
+            buffer_seconds,
+            started: Instant::now(),
+            error,
+        });
+        Ok(())
+    }
+
+    // Stops recording. The segments stay until the next start, so the last
+    // minutes can still be clipped.
+    pub fn stop(&self) -> Result<(), String> {
+        let mut recorder = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        let recorder = recorder.as_mut().ok_or("The replay buffer isn't recording")?;
+        if has_exited(recorder) {
+            return Ok(());
+        }
+        // "q" lets FFmpeg finish the current segment instead of cutting it off
+        if recorder.child.quit().is_err() {
+            let _ = recorder.child.kill();
+        }
+        recorder
+            .child
+            .wait()
+            .map_err(|e| format!("Failed to stop recording: {}", e))?;
+        Ok(())
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let mut recorder = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        match recorder.as_mut() {
+            Some(recorder) => ReplayStatus {
+                running: !has_exited(recorder),
+                buffered_seconds: recorder.started.elapsed().as_secs_f64().min(recorder.buffer_seconds),
+                error: recorder.error.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            },
+            None => ReplayStatus {
+                running: false,
+                buffered_seconds: 0.0,
+                error: None,
+            },
+        }
+    }
+
+    // Joins the segments covering the last `seconds` into one file, with the
+    // range to trim it to. Nothing is re-encoded.
+    pub async fn recent(&self, seconds: f64) -> Result<ReplayClip, String> {
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return Err("Clip length must be a positive number of seconds".to_string());
+        }
+        let dir = self
+            .recorder
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|recorder| recorder.dir.clone())
+            .ok_or("The replay buffer hasn't been started")?;
+        let segments = newest_segments(&dir, seconds)?;
+
+        let work_dir = paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+        let list_path = work_dir.path().join("segments.txt");
+        let list: String = segments
+            .iter()
+            .map(|segment| format!("file '{}'\n", segment.to_string_lossy().replace('\'', "'\\''")))
+            .collect();
+        std::fs::write(&list_path, list).map_err(|e| format!("Failed to write the segment list: {}", e))?;
+
+        let path = work_dir.path().join("replay.ts");
+        let mut command = ffmpeg::new_command();
+        command
+            .args(["-f", "concat", "-safe", "0"])
+            .input(list_path.to_string_lossy())
+            .args(["-c", "copy"])
+            .output(path.to_string_lossy())
+            .overwrite();
+        ffmpeg::run(command).await.map_err(|e| format!("Failed to join the replay buffer: {}", e))?;
+
+        let duration = probe::probe(&path)
+            .await?
+            .duration()
+            .ok_or("Couldn't read the length of the replay buffer")?;
+        Ok(ReplayClip {
+            _dir: work_dir,
+            path,
+            start_time: timecode::format_timecode((duration - seconds).max(0.0)),
+            end_time: timecode::format_timecode(duration),
+            name: format!("replay_{}", chrono::Utc::now().format("%Y%m%d%H%M%S")),
+        })
+    }
+}
+
+fn has_exited(recorder: &mut Recorder) -> bool {
+    recorder.child.as_inner_mut().try_wait().map_or(true, |status| status.is_some())
+}
+
+// Oldest first, enough to cover `seconds`. The newest segment is still being
+// written but holds the latest moments, so it's included.
+fn newest_segments(dir: &Path, seconds: f64) -> Result<Vec<PathBuf>, String> {
+    let mut segments: Vec<(SystemTime, PathBuf)> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read the replay folder: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "ts"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    if segments.is_empty() {
+        return Err("Nothing has been recorded yet".to_string());
+    }
+    segments.sort();
+    let needed = (seconds / SEGMENT_SECONDS as f64).ceil() as usize + 1;
+    let skip = segments.len().saturating_sub(needed);
+    Ok(segments.into_iter().skip(skip).map(|(_, path)| path).collect())
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::HistoryEntry;
+use crate::privacy;
+
+// Keeps the output folder from growing forever. Rules combine: a file goes
+// when either one says so.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    // Remove outputs last modified more than this many days ago
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    // Keep only this many of the newest exports of each source. Only
+    // exports in the history are counted, since it records their sources.
+    #[serde(default)]
+    pub keep_per_source: Option<u32>,
+    #[serde(default)]
+    pub action: RetentionAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    // Move to the OS trash, so a mistake can be undone
+    #[default]
+    Recycle,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupItem {
+    pub path: String,
+    pub bytes: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanupReport {
+    // True when nothing was actually removed
+    pub dry_run: bool,
+    pub items: Vec<CleanupItem>,
+    pub total_bytes: u64,
+}
+
+// Applies `policy` to the files directly in `dir`. Sidecars (posters,
+// captions, stems) share their clip's name and go with it. With `dry_run`
+// this only reports what would be removed.
+pub fn apply(dir: &Path, policy: &RetentionPolicy, history: &[HistoryEntry], dry_run: bool) -> Result<CleanupReport, String> {
+    let mut files: Vec<(PathBuf, SystemTime, u64)> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read the output folder: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some((entry.path(), metadata.modified().ok()?, metadata.len()))
+        })
+        .collect();
+    files.sort_by_key(|f| std::cmp::Reverse(f.1));
+
+    let mut reasons: HashMap<PathBuf, String> = HashMap::new();
+    if let Some(days) = policy.max_age_days {
+        let cutoff = SystemTime::now() - Duration::from_secs(u64::from(days) * 24 * 60 * 60);
+        for (path, modified, _) in &files {
+            if *modified < cutoff {
+                reasons.insert(path.clone(), format!("Older than {} days", days));
+            }
+        }
+    }
+    if let Some(keep) = policy.keep_per_source {
+        let mut by_source: HashMap<&str, Vec<&HistoryEntry>> = HashMap::new();
+        for entry in history.iter().filter(|entry| Path::new(&entry.output_path).parent() == Some(dir)) {
+            by_source.entry(entry.source.as_str()).or_default().push(entry);
+        }
+        for entries in by_source.values_mut() {
+            // RFC 3339 in UTC sorts chronologically as text
+            entries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            for entry in entries.iter().skip(keep as usize) {
+                let stem = Path::new(&entry.output_path).file_stem().unwrap_or_default().to_string_lossy();
+                for (path, _, _) in &files {
+                    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                    if name == stem || name.starts_with(&format!("{}_", stem)) {
+                        reasons
+                            .entry(path.clone())
+                            .or_insert_with(|| format!("More than {} newer exports of the same source", keep));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut items = Vec::new();
+    for (path, _, bytes) in files {
+        let Some(reason) = reasons.remove(&path) else {
+            continue;
+        };
+        if !dry_run {
+            let removed = match policy.action {
+                RetentionAction::Recycle => trash::delete(&path).map_err(|e| e.to_string()),
+                RetentionAction::Delete => std::fs::remove_file(&path).map_err(|e| e.to_string()),
+            };
+            if let Err(e) = removed {
+                log::warn!("Failed to clean up {}: {}", privacy::scrub(&path.display().to_string()), e);
+                continue;
+            }
+        }
+        items.push(CleanupItem {
+            path: path.display().to_string(),
+            bytes,
+            reason,
+        });
+    }
+    Ok(CleanupReport {
+        dry_run,
+        total_bytes: items.iter().map(|item| item.bytes).sum(),
+        items,
+    })
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+use tokio::process::Command;
+
+// What opens a file or folder with its default app
+#[cfg(windows)]
+const OPENER: &str = "explorer";
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(not(any(windows, target_os = "macos")))]
+const OPENER: &str = "xdg-open";
+
+// Shows the file in Explorer, Finder or the desktop's file manager with it
+// selected. File managers that can't select it open its folder instead.
+pub async fn reveal_in_file_manager(path: &Path) -> Result<(), String> {
+    let path = existing(path)?;
+    reveal(&path).await
+}
+
+// Opens the file in the app the OS uses for its type
+pub async fn open_output(path: &Path) -> Result<(), String> {
+    let path = existing(path)?;
+    if path.is_dir() {
+        return Err("Only files can be opened, use reveal for folders".to_string());
+    }
+    spawn(Command::new(OPENER).arg(&path), "open the file")
+}
+
+// Explorer and Finder want an absolute path to select
+fn existing(path: &Path) -> Result<PathBuf, String> {
+    if !path.exists() {
+        return Err(format!("{} doesn't exist anymore", path.display()));
+    }
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+}
+
+#[cfg(windows)]
+async fn reveal(path: &Path) -> Result<(), String> {
+    // Explorer only reads the path when it's quoted after the comma, which
+    // the usual argument quoting doesn't produce
+    spawn(
+        Command::new(OPENER).raw_arg(format!("/select,\"{}\"", path.display())),
+        "open the file manager",
+    )
+}
+
+#[cfg(target_os = "macos")]
+async fn reveal(path: &Path) -> Result<(), String> {
+    spawn(Command::new(OPENER).arg("-R").arg(path), "open the file manager")
+}
+
+// Most Linux file managers (Nautilus, Dolphin, Nemo, Thunar) select items
+// asked for over D-Bus; the rest only get the folder opened
+#[cfg(not(any(windows, target_os = "macos")))]
+async fn reveal(path: &Path) -> Result<(), String> {
+    if let Ok(uri) = url::Url::from_file_path(path) {
+        let shown = Command::new("dbus-send")
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.freedesktop.FileManager1",
+                "/org/freedesktop/FileManager1",
+                "org.freedesktop.FileManager1.ShowItems",
+            ])
+            .arg(format!("array:string:{}", uri))
+            .arg("string:")
+            .output()
+            .await;
+        match shown {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => log::debug!(
+                "No file manager took the D-Bus request: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(e) => log::debug!("Failed to run dbus-send: {}", e),
+        }
+    }
+    let folder = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    spawn(Command::new(OPENER).arg(folder), "open the file manager")
+}
+
+// The file manager or app keeps running on its own; tokio reaps it once it
+// exits
+fn spawn(command: &mut Command, action: &str) -> Result<(), String> {
+    command
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to {}: {}", action, e))
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use rhai::{Dynamic, Engine, Scope, AST};
+use serde::{Deserialize, Serialize};
+
+use crate::job::TrimOptions;
+use crate::privacy;
+
+// A user script can adjust a job right before it runs. The script defines
+//
+//   fn prepare(job) {
+//       if job.source_width < job.source_height { job.ratio = "9:16"; }
+//       job.output_name = "clip_" + job.start_time;
+//       job
+//   }
+//
+// and gets a ScriptJob as an object map. `ratio`, `options` (same fields as
+// TrimOptions) and `output_name` may be changed; the rest is information
+// only. Scripts have no file, network or process access, and run with
+// operation and size limits so a runaway loop can't hang an export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptJob {
+    pub source: String,
+    pub start_time: String,
+    pub end_time: String,
+    pub ratio: String,
+    pub options: TrimOptions,
+    // File name without extension
+    pub output_name: String,
+    pub source_width: Option<u32>,
+    pub source_height: Option<u32>,
+    pub source_duration: Option<f64>,
+}
+
+const HOOK_FUNCTION: &str = "prepare";
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(1_000_000);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+    engine.disable_symbol("eval");
+    engine.on_print(|text| log::info!("[job script] {}", privacy::scrub(text)));
+    engine.on_debug(|text, _, _| log::debug!("[job script] {}", privacy::scrub(text)));
+    engine
+}
+
+fn compile(engine: &Engine, script_path: &Path) -> Result<AST, String> {
+    let ast = engine
+        .compile_file(script_path.to_path_buf())
+        .map_err(|e| format!("Failed to load job script {}: {}", script_path.display(), e))?;
+    if !ast.iter_functions().any(|f| f.name == HOOK_FUNCTION && f.params.len() == 1) {
+        return Err(format!("Job script must define fn {}(job)", HOOK_FUNCTION));
+    }
+    Ok(ast)
+}
+
+// Compiles the script without running it, so a broken script is reported
+// when it is chosen rather than on the next export.
+pub fn check_script(script_path: &Path) -> Result<(), String> {
+    compile(&engine(), script_path).map(|_| ())
+}
+
+pub fn run_prepare_hook(script_path: &Path, job: ScriptJob) -> Result<ScriptJob, String> {
+    let engine = engine();
+    let ast = compile(&engine, script_path)?;
+    let input = rhai::serde::to_dynamic(&job).map_err(|e| format!("Failed to pass job to script: {}", e))?;
+    let output: Dynamic = engine
+        .call_fn(&mut Scope::new(), &ast, HOOK_FUNCTION, (input,))
+        .map_err(|e| format!("Job script failed: {}", e))?;
+    if output.is_unit() {
+        return Err(format!("Job script's {}(job) must return the job", HOOK_FUNCTION));
+    }
+    let updated: ScriptJob =
+        rhai::serde::from_dynamic(&output).map_err(|e| format!("Job script returned an invalid job: {}", e))?;
+
+    Ok(ScriptJob {
+        ratio: updated.ratio,
+        options: updated.options,
+        output_name: updated.output_name,
+        ..job
+    })
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::ffmpeg;
+use crate::job::Vocals;
+
+// A locally installed stem separator. Both are run in their two-stem mode
+// and write a vocals track and everything-else track under the output dir.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeparatorConfig {
+    pub kind: SeparatorKind,
+    // Executable to run, e.g. "demucs" or a full path to spleeter
+    pub command: String,
+    // Passed before the input, e.g. ["-n", "mdx_extra"] to pick a model
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeparatorKind {
+    Demucs,
+    Spleeter,
+}
+
+impl SeparatorKind {
+    // File names of the (vocals, accompaniment) stems
+    fn stem_names(self) -> (&'static str, &'static str) {
+        match self {
+            SeparatorKind::Demucs => ("vocals.wav", "no_vocals.wav"),
+            SeparatorKind::Spleeter => ("vocals.wav", "accompaniment.wav"),
+        }
+    }
+}
+
+// Separates `duration` seconds of `source` from `start` and returns the
+// stem `vocals` asks for, as a WAV in `work_dir` that starts at `start`.
+pub async fn separate(
+    source: &Path,
+    start: f64,
+    duration: f64,
+    vocals: Vocals,
+    config: &SeparatorConfig,
+    work_dir: &Path,
+) -> Result<PathBuf, String> {
+    // Both separators are trained on 44.1 kHz stereo
+    let audio_path = work_dir.join("mix.wav");
+    let mut extract = ffmpeg::new_command();
+    extract
+        .args(["-ss", &start.to_string()])
+        .input(source.to_string_lossy())
+        .args(["-t", &duration.to_string()])
+        .args(["-vn", "-sn", "-dn", "-ar", "44100", "-ac", "2", "-c:a", "pcm_s16le"])
+        .output(audio_path.to_string_lossy())
+        .overwrite();
+    ffmpeg::run(extract).await.map_err(|e| format!("Failed to extract audio for separation: {}", e))?;
+
+    let output_dir = work_dir.join("stems");
+    let mut separator = Command::new(&config.command);
+    match config.kind {
+        SeparatorKind::Demucs => {
+            separator.arg("--two-stems=vocals").args(&config.args).arg("-o").arg(&output_dir);
+        }
+        SeparatorKind::Spleeter => {
+            separator
+                .args(["separate", "-p", "spleeter:2stems"])
+                .args(&config.args)
+                .arg("-o")
+                .arg(&output_dir);
+        }
+    }
+    let output = separator
+        .arg(&audio_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run stem separator '{}': {}", config.command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Stem separator exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let (vocals_name, accompaniment_name) = config.kind.stem_names();
+    let wanted = match vocals {
+        Vocals::Isolate => vocals_name,
+        _ => accompaniment_name,
+    };
+    // Both nest the stems in folders named after the model and input
+    find_file(&output_dir, wanted).ok_or_else(|| format!("The stem separator ran, but wrote no {}", wanted))
+}
+
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(found) = find_file(&path, name) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|file_name| file_name == name) {
+            return Some(path);
+        }
+    }
+    None
+}
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::captions::WhisperConfig;
+use crate::download::RetryPolicy;
+use crate::job::{Quality, Watermark};
+use crate::markers::MarkerDetectorConfig;
+use crate::metered::MeteredPolicy;
+use crate::mirror::MirrorConfig;
+use crate::politeness::Politeness;
+use crate::remux::MuxOptions;
+use crate::replay::ReplayConfig;
+use crate::power::BatteryPolicy;
+use crate::retention::RetentionPolicy;
+use crate::separation::SeparatorConfig;
+use crate::translation::TranslatorConfig;
+use crate::{ffmpeg, paths, privacy, trim, ytdlp};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+const DEFAULT_DOWNLOAD_CACHE_MB: u64 = 2000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub proxy: Option<ProxyConfig>,
+    // KiB/s, shared by yt-dlp and the direct downloader
+    pub max_download_rate_kbps: Option<u64>,
+    // Retries and backoff for direct downloads that drop
+    pub download_retry: RetryPolicy,
+    // When URL jobs ask before a large download on a metered connection
+    pub metered: MeteredPolicy,
+    // Per-site request spacing and concurrency caps
+    pub politeness: Politeness,
+    // MB of YouTube downloads kept for re-trimming the same range; 2 GB when
+    // unset, and 0 turns the cache off
+    pub download_cache_mb: Option<u64>,
+    // Per-container muxer flags for outputs: faststart, mkv cues, webm live
+    pub mux: MuxOptions,
+    pub marker_detector: Option<MarkerDetectorConfig>,
+    // Rhai script whose prepare(job) hook runs before every export
+    pub job_script: Option<String>,
+    // Replaces the Downloads folder as where clips are saved
+    pub output_dir: Option<String>,
+    // Replaces the system temp folder for downloads and intermediate files
+    pub temp_dir: Option<String>,
+    // For re-encodes whose job doesn't choose a quality
+    pub default_quality: Quality,
+    // Exports started while this many are running wait their turn
+    pub max_concurrent_jobs: Option<u32>,
+    // A second folder every output is copied to
+    pub mirror: Option<MirrorConfig>,
+    // Clears old outputs from the output folder at startup
+    pub retention: Option<RetentionPolicy>,
+    // Applied to jobs that don't set a watermark of their own
+    pub watermark: Option<Watermark>,
+    pub whisper: Option<WhisperConfig>,
+    // Translates captions for jobs that ask for another language
+    pub translator: Option<TranslatorConfig>,
+    // demucs or spleeter, for removing or isolating vocals
+    pub stem_separator: Option<SeparatorConfig>,
+    // Hash URLs, file names and titles in logs and history
+    pub redact_logs: bool,
+    // Folder with a preinstalled ffmpeg and ffprobe, used instead of the
+    // managed download
+    pub ffmpeg_dir: Option<String>,
+    // Managed FFmpeg release to use instead of the default pinned one, e.g.
+    // "7.1"; see ffmpeg::AVAILABLE_BUILDS
+    pub ffmpeg_version: Option<String>,
+    // Preinstalled yt-dlp, used instead of the managed download
+    pub ytdlp_path: Option<String>,
+    // Capture source the instant-replay buffer records
+    pub replay: Option<ReplayConfig>,
+    // Defer or slow down encodes while a laptop is on battery
+    pub battery_policy: Option<BatteryPolicy>,
+}
+
+impl Settings {
+    pub fn resolved_output_dir(&self) -> PathBuf {
+        self.output_dir.as_deref().map(PathBuf::from).unwrap_or_else(paths::output_dir)
+    }
+
+    // For settings replaced wholesale, e.g. from the settings page
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+        }
+        for (label, dir) in [("Output", &self.output_dir), ("Temp", &self.temp_dir)] {
+            if dir.as_deref().is_some_and(|dir| dir.trim().is_empty()) {
+                return Err(format!("{} folder can't be blank", label));
+            }
+        }
+        if let Some(dir) = self.ffmpeg_dir.as_deref() {
+            if !Path::new(dir).is_dir() {
+                return Err(format!("FFmpeg folder not found: {}", dir));
+            }
+        }
+        if let Some(path) = self.ytdlp_path.as_deref() {
+            if !Path::new(path).is_file() {
+                return Err(format!("yt-dlp not found: {}", path));
+            }
+        }
+        if let Some(version) = self.ffmpeg_version.as_deref() {
+            if !ffmpeg::AVAILABLE_BUILDS.contains(&version) {
+                return Err(format!("FFmpeg {} isn't one of the managed builds", version));
+            }
+        }
+        if self.max_concurrent_jobs == Some(0) {
+            return Err("Allow at least one job at a time".to_string());
+        }
+        self.download_retry.validate()?;
+        if !self.metered.confirm_above_mb.is_finite() || self.metered.confirm_above_mb < 0.0 {
+            return Err("The confirmation threshold must be zero or more MB".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn download_cache_bytes(&self) -> u64 {
+        self.download_cache_mb.unwrap_or(DEFAULT_DOWNLOAD_CACHE_MB) * 1_000_000
+    }
+
+    pub fn max_download_bytes_per_sec(&self) -> Option<u64> {
+        self.max_download_rate_kbps
+            .filter(|&kbps| kbps > 0)
+            .map(|kbps| kbps * 1024)
+    }
+}
+
+// `url` carries the scheme, so "http://proxy:8080" and "socks5://proxy:1080"
+// are both accepted. Credentials are kept separate so the UI never has to
+// splice them into a URL itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let parsed = Url::parse(&self.url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        match parsed.scheme() {
+            "http" | "https" | "socks5" | "socks5h" => {}
+            other => return Err(format!("Unsupported proxy scheme: {}", other)),
+        }
+        if parsed.host_str().is_none() {
+            return Err("Proxy URL is missing a host".to_string());
+        }
+        Ok(())
+    }
+
+    // Proxy URL with credentials embedded, the form both reqwest and yt-dlp's
+    // `--proxy` understand.
+    pub fn url_with_auth(&self) -> Result<String, String> {
+        let mut parsed = Url::parse(&self.url).map_err(|e| format!("Invalid proxy URL: {}", e))?;
+        if let Some(username) = self.username.as_deref().filter(|u| !u.is_empty()) {
+            parsed
+                .set_username(username)
+                .map_err(|_| "Proxy URL cannot carry credentials".to_string())?;
+            parsed
+                .set_password(self.password.as_deref())
+                .map_err(|_| "Proxy URL cannot carry credentials".to_string())?;
+        }
+        Ok(parsed.to_string())
+    }
+}
+
+// Machine-wide defaults from the machine config dir sit underneath the
+// user's file: anything the user hasn't set comes from them. Only settings
+// that differ from the defaults are written back, so later changes IT makes
+// to the defaults still reach users who never touched that setting.
+pub struct SettingsState {
+    path: RwLock<PathBuf>,
+    defaults: Map<String, Value>,
+    settings: RwLock<Settings>,
+}
+
+impl SettingsState {
+    pub fn load(path: PathBuf) -> Self {
+        let defaults = paths::machine_config_dir()
+            .map(|dir| read_settings_file(&dir.join(SETTINGS_FILE)))
+            .unwrap_or_default();
+        let settings = merge_settings(&defaults, read_settings_file(&path));
+        apply_process_wide(&settings);
+        Self {
+            settings: RwLock::new(settings),
+            defaults,
+            path: RwLock::new(path),
+        }
+    }
+
+    // Switches to another settings file, e.g. when the profile changes
+    pub fn reload(&self, path: PathBuf) {
+        let mut settings = self.settings.write().unwrap_or_else(|e| e.into_inner());
+        *settings = merge_settings(&self.defaults, read_settings_file(&path));
+        apply_process_wide(&settings);
+        *self.path.write().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+
+    pub fn current(&self) -> Settings {
+        self.settings.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    // Clearing a setting the defaults provide falls back to the default
+    pub fn update(&self, apply: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+        let mut settings = self.settings.write().unwrap_or_else(|e| e.into_inner());
+        let mut updated = settings.clone();
+        apply(&mut updated);
+        let saved = self.save(&updated)?;
+        let updated = merge_settings(&self.defaults, saved);
+        apply_process_wide(&updated);
+        *settings = updated.clone();
+        Ok(updated)
+    }
+
+    fn save(&self, settings: &Settings) -> Result<Map<String, Value>, String> {
+        let path = self.path.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let mut fields = match serde_json::to_value(settings) {
+            Ok(Value::Object(fields)) => fields,
+            Ok(_) => return Err("Failed to serialize settings".to_string()),
+            Err(e) => return Err(format!("Failed to serialize settings: {}", e)),
+        };
+        fields.retain(|key, value| !value.is_null() && self.defaults.get(key) != Some(value));
+        let contents = serde_json::to_string_pretty(&fields).map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        std::fs::write(&*path, contents).map_err(|e| format!("Failed to save settings: {}", e))?;
+        Ok(fields)
+    }
+}
+
+// Settings that act through process-wide state rather than being read per job
+fn apply_process_wide(settings: &Settings) {
+    privacy::set_redaction(settings.redact_logs);
+    ffmpeg::set_configured_dir(settings.ffmpeg_dir.as_deref().map(PathBuf::from));
+    ffmpeg::set_managed_version(settings.ffmpeg_version.clone());
+    paths::set_temp_dir(settings.temp_dir.as_deref().map(PathBuf::from));
+    trim::set_max_concurrent_jobs(settings.max_concurrent_jobs);
+    ytdlp::set_configured_path(settings.ytdlp_path.as_deref().map(PathBuf::from));
+}
+
+// A null in the user's file means "not set", so it doesn't hide a default.
+// A user value that doesn't fit is dropped on its own, so one bad field
+// doesn't also lose the machine defaults and the user's other settings.
+fn merge_settings(defaults: &Map<String, Value>, user: Map<String, Value>) -> Settings {
+    let mut merged = defaults.clone();
+    merged.extend(user.iter().filter(|(_, value)| !value.is_null()).map(|(k, v)| (k.clone(), v.clone())));
+    if let Ok(settings) = serde_json::from_value(Value::Object(merged)) {
+        return settings;
+    }
+
+    let mut merged = defaults.clone();
+    if let Err(e) = serde_json::from_value::<Settings>(Value::Object(merged.clone())) {
+        log::warn!("Ignoring unreadable machine default settings: {}", e);
+        merged = Map::new();
+    }
+    for (key, value) in user.into_iter().filter(|(_, value)| !value.is_null()) {
+        let mut candidate = merged.clone();
+        candidate.insert(key.clone(), value);
+        match serde_json::from_value::<Settings>(Value::Object(candidate.clone())) {
+            Ok(_) => merged = candidate,
+            Err(e) => log::warn!("Ignoring unreadable setting \"{}\": {}", key, e),
+        }
+    }
+    serde_json::from_value(Value::Object(merged)).unwrap_or_default()
+}
+
+fn read_settings_file(path: &Path) -> Map<String, Value> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            log::warn!("Ignoring unreadable settings file {}: {}", privacy::scrub(&path.display().to_string()), e);
+            Map::new()
+        }),
+        Err(_) => Map::new(),
+    }
+}
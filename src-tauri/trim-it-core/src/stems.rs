@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use crate::probe::ProbeResult;
+use crate::{ffmpeg, filename};
+
+// Saves each audio track of the trimmed range as "<clip name>_<track>.wav",
+// so speakers recorded to separate tracks (OBS, Zoom, field recorders) can
+// be mixed in an editor. Tracks are named by title, then language. The
+// audio is exported untouched by the clip's own filters.
+pub async fn write_audio_stems(
+    source: &Path,
+    trim_range: Option<(&str, &str)>,
+    probe: &ProbeResult,
+    dir: &Path,
+    clip_stem: &str,
+) -> Result<Vec<PathBuf>, String> {
+    let tracks: Vec<_> = probe
+        .selectable_streams()
+        .into_iter()
+        .filter(|stream| stream.kind == "audio")
+        .collect();
+    if tracks.is_empty() {
+        return Err("The source has no audio tracks to export as stems".to_string());
+    }
+
+    let mut command = ffmpeg::new_command();
+    if let Some((start, end)) = trim_range {
+        command.args(["-ss", start, "-to", end]);
+    }
+    command.input(source.to_string_lossy());
+
+    let mut stems: Vec<PathBuf> = Vec::with_capacity(tracks.len());
+    for (number, track) in tracks.iter().enumerate() {
+        let label = track
+            .title
+            .as_ref()
+            .or(track.language.as_ref())
+            .map(|label| filename::sanitize_filename(label))
+            .filter(|label| !label.is_empty())
+            .unwrap_or_else(|| format!("track{}", number + 1));
+        let mut stem = dir.join(format!("{}_{}.wav", clip_stem, label));
+        // Tracks often share a language, or a title such as "Mic"
+        if stems.contains(&stem) {
+            stem = dir.join(format!("{}_{}_{}.wav", clip_stem, label, number + 1));
+        }
+        command
+            .args(["-map", &format!("0:{}", track.index), "-c:a", "pcm_s16le"])
+            .output(stem.to_string_lossy());
+        stems.push(stem);
+    }
+    command.overwrite();
+
+    ffmpeg::run(command).await.map_err(|e| format!("Failed to export audio stems: {}", e))?;
+    Ok(stems)
+}
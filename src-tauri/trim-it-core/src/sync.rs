@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::ffmpeg;
+
+const SYNC_SAMPLE_RATE: usize = 8000;
+// Recordings are compared over their first couple of minutes, so they must
+// start within MAX_OFFSET_SECONDS of each other
+const SYNC_WINDOW_SECONDS: usize = 60;
+const MAX_OFFSET_SECONDS: usize = 60;
+// The coarse search compares loudness envelopes at 100 points per second,
+// then the best lag is refined on the samples within one envelope step
+const ENVELOPE_STEP: usize = SYNC_SAMPLE_RATE / 100;
+const MIN_OVERLAP_SECONDS: usize = 10;
+const REFINE_SECONDS: usize = 20;
+// Envelope correlation below this means the two files don't share a sound
+const MIN_CONFIDENCE: f64 = 0.3;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncOffset {
+    // Where `a`'s start falls in `b`: positive when `b` started recording
+    // first. Usable as a multicam source offset or replacement audio offset.
+    pub offset_seconds: f64,
+    // Correlation of the two loudness envelopes at that offset, 0 to 1
+    pub confidence: f64,
+}
+
+// Lines up two recordings of the same event by cross-correlating their audio
+pub async fn find_sync_offset(a: &Path, b: &Path) -> Result<SyncOffset, String> {
+    let window = (SYNC_WINDOW_SECONDS + MAX_OFFSET_SECONDS) as f64;
+    let (a_samples, b_samples) = tokio::try_join!(decode_audio(a, window), decode_audio(b, window))?;
+
+    let a_envelope = envelope(&a_samples);
+    let b_envelope = envelope(&b_samples);
+    let max_lag = (MAX_OFFSET_SECONDS * SYNC_SAMPLE_RATE / ENVELOPE_STEP) as isize;
+    let min_overlap = MIN_OVERLAP_SECONDS * SYNC_SAMPLE_RATE / ENVELOPE_STEP;
+    let (coarse_lag, confidence) = best_lag(&a_envelope, &b_envelope, -max_lag..=max_lag, min_overlap)
+        .ok_or("The recordings are too short to sync; each needs at least 10 seconds of audio.")?;
+    if confidence < MIN_CONFIDENCE {
+        return Err("No matching audio was found in the two recordings.".to_string());
+    }
+
+    let center = coarse_lag * ENVELOPE_STEP as isize;
+    let step = ENVELOPE_STEP as isize;
+    let refine_len = REFINE_SECONDS * SYNC_SAMPLE_RATE;
+    let lag = best_lag(
+        &a_samples[..a_samples.len().min(refine_len + (step - center).max(0) as usize)],
+        &b_samples,
+        center - step..=center + step,
+        MIN_OVERLAP_SECONDS * SYNC_SAMPLE_RATE,
+    )
+    .map_or(center, |(lag, _)| lag);
+
+    Ok(SyncOffset {
+        offset_seconds: lag as f64 / SYNC_SAMPLE_RATE as f64,
+        confidence,
+    })
+}
+
+// Mono samples of the first `seconds` of the file's default audio track
+async fn decode_audio(path: &Path, seconds: f64) -> Result<Vec<f32>, String> {
+    let output = Command::new(ffmpeg::ffmpeg_path())
+        .args(["-hide_banner", "-loglevel", "error", "-t", &seconds.to_string()])
+        .arg("-i")
+        .arg(path)
+        .args(["-vn", "-sn", "-dn", "-ac", "1", "-ar", &SYNC_SAMPLE_RATE.to_string()])
+        .args(["-f", "f32le", "-"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to decode audio for syncing: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let samples: Vec<f32> = output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    if samples.is_empty() {
+        return Err(format!("{} has no audio to sync by", path.display()));
+    }
+    Ok(samples)
+}
+
+// RMS per envelope step. Loudness changes line up across microphones even
+// when their frequency response and placement differ.
+fn envelope(samples: &[f32]) -> Vec<f32> {
+    samples
+        .chunks(ENVELOPE_STEP)
+        .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt())
+        .collect()
+}
+
+// The lag (b index minus a index) with the highest Pearson correlation over
+// the overlapping part, and that correlation
+fn best_lag(
+    a: &[f32],
+    b: &[f32],
+    lags: std::ops::RangeInclusive<isize>,
+    min_overlap: usize,
+) -> Option<(isize, f64)> {
+    lags.filter_map(|lag| {
+        let a_start = (-lag).max(0) as usize;
+        let b_start = lag.max(0) as usize;
+        let len = a.len().saturating_sub(a_start).min(b.len().saturating_sub(b_start));
+        if len < min_overlap.max(2) {
+            return None;
+        }
+        Some((lag, correlation(&a[a_start..a_start + len], &b[b_start..b_start + len])))
+    })
+    .max_by(|(_, x), (_, y)| x.total_cmp(y))
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f64 {
+    let n = a.len() as f64;
+    let mean_a = a.iter().map(|&x| x as f64).sum::<f64>() / n;
+    let mean_b = b.iter().map(|&x| x as f64).sum::<f64>() / n;
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (&x, &y) in a.iter().zip(b) {
+        let (dx, dy) = (x as f64 - mean_a, y as f64 - mean_b);
+        covariance += dx * dy;
+        variance_a += dx * dx;
+        variance_b += dy * dy;
+    }
+    let denominator = (variance_a * variance_b).sqrt();
+    if denominator > 0.0 { covariance / denominator } else { 0.0 }
+}
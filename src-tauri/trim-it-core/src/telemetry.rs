@@ -0,0 +1,193 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sysinfo::{Networks, Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+use crate::download::DownloadProgress;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+// Guards the parent walk against pid reuse creating a cycle
+const MAX_PROCESS_DEPTH: usize = 32;
+
+// What the app and the tools it runs (FFmpeg, yt-dlp, plugins) are using.
+// Processes are this app's whole tree, so jobs running side by side share
+// the figures. Network traffic is machine-wide, since per-process counters
+// aren't available portably.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceSample {
+    pub elapsed_seconds: f64,
+    // Summed over processes, so one fully busy core is 100
+    pub cpu_percent: f32,
+    pub memory_bytes: u64,
+    pub network_rx_bytes_per_sec: u64,
+}
+
+// Receives a sample about once a second while a job runs
+#[derive(Clone)]
+pub struct ResourceListener(Arc<dyn Fn(ResourceSample) + Send + Sync>);
+
+impl ResourceListener {
+    pub fn new(on_sample: impl Fn(ResourceSample) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(on_sample))
+    }
+}
+
+impl fmt::Debug for ResourceListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResourceListener")
+    }
+}
+
+// Receives messages about decisions made while a job runs, such as falling
+// back to another encoder, so the user knows why the result differs from
+// what they asked for
+#[derive(Clone)]
+pub struct NoticeListener(Arc<dyn Fn(String) + Send + Sync>);
+
+impl NoticeListener {
+    pub fn new(on_notice: impl Fn(String) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(on_notice))
+    }
+
+    pub fn notify(&self, message: String) {
+        self.0(message)
+    }
+}
+
+impl fmt::Debug for NoticeListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NoticeListener")
+    }
+}
+
+// Told which file is in the way when a job set to ask about name
+// collisions stops for one
+#[derive(Clone)]
+pub struct CollisionListener(Arc<dyn Fn(PathBuf) + Send + Sync>);
+
+impl CollisionListener {
+    pub fn new(on_collision: impl Fn(PathBuf) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(on_collision))
+    }
+
+    pub fn notify(&self, existing: PathBuf) {
+        self.0(existing)
+    }
+}
+
+impl fmt::Debug for CollisionListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CollisionListener")
+    }
+}
+
+// Told how much of a direct download has been written out, counting what
+// an earlier attempt left in the partial file
+#[derive(Clone)]
+pub struct DownloadListener(Arc<dyn Fn(DownloadProgress) + Send + Sync>);
+
+impl DownloadListener {
+    pub fn new(on_progress: impl Fn(DownloadProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(on_progress))
+    }
+
+    pub fn notify(&self, progress: DownloadProgress) {
+        self.0(progress)
+    }
+}
+
+impl fmt::Debug for DownloadListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DownloadListener")
+    }
+}
+
+// Samples on a thread of its own, since FFmpeg's event loop blocks the job's
+// task. Stops when dropped.
+pub struct ResourceMonitor {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ResourceMonitor {
+    pub fn start(listener: ResourceListener) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let root = match sysinfo::get_current_pid() {
+                Ok(pid) => pid,
+                Err(e) => {
+                    log::warn!("Resource telemetry unavailable: {}", e);
+                    return;
+                }
+            };
+            let started = Instant::now();
+            let mut system = System::new();
+            let mut networks = Networks::new_with_refreshed_list();
+            let mut last_refresh = Instant::now();
+            loop {
+                std::thread::park_timeout(SAMPLE_INTERVAL);
+                if thread_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                system.refresh_processes_specifics(
+                    ProcessesToUpdate::All,
+                    true,
+                    ProcessRefreshKind::nothing().with_cpu().with_memory(),
+                );
+                networks.refresh(true);
+                let interval = last_refresh.elapsed().as_secs_f64().max(0.001);
+                last_refresh = Instant::now();
+
+                let (cpu_percent, memory_bytes) = system
+                    .processes()
+                    .iter()
+                    .filter(|(pid, _)| in_process_tree(&system, **pid, root))
+                    .fold((0.0, 0), |(cpu, memory), (_, process)| {
+                        (cpu + process.cpu_usage(), memory + process.memory())
+                    });
+                let received: u64 = networks.values().map(|data| data.received()).sum();
+
+                listener.0(ResourceSample {
+                    elapsed_seconds: started.elapsed().as_secs_f64(),
+                    cpu_percent,
+                    memory_bytes,
+                    network_rx_bytes_per_sec: (received as f64 / interval).round() as u64,
+                });
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn in_process_tree(system: &System, pid: Pid, root: Pid) -> bool {
+    let mut current = pid;
+    for _ in 0..MAX_PROCESS_DEPTH {
+        if current == root {
+            return true;
+        }
+        match system.process(current).and_then(|process| process.parent()) {
+            Some(parent) => current = parent,
+            None => return false,
+        }
+    }
+    false
+}
@@ -0,0 +1,201 @@
+// Time parsing and formatting shared by the trim pipeline and the frontend
+// commands. Everything is rounded to whole milliseconds so the value shown in
+// the UI is exactly the value handed to FFmpeg.
+
+const FORMATS: &str = "HH:MM:SS.mmm, MM:SS, seconds, 1m30s or a frame number like 1234f";
+
+// Parses HH:MM:SS or MM:SS (each with optional fractional seconds, and
+// minutes and seconds below 60 unless they lead), bare seconds ("90.5") and
+// shorthand ("1h2m3s", "1m30s", "250ms"). Frame numbers need `parse_time`
+// with the source's frame rate.
+pub fn time_to_seconds(time_str: &str) -> Result<f64, String> {
+    parse_time(time_str, None)
+}
+
+// Like `time_to_seconds`, but also accepts a frame number such as "1234f",
+// converted with `fps`
+pub fn parse_time(time_str: &str, fps: Option<f64>) -> Result<f64, String> {
+    let time = time_str.trim();
+    let invalid = || format!("Invalid time '{}'. Use {}", time, FORMATS);
+    if time.is_empty() {
+        return Err(invalid());
+    }
+
+    if is_frame_number(time) {
+        let fps = fps
+            .filter(|fps| fps.is_finite() && *fps > 0.0)
+            .ok_or_else(|| format!("Frame number '{}' needs the video's frame rate", time))?;
+        let frame: u64 = time[..time.len() - 1].parse().map_err(|_| invalid())?;
+        return Ok(frame as f64 / fps);
+    }
+
+    if time.contains(':') {
+        let parts: Vec<&str> = time.split(':').collect();
+        if parts.len() > 3 {
+            return Err(invalid());
+        }
+        let (seconds, whole) = parts.split_last().ok_or_else(invalid)?;
+        let mut total = 0.0;
+        for (index, part) in whole.iter().enumerate() {
+            if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            let value = part.parse::<f64>().map_err(|_| invalid())?;
+            if index > 0 && value >= 60.0 {
+                return Err(invalid());
+            }
+            total = total * 60.0 + value;
+        }
+        let seconds = parse_number(seconds).filter(|s| *s < 60.0).ok_or_else(invalid)?;
+        return Ok(total * 60.0 + seconds);
+    }
+
+    if let Some(seconds) = parse_number(time) {
+        return Ok(seconds);
+    }
+
+    // Shorthand: one or more number-and-unit pairs
+    let mut total = 0.0;
+    let mut rest = time;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).ok_or_else(invalid)?;
+        let value = parse_number(&rest[..number_len]).ok_or_else(invalid)?;
+        rest = &rest[number_len..];
+        let unit_len = rest.find(|c: char| c.is_ascii_digit() || c == '.').unwrap_or(rest.len());
+        total += value
+            * match rest[..unit_len].to_ascii_lowercase().as_str() {
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                "ms" => 0.001,
+                _ => return Err(invalid()),
+            };
+        rest = &rest[unit_len..];
+    }
+    Ok(total)
+}
+
+// Digits with at most one decimal point; no signs, exponents or "inf"
+fn parse_number(value: &str) -> Option<f64> {
+    let digits = value.bytes().filter(u8::is_ascii_digit).count();
+    let points = value.bytes().filter(|&b| b == b'.').count();
+    if digits == 0 || points > 1 || digits + points != value.len() {
+        return None;
+    }
+    value.parse().ok()
+}
+
+pub fn is_frame_number(time: &str) -> bool {
+    time.trim()
+        .strip_suffix('f')
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// Rewrites any accepted input as HH:MM:SS(.mmm), which FFmpeg and yt-dlp
+// both read exactly
+pub fn normalize_time(time: &str, fps: Option<f64>) -> Result<String, String> {
+    Ok(format_timecode(parse_time(time, fps)?))
+}
+
+pub fn to_millis(seconds: f64) -> u64 {
+    if seconds.is_finite() && seconds > 0.0 {
+        (seconds * 1000.0).round() as u64
+    } else {
+        0
+    }
+}
+
+// Formats as HH:MM:SS, adding .mmm only when there is a fractional part so
+// whole-second values round-trip through the existing inputs unchanged.
+pub fn format_timecode(seconds: f64) -> String {
+    let total_millis = to_millis(seconds);
+    let millis = total_millis % 1000;
+    let total_seconds = total_millis / 1000;
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let secs = total_seconds % 60;
+
+    if millis == 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, secs, millis)
+    }
+}
+
+pub fn add_offset(time: &str, offset_seconds: f64) -> Result<String, String> {
+    if !offset_seconds.is_finite() {
+        return Err("Offset must be a finite number of seconds".to_string());
+    }
+    let shifted = time_to_seconds(time)? + offset_seconds;
+    Ok(format_timecode(shifted.max(0.0)))
+}
+
+pub fn clamp_to_duration(time: &str, duration_seconds: f64) -> Result<String, String> {
+    if !duration_seconds.is_finite() || duration_seconds < 0.0 {
+        return Err("Duration must be a non-negative number of seconds".to_string());
+    }
+    let seconds = time_to_seconds(time)?;
+    Ok(format_timecode(seconds.clamp(0.0, duration_seconds)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_accepted_form() {
+        let cases = [
+            ("90", 90.0),
+            ("90.5", 90.5),
+            (" 12.25 ", 12.25),
+            ("01:30", 90.0),
+            ("90:00", 5400.0),
+            ("01:02:03", 3723.0),
+            ("01:02:03.456", 3723.456),
+            ("00:00:59.999", 59.999),
+            ("1h2m3s", 3723.0),
+            ("1m30s", 90.0),
+            ("1.5s", 1.5),
+            ("250ms", 0.25),
+        ];
+        for (input, expected) in cases {
+            let seconds = time_to_seconds(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+            assert!((seconds - expected).abs() < 1e-9, "{} parsed as {}", input, seconds);
+        }
+    }
+
+    #[test]
+    fn converts_frame_numbers_with_the_frame_rate() {
+        assert_eq!(parse_time("48f", Some(24.0)), Ok(2.0));
+        assert_eq!(parse_time("0f", Some(30.0)), Ok(0.0));
+        assert!((parse_time("1001f", Some(30000.0 / 1001.0)).unwrap() - 33.400033).abs() < 1e-6);
+        assert!(parse_time("48f", None).is_err());
+        assert!(parse_time("48f", Some(0.0)).is_err());
+        assert!(parse_time("48f", Some(f64::NAN)).is_err());
+        assert!(time_to_seconds("48f").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_minutes_and_seconds() {
+        for input in ["00:60", "01:75", "00:00:60", "00:00:59.9999999999999999", "01:60:00", "1:99:00"] {
+            assert!(time_to_seconds(input).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn rejects_negative_values() {
+        for input in ["-1", "-00:01", "00:-01", "-1m", "1m-30s", "-5f"] {
+            assert!(parse_time(input, Some(25.0)).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        for input in [
+            "", "   ", "abc", "1:2:3:4", "::", "1:", ":30", "1.2.3", "1e3", "inf", "NaN", "+5", "10x", "1m30", "h", "1.5f",
+            "f",
+        ] {
+            assert!(parse_time(input, Some(25.0)).is_err(), "{:?} was accepted", input);
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::{ffmpeg, paths, ytdlp};
+
+// Encoder name fragments that indicate GPU/ASIC encoding
+const HARDWARE_ENCODER_MARKERS: &[&str] = &["nvenc", "qsv", "amf", "videotoolbox", "vaapi", "v4l2m2m", "mf"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub ffmpeg: Option<FfmpegInfo>,
+    pub ytdlp: Option<ytdlp::YtDlpStatus>,
+    pub temp_dir: DirectorySpace,
+    pub output_dir: DirectorySpace,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FfmpegInfo {
+    pub path: String,
+    pub version: String,
+    pub managed: bool,
+    pub video_encoders: Vec<String>,
+    pub audio_encoders: Vec<String>,
+    pub hardware_encoders: Vec<HardwareEncoder>,
+}
+
+// `usable` is only true when a test encode actually succeeded; being compiled
+// into FFmpeg says nothing about whether the GPU and driver are present.
+#[derive(Debug, Clone, Serialize)]
+pub struct HardwareEncoder {
+    pub name: String,
+    pub usable: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectorySpace {
+    pub path: String,
+    pub free_bytes: Option<u64>,
+}
+
+pub async fn get_tool_status() -> ToolStatus {
+    let ytdlp_path = ytdlp::ytdlp_path();
+    let ytdlp = ytdlp::installed_version(&ytdlp_path)
+        .await
+        .map(|version| ytdlp::YtDlpStatus {
+            path: ytdlp_path.display().to_string(),
+            version,
+            managed: ytdlp::managed_ytdlp_path().is_some_and(|managed| managed == ytdlp_path),
+        });
+
+    ToolStatus {
+        ffmpeg: ffmpeg_info().await,
+        ytdlp,
+        temp_dir: directory_space(&paths::temp_dir()),
+        output_dir: directory_space(&paths::output_dir()),
+    }
+}
+
+pub fn directory_space(path: &Path) -> DirectorySpace {
+    // The directory may not exist yet; report space for the nearest ancestor
+    let existing = path.ancestors().find(|p| p.exists()).unwrap_or(path);
+    DirectorySpace {
+        path: path.display().to_string(),
+        free_bytes: fs2::available_space(existing).ok(),
+    }
+}
+
+async fn ffmpeg_info() -> Option<FfmpegInfo> {
+    let path = ffmpeg::ffmpeg_path();
+    let version = ffmpeg_version(&path).await?;
+
+    let output = Command::new(&path)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .await
+        .ok()?;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    let (video_encoders, audio_encoders) = parse_encoders(&listing);
+
+    let mut hardware_encoders = Vec::new();
+    for name in video_encoders.iter().filter(|name| is_hardware_encoder(name)) {
+        hardware_encoders.push(HardwareEncoder {
+            name: name.clone(),
+            usable: test_encode(&path, name, None).await,
+        });
+    }
+
+    Some(FfmpegInfo {
+        path: path.display().to_string(),
+        version,
+        managed: ffmpeg::managed_ffmpeg_path().is_some_and(|managed| managed == path),
+        video_encoders,
+        audio_encoders,
+        hardware_encoders,
+    })
+}
+
+// "ffmpeg version 7.0.2-static https://johnvansickle.com/ffmpeg/ ..." -> "7.0.2-static"
+pub async fn ffmpeg_version(path: &PathBuf) -> Option<String> {
+    let output = Command::new(path).arg("-version").output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()?
+        .split_whitespace()
+        .nth(2)
+        .map(str::to_string)
+}
+
+// `ffmpeg -encoders` lines look like " V....D libx264  libx264 H.264 ..."
+pub(crate) fn parse_encoders(listing: &str) -> (Vec<String>, Vec<String>) {
+    let mut video = Vec::new();
+    let mut audio = Vec::new();
+    let entries = listing
+        .lines()
+        .skip_while(|line| !line.trim_start().starts_with("------"))
+        .skip(1);
+    for line in entries {
+        let mut fields = line.split_whitespace();
+        let (Some(flags), Some(name)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        match flags.chars().next() {
+            Some('V') => video.push(name.to_string()),
+            Some('A') => audio.push(name.to_string()),
+            _ => {}
+        }
+    }
+    (video, audio)
+}
+
+fn is_hardware_encoder(name: &str) -> bool {
+    name.split('_')
+        .skip(1)
+        .any(|part| HARDWARE_ENCODER_MARKERS.contains(&part))
+}
+
+// Fails unless this FFmpeg build has the encoder and, for GPU encoders, a
+// test encode works on this machine right now. Consumer NVIDIA cards only
+// allow a few NVENC sessions at once, so another app recording or streaming
+// can make an installed GPU encoder unusable.
+// `gpu` picks the GPU a hardware encoder is tried on
+pub async fn check_encoder(encoder: &str, gpu: Option<u32>) -> Result<(), String> {
+    let path = ffmpeg::ffmpeg_path();
+    let output = Command::new(&path)
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+    let (video_encoders, _) = parse_encoders(&String::from_utf8_lossy(&output.stdout));
+    if !video_encoders.iter().any(|name| name == encoder) {
+        return Err(format!("This FFmpeg build doesn't include the {} encoder", encoder));
+    }
+    if is_hardware_encoder(encoder) && !test_encode(&path, encoder, gpu).await {
+        return Err(format!("The {} encoder isn't usable on this machine (no supported GPU or driver)", encoder));
+    }
+    Ok(())
+}
+
+// Encodes a handful of blank frames; fails fast when the GPU, driver or
+// encoder session isn't actually available.
+pub async fn test_encode(ffmpeg_path: &PathBuf, encoder: &str, gpu: Option<u32>) -> bool {
+    let mut command = Command::new(ffmpeg_path);
+    command
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(["-f", "lavfi", "-i", "color=black:s=256x256:d=0.2"])
+        .args(["-frames:v", "5", "-c:v", encoder]);
+    if let Some(gpu) = gpu {
+        command.args(["-gpu", &gpu.to_string()]);
+    }
+    command
+        .args(["-f", "null", "-"])
+        .status()
+        .await
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
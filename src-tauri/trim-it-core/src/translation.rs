@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::download;
+use crate::settings::Settings;
+
+const KEYCHAIN_SERVICE: &str = "trim-it";
+const DEEPL_KEY_ACCOUNT: &str = "deepl-api-key";
+// DeepL takes at most 50 texts per request
+const DEEPL_BATCH: usize = 50;
+
+// What translates generated captions before they're burned in or saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TranslatorConfig {
+    // A local program that reads an SRT on stdin and prints the translated
+    // SRT. "{language}" in `args` is replaced by the target language.
+    Command {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    // DeepL's API, with the key kept in the OS keychain rather than settings
+    Deepl {
+        // Free-tier keys use a separate endpoint
+        #[serde(default)]
+        free_tier: bool,
+    },
+}
+
+pub fn set_deepl_api_key(key: Option<&str>) -> Result<(), String> {
+    let entry = keyring::Entry::new(KEYCHAIN_SERVICE, DEEPL_KEY_ACCOUNT)
+        .map_err(|e| format!("Failed to open the keychain: {}", e))?;
+    match key.map(str::trim).filter(|key| !key.is_empty()) {
+        Some(key) => entry
+            .set_password(key)
+            .map_err(|e| format!("Failed to save the API key to the keychain: {}", e)),
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to remove the API key from the keychain: {}", e)),
+        },
+    }
+}
+
+fn deepl_api_key() -> Result<String, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, DEEPL_KEY_ACCOUNT)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => "No DeepL API key is saved".to_string(),
+            e => format!("Failed to read the DeepL API key from the keychain: {}", e),
+        })
+}
+
+// Translates the text of every cue in `srt` into `language` (e.g. "de"),
+// keeping the numbering and timings as they are
+pub async fn translate_srt(srt: &str, language: &str, config: &TranslatorConfig, settings: &Settings) -> Result<String, String> {
+    match config {
+        TranslatorConfig::Command { command, args } => {
+            let mut child = Command::new(command)
+                .args(args.iter().map(|arg| arg.replace("{language}", language)))
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("Failed to run translator '{}': {}", command, e))?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin
+                    .write_all(srt.as_bytes())
+                    .await
+                    .map_err(|e| format!("Failed to send captions to the translator: {}", e))?;
+            }
+            let output = child
+                .wait_with_output()
+                .await
+                .map_err(|e| format!("Failed to run translator '{}': {}", command, e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "Translator exited with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            let translated = String::from_utf8_lossy(&output.stdout).into_owned();
+            if !translated.contains("-->") {
+                return Err("The translator didn't print any captions".to_string());
+            }
+            Ok(translated)
+        }
+        TranslatorConfig::Deepl { free_tier } => {
+            let endpoint = if *free_tier {
+                "https://api-free.deepl.com/v2/translate"
+            } else {
+                "https://api.deepl.com/v2/translate"
+            };
+            let key = deepl_api_key()?;
+            let client = download::build_client(settings.proxy.as_ref())?;
+
+            // (cue header lines, cue text) per block
+            let cues: Vec<(String, String)> = srt
+                .replace("\r\n", "\n")
+                .split("\n\n")
+                .filter_map(|block| {
+                    let lines: Vec<&str> = block.lines().collect();
+                    let timing = lines.iter().position(|line| line.contains("-->"))?;
+                    Some((lines[..=timing].join("\n"), lines[timing + 1..].join("\n")))
+                })
+                .collect();
+
+            let mut translated = Vec::with_capacity(cues.len());
+            for batch in cues.chunks(DEEPL_BATCH) {
+                let body = serde_json::json!({
+                    "text": batch.iter().map(|(_, text)| text).collect::<Vec<_>>(),
+                    "target_lang": language.to_uppercase(),
+                    "preserve_formatting": true,
+                });
+                let response = client
+                    .post(endpoint)
+                    .header("Authorization", format!("DeepL-Auth-Key {}", key))
+                    .json(&body)
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to reach DeepL: {}", e))?;
+                if !response.status().is_success() {
+                    return Err(format!("DeepL refused the translation: HTTP {}", response.status()));
+                }
+                let result: serde_json::Value =
+                    response.json().await.map_err(|e| format!("Unexpected DeepL response: {}", e))?;
+                let texts: Vec<String> = result
+                    .get("translations")
+                    .and_then(|t| t.as_array())
+                    .map(|translations| {
+                        translations
+                            .iter()
+                            .filter_map(|t| t.get("text").and_then(|text| text.as_str()).map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if texts.len() != batch.len() {
+                    return Err("DeepL returned a different number of captions than it was sent".to_string());
+                }
+                translated.extend(batch.iter().zip(texts).map(|((header, _), text)| format!("{}\n{}", header, text)));
+            }
+            let mut out = translated.join("\n\n");
+            out.push('\n');
+            Ok(out)
+        }
+    }
+}
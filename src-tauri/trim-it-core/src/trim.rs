@@ -0,0 +1,1901 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use ffmpeg_sidecar::command::FfmpegCommand;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use url::Url;
+
+use crate::cancel::{self, CancellationToken};
+use crate::encryption::{self, Passphrase};
+use crate::error::{ErrorCode, TrimError};
+use crate::ffmpeg::FfmpegBuild;
+use crate::history::{self, HistoryState};
+use crate::job::{self, CaptionMode, ClosedCaptions, JobSpec, Keyframes, OutputCollision, Quality, Split, SubtitleMode, TrimOptions, VideoCodec, Vocals};
+use crate::plugins::{FilterRequest, PluginRegistry};
+use crate::probe::HdrFormat;
+use crate::settings::Settings;
+use crate::telemetry::{CollisionListener, DownloadListener, NoticeListener, ResourceListener, ResourceMonitor};
+use crate::estimate::{self, OutputEstimate};
+use crate::joblog::{self, JobLog};
+use crate::{blocking, cache, capabilities, captions, closed_captions, download, ffmpeg, filename, filters, fingerprint, gpu, loudness, metadata, metered, mirror, paths, politeness, poster, privacy, probe, range, scripting, separation, stems, timecode, tools, translation, volume, ytdlp};
+
+// How many jobs may run at once, from settings; no limit when unset
+static JOB_SLOTS: RwLock<Option<(u32, Arc<Semaphore>)>> = RwLock::new(None);
+
+pub fn set_max_concurrent_jobs(limit: Option<u32>) {
+    let limit = limit.filter(|&limit| limit > 0);
+    let mut slots = JOB_SLOTS.write().unwrap_or_else(|e| e.into_inner());
+    // Jobs already running keep their slots in the old semaphore
+    if slots.as_ref().map(|(current, _)| *current) != limit {
+        *slots = limit.map(|limit| (limit, Arc::new(Semaphore::new(limit as usize))));
+    }
+}
+
+/// A single trim: a source (local path, direct video URL or YouTube URL), a
+/// range and how the result should look.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), String> {
+/// use trim_it_core::trim::TrimJob;
+///
+/// let output = TrimJob::builder("input.mp4", "00:01:00", "00:01:30")
+///     .ratio("9:16")
+///     .build()?
+///     .run(None)
+///     .await?;
+/// println!("Saved to {}", output.outputs[0].display());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TrimJob {
+    source: String,
+    start_time: String,
+    end_time: String,
+    ratio: String,
+    options: TrimOptions,
+    settings: Settings,
+    output_dir: PathBuf,
+    plugins: Arc<PluginRegistry>,
+    passphrase: Option<Passphrase>,
+    resource_listener: Option<ResourceListener>,
+    notice_listener: Option<NoticeListener>,
+    collision_listener: Option<CollisionListener>,
+    download_listener: Option<DownloadListener>,
+    cancellation: CancellationToken,
+    output_name: Option<String>,
+}
+
+/// Builds a [`TrimJob`]. Only the source and range are required; everything
+/// else defaults to a plain trim saved to the user's Downloads folder.
+#[derive(Debug, Clone)]
+pub struct TrimJobBuilder {
+    source: String,
+    start_time: String,
+    end_time: String,
+    ratio: String,
+    options: TrimOptions,
+    settings: Settings,
+    output_dir: Option<PathBuf>,
+    plugins: Arc<PluginRegistry>,
+    passphrase: Option<Passphrase>,
+    resource_listener: Option<ResourceListener>,
+    notice_listener: Option<NoticeListener>,
+    collision_listener: Option<CollisionListener>,
+    download_listener: Option<DownloadListener>,
+    cancellation: CancellationToken,
+    output_name: Option<String>,
+}
+
+/// Files written by a finished job.
+#[derive(Debug, Clone)]
+pub struct TrimOutput {
+    /// The clip, or its parts in order when it was split for the output drive
+    pub outputs: Vec<PathBuf>,
+    pub output_dir: PathBuf,
+    /// Length of the clip as exported, in seconds
+    pub duration: f64,
+    /// Combined size of the outputs
+    pub size_bytes: u64,
+    /// Video codec of the outputs as ffprobe names it, e.g. `h264`
+    pub codec: Option<String>,
+    /// How long the job took, downloads included
+    pub elapsed: Duration,
+}
+
+/// A [`TrimOutput`] as reported to the app.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrimResult {
+    /// The clip, or its first part
+    pub output_path: String,
+    pub outputs: Vec<String>,
+    pub output_dir: String,
+    pub duration: f64,
+    pub size_bytes: u64,
+    pub codec: Option<String>,
+    /// Seconds
+    pub elapsed: f64,
+}
+
+impl From<TrimOutput> for TrimResult {
+    fn from(output: TrimOutput) -> Self {
+        let outputs: Vec<String> = output.outputs.iter().map(|path| path.display().to_string()).collect();
+        TrimResult {
+            output_path: outputs[0].clone(),
+            outputs,
+            output_dir: output.output_dir.display().to_string(),
+            duration: output.duration,
+            size_bytes: output.size_bytes,
+            codec: output.codec,
+            elapsed: output.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+impl TrimJob {
+    /// `start_time` and `end_time` are HH:MM:SS or MM:SS with optional
+    /// fractional seconds, bare seconds, shorthand like `1m30s`, or frame
+    /// numbers like `1234f`, which are converted with the source's frame rate.
+    pub fn builder(
+        source: impl Into<String>,
+        start_time: impl Into<String>,
+        end_time: impl Into<String>,
+    ) -> TrimJobBuilder {
+        TrimJobBuilder {
+            source: source.into(),
+            start_time: start_time.into(),
+            end_time: end_time.into(),
+            ratio: "Original".to_string(),
+            options: TrimOptions::default(),
+            settings: Settings::default(),
+            output_dir: None,
+            plugins: Arc::default(),
+            passphrase: None,
+            resource_listener: None,
+            notice_listener: None,
+            collision_listener: None,
+            download_listener: None,
+            cancellation: CancellationToken::default(),
+            output_name: None,
+        }
+    }
+
+    /// Starts a builder from a saved or imported [`JobSpec`].
+    pub fn from_spec(spec: JobSpec) -> TrimJobBuilder {
+        TrimJob::builder(spec.source, spec.start_time, spec.end_time)
+            .ratio(spec.ratio)
+            .options(spec.options)
+    }
+
+    /// Predicts the output's size and encode time without exporting. Needs a
+    /// local file or a direct video link, which ffprobe can read in place.
+    pub async fn estimate(&self) -> Result<OutputEstimate, String> {
+        let is_youtube_video = self.source.contains("youtube.com") || self.source.contains("youtu.be");
+        if is_youtube_video || self.plugins.resolver_for(&self.source).is_some() {
+            return Err("Output estimates need a local file or a direct video link".to_string());
+        }
+        if !self.source.starts_with("http") && !Path::new(&self.source).exists() {
+            return Err(format!("Local video file not found: {}", self.source));
+        }
+        let source_probe = probe::probe(Path::new(&self.source)).await?;
+        let fps = source_probe.video_stream().and_then(|v| v.frame_rate());
+        let start_seconds = timecode::parse_time(&self.start_time, fps)?;
+        let clip_duration = timecode::parse_time(&self.end_time, fps)? - start_seconds;
+        estimate::estimate_output(&source_probe, start_seconds, clip_duration, &self.ratio, &self.options)
+    }
+
+    /// Downloads the source if needed, runs FFmpeg and returns what was
+    /// written. With `history`, exports of an already exported clip are
+    /// refused (unless `allow_duplicates` is set) and the job is recorded.
+    /// FFmpeg's and yt-dlp's output goes to a job log when
+    /// [`joblog::set_log_dir`] was called.
+    pub async fn run(self, history: Option<&HistoryState>) -> Result<TrimOutput, TrimError> {
+        let slots = JOB_SLOTS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|(_, slots)| slots.clone());
+        let cancellation = self.cancellation.clone();
+        // Queued here until a running job finishes
+        let _slot = match slots {
+            Some(slots) => tokio::select! {
+                slot = slots.acquire_owned() => slot.ok(),
+                _ = cancellation.cancelled() => return Err(ErrorCode::Cancelled.error(cancel::CANCELLED)),
+            },
+            None => None,
+        };
+        let job_log = JobLog::create(&format!(
+            "Job: {} from {} to {}, ratio {}",
+            self.source, self.start_time, self.end_time, self.ratio
+        ));
+        let build = self.options.ffmpeg_build;
+        let job = joblog::scope(job_log, async move {
+            let result = self.run_logged(history).await;
+            match &result {
+                Ok(output) => joblog::record(&format!(
+                    "Finished in {:.1}s: {}",
+                    output.elapsed.as_secs_f64(),
+                    output.outputs[0].display()
+                )),
+                Err(e) => joblog::record(&format!("Failed ({:?}): {}", e.code, e.message)),
+            }
+            result
+        });
+        let result = cancel::scope(cancellation.clone(), ffmpeg::scope(build, job)).await;
+        // Whichever stage noticed reports it in its own words
+        result.map_err(|e| {
+            if cancellation.is_cancelled() {
+                ErrorCode::Cancelled.error(cancel::CANCELLED)
+            } else {
+                e
+            }
+        })
+    }
+
+    async fn run_logged(self, history: Option<&HistoryState>) -> Result<TrimOutput, TrimError> {
+        let TrimJob {
+            source: video_source,
+            mut start_time,
+            mut end_time,
+            mut ratio,
+            mut options,
+            settings,
+            output_dir,
+            plugins,
+            passphrase,
+            resource_listener,
+            notice_listener,
+            collision_listener,
+            download_listener,
+            cancellation: _,
+            output_name,
+        } = self;
+        let notify = |message: String| {
+            log::warn!("{}", message);
+            joblog::record(&message);
+            if let Some(listener) = &notice_listener {
+                listener.notify(message);
+            }
+        };
+        let started = Instant::now();
+        // Sampled for the whole job, downloads included
+        let _resource_monitor = resource_listener.map(ResourceMonitor::start);
+        let video_source_label = video_source.clone();
+        let video_path: PathBuf;
+        let temp_dir_guard: Option<tempfile::TempDir>;
+
+        if options.ffmpeg_build == FfmpegBuild::Managed && ffmpeg::managed_ffmpeg_path().is_none() {
+            return Err(ErrorCode::FfmpegMissing.error(format!(
+                "The managed FFmpeg {} isn't installed",
+                ffmpeg::managed_version()
+            )));
+        }
+        capabilities::check_options(&options)
+            .await
+            .map_err(|e| ErrorCode::UnsupportedFeature.error(e))?;
+        if let Some(index) = options.gpu {
+            gpu::check_gpu(index).await.map_err(|e| ErrorCode::InvalidOptions.error(e))?;
+        }
+
+        // Plugins get first pick of a source, ahead of the built-in resolvers
+        let source_plugin = plugins.resolver_for(&video_source);
+
+        // Check if it's a YouTube video before consuming the string
+        let is_youtube_video = source_plugin.is_none()
+            && (video_source.contains("youtube.com") || video_source.contains("youtu.be"));
+        let is_remote = source_plugin.is_some() || video_source.starts_with("http");
+
+        // The same range downloaded for an earlier job is reused, e.g. when
+        // trying another ratio, without going back to YouTube
+        let cache_key = match (timecode::time_to_seconds(&start_time), timecode::time_to_seconds(&end_time)) {
+            (Ok(start_seconds), Ok(end_seconds)) if is_youtube_video => {
+                cache::segment_key(&video_source, start_seconds, end_seconds)
+            }
+            _ => None,
+        };
+        let is_cached = cache_key.as_deref().is_some_and(cache::contains);
+
+        // Streaming manifests are where protected content comes from
+        let is_manifest = Url::parse(&video_source)
+            .is_ok_and(|url| url.path().ends_with(".m3u8") || url.path().ends_with(".mpd"));
+        if source_plugin.is_none() && !is_cached && (is_youtube_video || is_manifest) {
+            ytdlp::ensure_not_drm_protected(&video_source, &settings)
+                .await
+                .map_err(|e| ErrorCode::DrmProtected.error(e))?;
+        }
+
+        if timecode::is_frame_number(&start_time) || timecode::is_frame_number(&end_time) {
+            if is_youtube_video || source_plugin.is_some() {
+                return Err(ErrorCode::InvalidRange.error("Frame numbers need a local file or a direct video link"));
+            }
+            (start_time, end_time) = resolve_frame_numbers(&video_source, &start_time, &end_time).await?;
+        }
+
+        // Sized up front, so a download that needs confirming or can't fit
+        // fails before it starts
+        if is_remote && source_plugin.is_none() && !is_cached {
+            let download_bytes = download::estimate_size(&video_source, &start_time, &end_time, &settings)
+                .await
+                .unwrap_or_else(|e| {
+                    log::warn!("Failed to estimate the download size: {}", privacy::scrub(&e));
+                    None
+                });
+            if !options.allow_metered_download {
+                metered::check_download(download_bytes, &settings)
+                    .await
+                    .map_err(|e| ErrorCode::ConfirmationRequired.error(e))?;
+            }
+            if let Some(bytes) = download_bytes {
+                // yt-dlp keeps the video and audio until it has merged them
+                let needed = if is_youtube_video { bytes.saturating_mul(2) } else { bytes };
+                volume::ensure_free_space(&paths::temp_dir(), needed)
+                    .map_err(|e| ErrorCode::NotEnoughSpace.error(e))?;
+            }
+        }
+
+        if is_remote {
+            let temp_dir = paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+            // Held until the download is done
+            let _host_permit = match source_plugin {
+                Some(_) => None,
+                None if is_cached => None,
+                None => politeness::acquire(&video_source, &settings.politeness).await?,
+            };
+
+            // Check for YouTube URLs and download only the segment
+            let cached_segment = cache_key
+                .as_deref()
+                .filter(|_| is_cached)
+                .and_then(|key| cache::restore(key, temp_dir.path()));
+            if let Some(cached) = cached_segment {
+                log::info!("Reusing the cached download of this range");
+                video_path = cached;
+            } else if let Some(plugin) = source_plugin {
+                video_path = plugin
+                    .resolve_source(&video_source, temp_dir.path())
+                    .await
+                    .map_err(TrimError::download)?;
+            } else if is_youtube_video {
+                video_path = ytdlp::download_youtube_video_segment(
+                    &video_source, 
+                    temp_dir.path(), 
+                    &start_time, 
+                    &end_time,
+                    &settings,
+                ).await.map_err(TrimError::download)?;
+                if let Some(key) = &cache_key {
+                    cache::store(key, &video_path, settings.download_cache_bytes());
+                }
+            } else {
+                // For other direct video links, download the full video
+                let parsed_url = Url::parse(&video_source).map_err(|e| format!("Invalid URL: {}", e))?;
+                let filename = parsed_url
+                    .path_segments()
+                    .and_then(|mut segments| segments.next_back())
+                    .filter(|segment| !segment.is_empty())
+                    .map(filename::sanitize_filename)
+                    .unwrap_or_else(|| "downloaded_video.mp4".to_string());
+
+                let temp_path = temp_dir.path().join(filename);
+
+                let client = download::build_client(settings.proxy.as_ref())?;
+                let options = download::DownloadOptions {
+                    retry: settings.download_retry.clone(),
+                    max_bytes_per_sec: settings.max_download_bytes_per_sec(),
+                    listener: download_listener.clone(),
+                };
+                download::download_video_from_url(&client, &video_source, &temp_path, &options)
+                    .await
+                    .map_err(|e| ErrorCode::DownloadFailed.error(format!("Failed to download video: {}", e)))?;
+
+                video_path = temp_path;
+            }
+
+            temp_dir_guard = Some(temp_dir);
+        } else {
+            video_path = PathBuf::from(video_source);
+            if !video_path.exists() {
+                return Err(ErrorCode::FileNotFound.error(format!("Local video file not found: {}", video_path.display())));
+            }
+            temp_dir_guard = None;
+        }
+
+        cancel::check()?;
+
+        // YouTube downloads already start at the trim point
+        let start_seconds = timecode::time_to_seconds(&start_time)?;
+        let clip_duration = timecode::time_to_seconds(&end_time)? - start_seconds;
+        let file_start = if is_youtube_video { 0.0 } else { start_seconds };
+
+        let mut output_stem = output_name.unwrap_or_else(|| {
+            format!(
+                "trimmed_{}",
+                chrono::Utc::now().format("%Y%m%d%H%M%S")
+            )
+        });
+        let mut source_probe = None;
+        if let Some(script_path) = settings.job_script.as_deref() {
+            let script_probe = probe::probe(&video_path).await?;
+            let (source_width, source_height) = script_probe.video_size().unzip();
+            let prepared = scripting::run_prepare_hook(
+                Path::new(script_path),
+                scripting::ScriptJob {
+                    source: video_source_label.clone(),
+                    start_time: start_time.clone(),
+                    end_time: end_time.clone(),
+                    ratio,
+                    options,
+                    output_name: output_stem,
+                    source_width,
+                    source_height,
+                    source_duration: script_probe.duration(),
+                },
+            )?;
+            filters::validate_ratio(&prepared.ratio)?;
+            ratio = prepared.ratio;
+            options = prepared.options;
+            output_stem = filename::sanitize_filename(&prepared.output_name);
+            source_probe = Some(script_probe);
+        }
+
+        let clip_fingerprint = match fingerprint::compute(&video_path, file_start, clip_duration).await {
+            Ok(clip_fingerprint) => Some(clip_fingerprint),
+            Err(e) => {
+                log::warn!("Skipping duplicate detection: {}", privacy::scrub(&e));
+                None
+            }
+        };
+        if !options.allow_duplicates {
+            let existing = history.zip(clip_fingerprint.as_ref()).and_then(|(h, fp)| h.find_duplicate(fp));
+            if let Some(existing) = existing {
+                return Err(ErrorCode::DuplicateClip.error(format!(
+                    "An identical clip was already exported on {} to {}. Enable \"allow duplicates\" to export it again.",
+                    existing.created_at, existing.output_path
+                )));
+            }
+        }
+
+        if !output_dir.exists() {
+            std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create output directory: {}", e))?;
+        }
+
+        // Encrypted exports are staged in a private temp dir, so the
+        // unencrypted clip never sits in the output folder
+        let staging_dir = match passphrase {
+            Some(_) => Some(paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?),
+            None => None,
+        };
+        let write_dir = staging_dir
+            .as_ref()
+            .map_or_else(|| output_dir.clone(), |dir| dir.path().to_path_buf());
+        let container = options.video_codec.unwrap_or_default().container();
+        // Held until the job ends, so a job finishing meanwhile can't take
+        // the same name
+        let (output_stem, _reservation) = reserve_output(
+            &output_dir,
+            output_stem,
+            container,
+            passphrase.is_some(),
+            options.on_collision,
+            collision_listener.as_ref(),
+        )?;
+        let output_path = write_dir.join(format!("{}.{}", output_stem, container));
+
+        let file_size_limit = volume::volume_info(&output_dir).max_file_size;
+        let needs_probe = options.resolution == job::Resolution::Source
+            || file_size_limit.is_some()
+            || options.preserve_creation_time
+            || options.location == job::LocationPolicy::Keep
+            || options.watermark.is_some()
+            || options.audio_stems
+            || options.closed_captions == ClosedCaptions::Extract
+            || !options.audio_languages.is_empty()
+            || !options.subtitle_languages.is_empty()
+            || matches!(options.split, Some(Split::MaxSize { .. }));
+        if source_probe.is_none() && needs_probe {
+            source_probe = Some(probe::probe(&video_path).await?);
+        }
+        let source_size = source_probe.as_ref().and_then(|p| p.video_size());
+        // A speed change acts on the timestamps, so a cut on the output would
+        // land on the retimed timeline, and reverse or a loop would take in
+        // everything up to the end of the source. Retimed clips are cut on
+        // the input instead, and their filters see a timeline starting at 0.
+        let seek_input =
+            !is_youtube_video && (options.speed() != 1.0 || options.reverse || options.looping.is_some());
+        let range_start = if seek_input { 0.0 } else { file_start };
+        let output_duration = clip_duration / options.speed() * options.passes() as f64;
+        let seek = if is_youtube_video {
+            Seek::None
+        } else if seek_input {
+            Seek::Input {
+                start: &start_time,
+                end: &end_time,
+                length: output_duration,
+            }
+        } else {
+            Seek::Output {
+                start: &start_time,
+                end: &end_time,
+            }
+        };
+        let range = filters::ClipRange {
+            start: range_start,
+            end: range_start + clip_duration,
+            source_offset: start_seconds - range_start,
+        };
+
+        // Where the trim start falls in the replacement audio
+        let replacement_audio_start = match options.replacement_audio_offset {
+            Some(offset) => {
+                let audio_start = start_seconds + offset;
+                if !audio_start.is_finite() || audio_start < 0.0 {
+                    return Err(ErrorCode::InvalidOptions.error("The replacement audio starts after the trimmed range begins"));
+                }
+                audio_start
+            }
+            None => 0.0,
+        };
+
+        let subtitle_mode = options.subtitles.mode;
+        let wants_subtitles = matches!(subtitle_mode, SubtitleMode::Keep | SubtitleMode::Burn);
+        let subtitle_file: Option<PathBuf> = match (&options.subtitles.file, &options.subtitles.language) {
+            (Some(file), _) if wants_subtitles => {
+                if !Path::new(file).is_file() {
+                    return Err(ErrorCode::FileNotFound.error(format!("Subtitle file not found: {}", file)));
+                }
+                Some(PathBuf::from(file))
+            }
+            (None, Some(language)) if wants_subtitles && is_youtube_video => {
+                let download_dir = temp_dir_guard
+                    .as_ref()
+                    .map(|dir| dir.path())
+                    .ok_or("No download directory for subtitles")?;
+                Some(ytdlp::download_subtitles(&video_source_label, download_dir, language, &settings).await?)
+            }
+            _ => None,
+        };
+        // Captions are transcribed from whatever audio ends up in the clip,
+        // before any speed change; cue times start at the trimmed range.
+        let mut captions_dir = None;
+        let translate_to = options.translate_captions_to.as_deref().map(str::trim).filter(|l| !l.is_empty());
+        let captions_file = if options.captions == CaptionMode::Off {
+            if translate_to.is_some() {
+                return Err(ErrorCode::InvalidOptions.error("Turn on captions to translate them"));
+            }
+            None
+        } else {
+            if options.reverse {
+                return Err(ErrorCode::InvalidOptions.error("Captions can't be generated for reversed clips"));
+            }
+            if options.captions == CaptionMode::Burn && subtitle_mode == SubtitleMode::Burn {
+                return Err(ErrorCode::InvalidOptions.error("Choose either burned-in subtitles or burned-in captions, not both"));
+            }
+            let config = settings.whisper.as_ref().ok_or("No whisper transcriber is configured.")?;
+            let work_dir = captions_dir
+                .insert(paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?)
+                .path();
+            let srt = match options.replacement_audio.as_deref() {
+                Some(audio_path) => {
+                    captions::transcribe(Path::new(audio_path), replacement_audio_start, clip_duration, config, work_dir)
+                        .await?
+                }
+                None => captions::transcribe(&video_path, file_start, clip_duration, config, work_dir).await?,
+            };
+            match translate_to {
+                Some(language) => {
+                    let translator = settings.translator.as_ref().ok_or("No caption translator is configured.")?;
+                    let transcript =
+                        std::fs::read_to_string(&srt).map_err(|e| format!("Failed to read generated captions: {}", e))?;
+                    let translated = translation::translate_srt(&transcript, language, translator, &settings).await?;
+                    let translated_path = work_dir.join("captions.translated.srt");
+                    std::fs::write(&translated_path, translated)
+                        .map_err(|e| format!("Failed to save translated captions: {}", e))?;
+                    Some(translated_path)
+                }
+                None => Some(srt),
+            }
+        };
+
+        let burn_in = if options.captions == CaptionMode::Burn {
+            captions_file.as_deref().map(|file| filters::BurnIn {
+                path: file,
+                stream_index: None,
+                time_offset: -range_start,
+            })
+        } else {
+            (subtitle_mode == SubtitleMode::Burn).then(|| match &subtitle_file {
+                Some(file) => filters::BurnIn {
+                    path: file,
+                    stream_index: None,
+                    time_offset: range.source_offset,
+                },
+                None => filters::BurnIn {
+                    path: &video_path,
+                    stream_index: Some(options.subtitles.track),
+                    time_offset: file_start - range_start,
+                },
+            })
+        };
+        let sidecar_captions = match (&captions_file, options.captions) {
+            (Some(file), CaptionMode::Sidecar) => Some(
+                std::fs::read_to_string(file).map_err(|e| format!("Failed to read generated captions: {}", e))?,
+            ),
+            _ => None,
+        };
+
+        let mut video_filters = filters::video_filters(&ratio, &options, source_size, range, burn_in)?;
+        let mut audio_filters = filters::audio_filters(&options, range)?;
+        for name in &options.filter_plugins {
+            let plugin = plugins
+                .get(name)
+                .ok_or_else(|| format!("Filter plugin '{}' is not installed", name))?;
+            let stage = plugin
+                .filter_stage(&FilterRequest {
+                    source: &video_source_label,
+                    start: range.start,
+                    end: range.end,
+                    ratio: &ratio,
+                    options: &options,
+                })
+                .await?;
+            video_filters.chain.extend(stage.video);
+            if !options.mute {
+                audio_filters.extend(stage.audio);
+            }
+        }
+
+        let audio_kbps = estimate::encoded_audio_kbps(&options);
+        let video_kbps = match options.max_size_mb {
+            Some(max_size_mb) => {
+                let reserved_audio_kbps = if options.mute { 0 } else { audio_kbps };
+                Some(estimate::target_video_kbps(max_size_mb, output_duration, reserved_audio_kbps)?)
+            }
+            None => None,
+        };
+        // A size target can only be hit by re-encoding
+        let mut reencode_video =
+            !video_filters.chain.is_empty() || video_kbps.is_some() || options.video_codec.is_some();
+
+        // Trimmed VFR clips drift out of audio sync in editors, so encodes
+        // are made constant rate and copies only keep VFR when asked to
+        if source_probe.is_none() {
+            match probe::probe(&video_path).await {
+                Ok(probed) => source_probe = Some(probed),
+                Err(e) => log::warn!("Skipping frame rate check: {}", privacy::scrub(&e)),
+            }
+        }
+        // Caught here rather than as an empty file or a cryptic FFmpeg error
+        if let Some(probe) = source_probe.as_ref().filter(|_| !is_youtube_video) {
+            let report = range::check(probe, start_seconds, start_seconds + clip_duration);
+            if let Some(error) = report.errors.into_iter().next() {
+                return Err(ErrorCode::InvalidRange.error(error.message));
+            }
+            for warning in report.warnings {
+                notify(warning.message);
+            }
+        }
+        let source_video = source_probe.as_ref().and_then(|p| p.video_stream());
+        let constant_rate = source_video
+            .filter(|s| s.is_variable_frame_rate())
+            .and_then(|s| s.constant_frame_rate());
+        let source_hdr = source_video.and_then(|s| s.hdr_format());
+        reencode_video |= options.tone_map_hdr && source_hdr.is_some();
+        if let Some(rate) = &constant_rate {
+            reencode_video |= options.constant_frame_rate;
+            if reencode_video {
+                video_filters.chain.push(format!("fps={}", rate));
+                notify(format!(
+                    "The source has a variable frame rate. Converting it to a constant {} fps to keep the audio in sync.",
+                    rate
+                ));
+            } else {
+                notify(
+                    "The source has a variable frame rate and was copied as is, so editors may drift out of audio sync. Enable constant frame rate conversion to fix this."
+                        .to_string(),
+                );
+            }
+        }
+        if options.keyframes.is_set() && !reencode_video {
+            notify("The video is copied, so it keeps the source's keyframes. Choose a codec to apply keyframe settings.".to_string());
+        }
+        let mut video_codec = options.video_codec.unwrap_or_default();
+        if reencode_video && video_codec != VideoCodec::H264 {
+            if let Err(e) = tools::check_encoder(video_codec.encoder(), options.gpu).await {
+                let fallback = video_codec.software_fallback().ok_or_else(|| e.clone())?;
+                tools::check_encoder(fallback.encoder(), None).await?;
+                notify(format!("{}. Encoding with {} instead.", e, fallback.encoder()));
+                video_codec = fallback;
+            }
+        }
+
+        let source_has_closed_captions = source_probe.as_ref().is_some_and(|p| p.has_closed_captions());
+        // Of the encoders, only x264 writes the captions back into the stream
+        if source_has_closed_captions
+            && reencode_video
+            && video_codec != VideoCodec::H264
+            && options.closed_captions == ClosedCaptions::Keep
+        {
+            notify(format!(
+                "{} drops the source's closed captions. Extract them to keep a copy.",
+                video_codec.encoder()
+            ));
+        }
+        let closed_caption_srt = match source_probe.as_ref() {
+            Some(probe) if options.closed_captions == ClosedCaptions::Extract => {
+                if source_has_closed_captions {
+                    let srt = closed_captions::extract_closed_captions(&video_path, probe, file_start, file_start + clip_duration)
+                        .await?;
+                    if srt.is_none() {
+                        notify("The source has closed captions, but none fall in the trimmed range".to_string());
+                    }
+                    srt
+                } else {
+                    notify("The source has no closed captions to extract".to_string());
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        // Copies keep HDR as is. Encoding HDR as 8-bit BT.709 without tone
+        // mapping is what makes it look washed out.
+        let color = match source_hdr {
+            Some(_) if options.tone_map_hdr || !video_codec.carries_hdr() => {
+                if reencode_video && !options.tone_map_hdr {
+                    notify(format!(
+                        "{} can't keep HDR, so the clip is converted to SDR.",
+                        video_codec.encoder()
+                    ));
+                }
+                if capabilities::supports(capabilities::Feature::HdrToneMapping).await {
+                    video_filters.chain.insert(0, filters::HDR_TO_SDR_FILTER.to_string());
+                } else {
+                    notify("This FFmpeg build can't tone map HDR (no zscale), so the SDR clip may look washed out.".to_string());
+                }
+                ColorOutput::Sdr
+            }
+            Some(format) => ColorOutput::Hdr(format),
+            None => ColorOutput::Source,
+        };
+
+        // A minute of 1080p is several GB of raw frames. Reversed clips are
+        // cut before the filters, so only the range itself is held.
+        const MAX_REVERSE_SECONDS: f64 = 60.0;
+        if options.looping.is_some() && clip_duration > MAX_REVERSE_SECONDS && !options.allow_long_reverse {
+            return Err(ErrorCode::InvalidOptions.error(format!(
+                "Looping a {:.0}s range needs to hold every frame in memory and may exhaust RAM. Trim to {:.0}s or less, or enable \"allow long reverse\".",
+                clip_duration, MAX_REVERSE_SECONDS
+            )));
+        }
+        // Each pass would fade on its own, leaving dips to black in between
+        if options.looping.is_some() && (options.fade_in.is_some() || options.fade_out.is_some()) {
+            return Err(ErrorCode::InvalidOptions.error("Fades can't be combined with looping"));
+        }
+        if options.reverse && clip_duration > MAX_REVERSE_SECONDS && !options.allow_long_reverse {
+            return Err(ErrorCode::InvalidOptions.error(format!(
+                "Reversing a {:.0}s range needs to hold every frame in memory and may exhaust RAM. Trim to {:.0}s or less, or enable \"allow long reverse\".",
+                clip_duration, MAX_REVERSE_SECONDS
+            )));
+        }
+
+        // Stems are exported straight from the source, so they would no
+        // longer line up with a retimed clip
+        if options.audio_stems && (options.speed() != 1.0 || options.reverse || options.passes() > 1) {
+            return Err(ErrorCode::InvalidOptions.error("Audio stems can't be exported for sped up, slowed down, reversed or looped clips"));
+        }
+        if options.closed_captions == ClosedCaptions::Extract && options.reverse {
+            return Err(ErrorCode::InvalidOptions.error("Closed captions can't be extracted for reversed clips"));
+        }
+
+        // On FAT32 and similar volumes, split by time into parts that stay under
+        // the file size limit (with headroom, since copies can only cut on
+        // keyframes) instead of failing when the muxer hits it.
+        let estimated_rate = match options.max_size_mb {
+            Some(max_size_mb) => Some(max_size_mb * 1_000_000.0 / output_duration),
+            // High-quality re-encodes can run above the source bitrate
+            None => source_probe
+                .as_ref()
+                .and_then(|p| p.bytes_per_second())
+                .map(|source_rate| if reencode_video { source_rate * 1.5 } else { source_rate }),
+        };
+        let requested_split = match options.split {
+            None => None,
+            Some(Split::Parts { count }) => {
+                if count == 0 {
+                    return Err(ErrorCode::InvalidOptions.error("Split into at least one part"));
+                }
+                // A millisecond over, so rounding can't leave a sliver of a last part
+                Some(output_duration / count as f64 + 0.001)
+            }
+            Some(Split::Chunks { seconds }) => {
+                if !seconds.is_finite() || seconds < 1.0 {
+                    return Err(ErrorCode::InvalidOptions.error("Chunks must be at least a second long"));
+                }
+                Some(seconds)
+            }
+            Some(Split::MaxSize { mb }) => {
+                if !mb.is_finite() || mb <= 0.0 {
+                    return Err(ErrorCode::InvalidOptions.error("The part size limit must be a positive number of MB"));
+                }
+                let rate = estimated_rate.ok_or("Couldn't read the source's bitrate to split it by size")?;
+                Some((mb * 1_000_000.0 * 0.9 / rate).floor().max(1.0))
+            }
+        };
+        let segment_seconds = match (file_size_limit, estimated_rate) {
+            (Some(limit), Some(rate)) => {
+                let budget = limit as f64 * 0.9;
+                let estimated = rate * requested_split.unwrap_or(output_duration).min(output_duration);
+                if estimated <= budget {
+                    requested_split
+                } else if options.split_at_filesystem_limit {
+                    Some((budget / rate).floor().max(1.0))
+                } else if requested_split.is_some() {
+                    return Err(ErrorCode::InvalidOptions.error(format!(
+                        "Each part would be about {} MB, over the {} MB file size limit of the output drive. Split into smaller parts or enable \"split large outputs\".",
+                        (estimated / 1_000_000.0).round(),
+                        limit / 1_000_000
+                    )));
+                } else {
+                    return Err(ErrorCode::InvalidOptions.error(format!(
+                        "The output would be about {} MB, over the {} MB file size limit of the output drive. Enable \"split large outputs\" to export it in parts.",
+                        (estimated / 1_000_000.0).round(),
+                        limit / 1_000_000
+                    )));
+                }
+            }
+            _ => requested_split,
+        };
+
+        let replacement_audio = options
+            .replacement_audio
+            .as_deref()
+            .filter(|_| !options.mute);
+        if let Some(audio_path) = replacement_audio {
+            if !Path::new(audio_path).exists() {
+                return Err(ErrorCode::FileNotFound.error(format!("Replacement audio file not found: {}", audio_path)));
+            }
+        }
+        // Vocals are removed from or isolated in the audio the clip would
+        // otherwise have, and the result stands in as its replacement audio
+        let mut separation_dir = None;
+        let separated_audio = match options.vocals {
+            Vocals::Keep => None,
+            _ if options.mute => None,
+            vocals => {
+                let config = settings.stem_separator.as_ref().ok_or("No stem separator is configured.")?;
+                let work_dir = separation_dir
+                    .insert(paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?)
+                    .path();
+                let (audio_source, audio_start) = match replacement_audio {
+                    Some(audio_path) => (Path::new(audio_path), replacement_audio_start),
+                    None => (video_path.as_path(), file_start),
+                };
+                let stem = separation::separate(audio_source, audio_start, clip_duration, vocals, config, work_dir).await?;
+                Some(stem.to_string_lossy().into_owned())
+            }
+        };
+        let (replacement_audio, replacement_audio_start) = match &separated_audio {
+            Some(stem) => (Some(stem.as_str()), 0.0),
+            None => (replacement_audio, replacement_audio_start),
+        };
+        let background_music = options.background_music.as_ref().filter(|_| !options.mute);
+        if let Some(music) = background_music {
+            if !Path::new(&music.path).exists() {
+                return Err(ErrorCode::FileNotFound.error(format!("Background music file not found: {}", music.path)));
+            }
+            if options.audio_streams.as_ref().is_some_and(|streams| streams.len() > 1) {
+                return Err(ErrorCode::InvalidOptions.error("Background music can only be mixed into a single audio track"));
+            }
+        }
+
+        if options.normalize_loudness && !options.mute {
+            let measurement = match replacement_audio {
+                Some(audio_path) => {
+                    loudness::measure(Path::new(audio_path), replacement_audio_start, clip_duration, &audio_filters)
+                        .await?
+                }
+                None => loudness::measure(&video_path, file_start, clip_duration, &audio_filters).await?,
+            };
+            match measurement.normalize_filter() {
+                Some(filter) => audio_filters.push(filter),
+                None => log::info!("Clip is silent, skipping loudness normalization"),
+            }
+        }
+
+        let clip_creation_time = if let Some(time) = &options.tags.creation_time {
+            Some(metadata::parse_creation_time(time)?)
+        } else if options.preserve_creation_time {
+            let local_file = if is_remote { None } else { Some(video_path.as_path()) };
+            source_probe
+                .as_ref()
+                .and_then(|p| metadata::source_creation_time(p, local_file))
+                .map(|recorded| recorded + chrono::Duration::milliseconds(timecode::to_millis(start_seconds) as i64))
+        } else {
+            None
+        };
+
+        // Only encodes are worth holding back; a copy finishes in moments
+        let encoder_threads = match settings.battery_policy.as_ref().filter(|_| reencode_video) {
+            Some(policy) => policy.wait_for_power(&notify).await,
+            None => None,
+        };
+
+        cancel::check()?;
+
+        // A stream copy can fail on codecs MP4 can't hold or on broken
+        // timestamps; those are retried once as a re-encode
+        let (success, ffmpeg_errors) = loop {
+            let mut command = ffmpeg::new_command();
+
+            // Decoding on the chosen GPU too keeps the other one idle; FFmpeg
+            // decodes in software when the GPU can't handle the source codec
+            if let Some(gpu) = options.gpu.filter(|_| reencode_video) {
+                command.args(["-hwaccel", "cuda", "-hwaccel_device", &gpu.to_string()]);
+            }
+            seek.add_source(&mut command, &video_path);
+
+            if let Some(audio_path) = replacement_audio {
+                // The trim below seeks the output timeline, so shift the new audio to
+                // begin exactly where the trimmed range starts.
+                if replacement_audio_start > 0.0 {
+                    command.args(["-ss", &replacement_audio_start.to_string()]);
+                }
+                command
+                    .args(["-itsoffset", &range_start.to_string()])
+                    .input(audio_path);
+            }
+
+            // Like the replacement audio, an external subtitle file is on the
+            // original video's timeline and is shifted to line up with the video.
+            let mut subtitle_input = None;
+            if subtitle_mode == SubtitleMode::Keep {
+                if let Some(file) = &subtitle_file {
+                    subtitle_input = Some(if replacement_audio.is_some() { 2 } else { 1 });
+                    command
+                        .args(["-itsoffset", &(-range.source_offset).to_string()])
+                        .input(file.to_string_lossy());
+                }
+            }
+
+            // Starts at the trim start like the replacement audio, mixed in
+            // through a filter graph in place of -af
+            let mut music_graph = None;
+            if let Some(music) = background_music {
+                let music_input = 1 + usize::from(replacement_audio.is_some()) + usize::from(subtitle_input.is_some());
+                command
+                    .args(["-stream_loop", "-1", "-itsoffset", &range_start.to_string()])
+                    .input(&music.path);
+                let voice = match (replacement_audio, &options.audio_streams) {
+                    (Some(_), _) => "1:a:0".to_string(),
+                    (None, Some(streams)) if !streams.is_empty() => format!("0:{}", streams[0]),
+                    (None, _) => "0:a:0".to_string(),
+                };
+                let voice_filters = filters::voice_filters(options.voice_preset);
+                let (voice_filters, mix_filters) = audio_filters.split_at(voice_filters.len());
+                music_graph = Some(filters::music_mix_graph(&voice, music_input, music, voice_filters, mix_filters)?);
+            }
+
+            seek.add_output_trim(&mut command);
+
+            // Kept until the second pass has read the first pass's statistics
+            let mut passlog_dir = None;
+            if !reencode_video {
+                // Nothing to change in the picture, so copy with quality preservation
+                command.args(["-c:v", "copy"]);
+                if matches!(seek, Seek::Output { .. }) {
+                    command.args(["-avoid_negative_ts", "make_zero"]);
+                }
+            } else {
+                if !video_filters.chain.is_empty() {
+                    command.args(["-vf", &video_filters.chain.join(",")]);
+                }
+                apply_video_encoding(
+                    &mut command,
+                    video_codec,
+                    color,
+                    video_filters.output_size,
+                    video_kbps,
+                    options.quality.unwrap_or(settings.default_quality),
+                    options.keyframes,
+                );
+                if let Some(gpu) = options.gpu.filter(|_| video_codec == VideoCodec::HevcNvenc) {
+                    command.args(["-gpu", &gpu.to_string()]);
+                }
+                if let Some(threads) = encoder_threads {
+                    command.args(["-threads", &threads.to_string()]);
+                }
+                // Encodes can cut anywhere, so parts start exactly on time
+                if let Some(seconds) = segment_seconds {
+                    command.args(["-force_key_frames", &format!("expr:gte(t,n_forced*{})", seconds)]);
+                }
+                if let Some(kbps) = video_kbps.filter(|_| video_codec.supports_two_pass()) {
+                    let passlog = passlog_dir
+                        .insert(paths::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?)
+                        .path()
+                        .join("passlog");
+                    FirstPass {
+                        video_path: &video_path,
+                        seek,
+                        video_filters: &video_filters,
+                        codec: video_codec,
+                        color,
+                        kbps,
+                        keyframes: options.keyframes,
+                    }
+                    .run(&passlog)
+                    .await?;
+                    command.args(["-pass", "2", "-passlogfile", &passlog.to_string_lossy()]);
+                }
+            }
+
+            // Any -map turns off FFmpeg's default stream selection, so video and
+            // audio are mapped explicitly whenever one of these needs a map.
+            let selects_streams = replacement_audio.is_some()
+                || music_graph.is_some()
+                || subtitle_mode == SubtitleMode::Keep
+                || options.audio_streams.is_some()
+                || options.subtitle_streams.is_some();
+            if selects_streams {
+                command.args(["-map", "0:v:0"]);
+                match (replacement_audio, &options.audio_streams) {
+                    _ if music_graph.is_some() => {
+                        command.args(["-map", "[aout]"]);
+                    }
+                    (Some(_), _) => {
+                        command.args(["-map", "1:a:0"]);
+                    }
+                    (None, Some(streams)) => {
+                        for index in streams {
+                            command.args(["-map", &format!("0:{}", index)]);
+                        }
+                    }
+                    (None, None) => {
+                        command.args(["-map", "0:a:0?"]);
+                    }
+                }
+            }
+            match subtitle_mode {
+                // Burned-in subtitles shouldn't also appear as a track
+                SubtitleMode::Burn | SubtitleMode::Drop => {
+                    command.arg("-sn");
+                }
+                SubtitleMode::Keep | SubtitleMode::Unchanged => {
+                    // mp4 and mov only hold text subtitles, as mov_text, so
+                    // bitmap ones such as PGS or VobSub are left out
+                    let is_text = |index: u32| {
+                        source_probe
+                            .as_ref()
+                            .and_then(|p| p.streams.iter().find(|s| s.index == index))
+                            .map_or(true, |s| s.is_text_subtitle())
+                    };
+                    let subtitle_maps: Vec<String> = match (subtitle_input, &options.subtitle_streams) {
+                        (Some(index), _) => vec![format!("{}:s:0", index)],
+                        (None, Some(streams)) => streams
+                            .iter()
+                            .filter(|index| is_text(**index))
+                            .map(|index| format!("0:{}", index))
+                            .collect(),
+                        (None, None) if subtitle_mode == SubtitleMode::Keep => match source_probe.as_ref() {
+                            Some(probe) => probe
+                                .streams
+                                .iter()
+                                .filter(|s| s.codec_type.as_deref() == Some("subtitle") && s.is_text_subtitle())
+                                .map(|s| format!("0:{}", s.index))
+                                .collect(),
+                            None => vec!["0:s?".to_string()],
+                        },
+                        (None, None) => Vec::new(),
+                    };
+                    for map in &subtitle_maps {
+                        command.args(["-map", map]);
+                    }
+                    if !subtitle_maps.is_empty() {
+                        command.args(["-c:s", "mov_text"]);
+                    }
+                    // Tags for streams that aren't in the output would fail the encode
+                    let subtitle_outputs = subtitle_maps.iter().filter(|map| *map != "0:s?").count();
+                    command.args(metadata::language_metadata_args(
+                        "s",
+                        &options.subtitle_languages,
+                        subtitle_outputs,
+                    ));
+                }
+            }
+
+            // Audio is re-encoded alongside a video re-encode, and whenever it is
+            // filtered or replaced
+            if options.mute {
+                command.arg("-an");
+            } else if !reencode_video && audio_filters.is_empty() && replacement_audio.is_none() && music_graph.is_none() {
+                command.args(["-c:a", "copy"]);
+            } else {
+                if let Some(graph) = &music_graph {
+                    command.args(["-filter_complex", graph]);
+                } else if !audio_filters.is_empty() {
+                    command.args(["-af", &audio_filters.join(",")]);
+                }
+                if video_codec.is_intermediate() {
+                    // What editors expect alongside ProRes and DNxHR
+                    command.args(["-c:a", "pcm_s16le", "-ar", "48000", "-ac", "2"]);
+                } else {
+                    apply_best_quality_audio_encoding(&mut command, audio_kbps);
+                }
+            }
+
+            if !options.mute {
+                let audio_outputs = match (&music_graph, replacement_audio, &options.audio_streams) {
+                    (Some(_), _, _) | (None, Some(_), _) => 1,
+                    (None, None, Some(streams)) => streams.len(),
+                    // FFmpeg's default pick, if the source has audio at all
+                    (None, None, None) => source_probe.as_ref().map_or(0, |p| {
+                        usize::from(p.streams.iter().any(|s| s.codec_type.as_deref() == Some("audio")))
+                    }),
+                };
+                command.args(metadata::language_metadata_args("a", &options.audio_languages, audio_outputs));
+            }
+
+            if options.strip_metadata {
+                command.args(metadata::STRIP_METADATA_ARGS);
+            }
+            for (key, value) in options.tags.text_tags() {
+                command.args(["-metadata", &format!("{}={}", key, value)]);
+            }
+            if let Some(time) = clip_creation_time {
+                command.args(["-metadata", &format!("creation_time={}", metadata::creation_time_tag(time))]);
+            }
+
+            // The mp4 muxer drops tags it doesn't know (like the Apple location key)
+            // unless told to write arbitrary metadata.
+            let mut movflags = settings.mux.movflags();
+            match options.location {
+                job::LocationPolicy::Unchanged => {}
+                job::LocationPolicy::Keep => {
+                    match source_probe.as_ref().and_then(metadata::source_location) {
+                        Some(location) => {
+                            command.args(metadata::location_metadata_args(Some(&location)));
+                            movflags.push_str("+use_metadata_tags");
+                        }
+                        None => log::info!("Source has no location metadata to keep"),
+                    }
+                }
+                job::LocationPolicy::Strip => {
+                    command.args(metadata::location_metadata_args(None));
+                }
+            }
+
+            match segment_seconds {
+                Some(seconds) => {
+                    command
+                        .args(["-f", "segment", "-segment_time", &seconds.to_string()])
+                        .args(["-reset_timestamps", "1", "-segment_format", container]);
+                    if !movflags.is_empty() {
+                        command.args(["-segment_format_options", &format!("movflags={}", movflags)]);
+                    }
+                    let pattern = write_dir.join(format!("{}_part%03d.{}", output_stem, container));
+                    command.output(pattern.to_string_lossy()).overwrite();
+                }
+                None => {
+                    if !movflags.is_empty() {
+                        command.args(["-movflags", &movflags]);
+                    }
+                    command.output(output_path.to_string_lossy()).overwrite();
+                }
+            }
+
+            ffmpeg::record_command(&mut command);
+            let mut child = command.spawn().map_err(|e| {
+                let code = if e.kind() == std::io::ErrorKind::NotFound {
+                    ErrorCode::FfmpegMissing
+                } else {
+                    ErrorCode::EncodeFailed
+                };
+                code.error(format!("Failed to execute FFmpeg: {}", e))
+            })?;
+
+            let (success, ffmpeg_errors, ffmpeg_diagnostics) = ffmpeg::drain(&mut child, |events| {
+                let mut success = false;
+                let mut ffmpeg_errors: Vec<String> = Vec::new();
+                // Muxer complaints about timestamps are logged as warnings before
+                // the error that stops the copy
+                let mut ffmpeg_diagnostics: Vec<String> = Vec::new();
+                for event in events {
+                    ffmpeg::record_event(&event);
+                    match event {
+                        ffmpeg_sidecar::event::FfmpegEvent::Done => {
+                            success = true;
+                            break;
+                        }
+                        ffmpeg_sidecar::event::FfmpegEvent::Error(e) => {
+                            ffmpeg_diagnostics.push(e.clone());
+                            ffmpeg_errors.push(e);
+                        }
+                        ffmpeg_sidecar::event::FfmpegEvent::Log(
+                            ffmpeg_sidecar::event::LogLevel::Warning | ffmpeg_sidecar::event::LogLevel::Error,
+                            line,
+                        ) => {
+                            ffmpeg_diagnostics.push(line);
+                        }
+                        _ => {}
+                    }
+                }
+                (success, ffmpeg_errors, ffmpeg_diagnostics)
+            })
+            .await?;
+
+            if !success && !reencode_video {
+                if let Some(reason) = ffmpeg::stream_copy_failure(&ffmpeg_diagnostics) {
+                    notify(format!("Copying the video failed because {}. Re-encoding it instead.", reason));
+                    for part in segment_outputs(&write_dir, &output_stem) {
+                        let _ = std::fs::remove_file(part);
+                    }
+                    reencode_video = true;
+                    if let Some(rate) = &constant_rate {
+                        video_filters.chain.push(format!("fps={}", rate));
+                    }
+                    continue;
+                }
+            }
+            break (success, ffmpeg_errors);
+        };
+
+        let outputs: Vec<PathBuf> = if segment_seconds.is_some() {
+            segment_outputs(&write_dir, &output_stem)
+        } else if output_path.exists() {
+            vec![output_path.clone()]
+        } else {
+            Vec::new()
+        };
+
+        if success && !outputs.is_empty() {
+            let mut saved = Vec::with_capacity(outputs.len());
+            for (index, output) in outputs.iter().enumerate() {
+                // Each part gets a poster of its own, sharing the part's name
+                let mut written = vec![output.clone()];
+                if options.poster_frame {
+                    written.push(poster::write_poster_frame(output).await?);
+                }
+                // Stems cover the whole range, so they go with the first part
+                if let Some(probe) = source_probe.as_ref().filter(|_| options.audio_stems && index == 0) {
+                    let trim_range = (!is_youtube_video).then_some((start_time.as_str(), end_time.as_str()));
+                    written.extend(stems::write_audio_stems(&video_path, trim_range, probe, &write_dir, &output_stem).await?);
+                }
+                if let Some(srt) = &sidecar_captions {
+                    let part_start = segment_seconds.unwrap_or(0.0) * index as f64;
+                    let srt_path = output.with_extension("srt");
+                    std::fs::write(&srt_path, captions::retime_srt(srt, options.speed(), part_start))
+                        .map_err(|e| format!("Failed to save captions: {}", e))?;
+                    written.push(srt_path);
+                }
+                if let Some(srt) = &closed_caption_srt {
+                    let part_start = segment_seconds.unwrap_or(0.0) * index as f64;
+                    let srt_path = output.with_extension("cc.srt");
+                    std::fs::write(&srt_path, captions::retime_srt(srt, options.speed(), part_start))
+                        .map_err(|e| format!("Failed to save closed captions: {}", e))?;
+                    written.push(srt_path);
+                }
+                if let Some(passphrase) = passphrase.clone() {
+                    let output_dir = output_dir.clone();
+                    written = blocking::run(move || {
+                        written
+                            .iter()
+                            .map(|file| encryption::encrypt_file(file, &output_dir, &passphrase))
+                            .collect::<Result<_, _>>()
+                    })
+                    .await??;
+                }
+                if let Some(time) = clip_creation_time {
+                    let offset = segment_seconds.unwrap_or(0.0) * index as f64;
+                    let part_time = time + chrono::Duration::milliseconds(timecode::to_millis(offset) as i64);
+                    for file in &written {
+                        if let Err(e) = metadata::set_file_times(file, part_time) {
+                            log::warn!("{}", privacy::scrub(&e));
+                        }
+                    }
+                }
+                // A failed mirror leaves the export itself intact
+                if let Some(config) = &settings.mirror {
+                    for file in &written {
+                        if let Err(e) = mirror::mirror_file(file, config).await {
+                            let name = file.file_name().unwrap_or_default().to_string_lossy();
+                            notify(format!("{} was saved but not mirrored: {}", name, e));
+                        }
+                    }
+                }
+                saved.push(written.swap_remove(0));
+            }
+            let outputs = saved;
+
+            let size_bytes = outputs
+                .iter()
+                .filter_map(|output| std::fs::metadata(output).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+            if let Some(history) = history {
+                let entry = history::HistoryEntry {
+                    id: chrono::Utc::now().timestamp_millis().to_string(),
+                    source: privacy::redact(&video_source_label),
+                    start_time,
+                    end_time,
+                    ratio,
+                    options: privacy::redact_options(&options),
+                    output_path: privacy::redact(&outputs[0].display().to_string()),
+                    size_bytes: Some(size_bytes),
+                    created_at: chrono::Utc::now().to_rfc3339(),
+                    fingerprint: clip_fingerprint,
+                };
+                if let Err(e) = history.record(entry) {
+                    log::warn!("Failed to record trim history: {}", privacy::scrub(&e));
+                }
+            }
+            let source_video = source_probe.as_ref().and_then(|p| p.video_stream());
+            let codec = match source_video {
+                Some(_) if reencode_video => Some(video_codec.codec_name().to_string()),
+                Some(video) => video.codec_name.clone(),
+                None => None,
+            };
+            Ok(TrimOutput {
+                outputs,
+                output_dir,
+                duration: output_duration,
+                size_bytes,
+                codec,
+                elapsed: started.elapsed(),
+            })
+        } else {
+            if !ffmpeg_errors.is_empty() {
+                Err(ErrorCode::EncodeFailed.error(format!("FFmpeg failed: {}", ffmpeg_errors.join("; "))))
+            } else {
+                Err(ErrorCode::EncodeFailed.error("FFmpeg failed to create the output file or did not finish successfully."))
+            }
+        }
+    }
+}
+
+// Converts frame numbers ("1234f") with the source's frame rate, which
+// means probing it before anything else happens
+async fn resolve_frame_numbers(source: &str, start_time: &str, end_time: &str) -> Result<(String, String), TrimError> {
+    let fps = probe::probe(Path::new(source)).await?.video_stream().and_then(|v| v.frame_rate());
+    let invalid_range = |e: String| ErrorCode::InvalidRange.error(e);
+    let start = timecode::parse_time(start_time, fps).map_err(invalid_range)?;
+    let end = timecode::parse_time(end_time, fps).map_err(invalid_range)?;
+    if end <= start {
+        return Err(ErrorCode::InvalidRange.error("End time must be after start time"));
+    }
+    Ok((timecode::format_timecode(start), timecode::format_timecode(end)))
+}
+
+impl TrimJobBuilder {
+    /// Aspect ratio ("9:16"), exact size ("1080x1920") or "Original".
+    pub fn ratio(mut self, ratio: impl Into<String>) -> Self {
+        self.ratio = ratio.into();
+        self
+    }
+
+    pub fn options(mut self, options: TrimOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Proxy, rate limit and other settings used for downloads, plus the
+    /// output folder and watermark defaults.
+    pub fn settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Where the clip is saved. Defaults to the settings' `output_dir`, then
+    /// [`crate::paths::output_dir`].
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// How direct downloads retry after a dropped connection, in place of
+    /// the settings' `download_retry`.
+    pub fn download_retry(mut self, retry: download::RetryPolicy) -> Self {
+        self.settings.download_retry = retry;
+        self
+    }
+
+    // For parts that only feed another export, like the pieces of a concat
+    pub(crate) fn without_mirror(mut self) -> Self {
+        self.settings.mirror = None;
+        self
+    }
+
+    /// Source resolvers and filter stages from plugins. Filter plugins only
+    /// run when named in the job's `filter_plugins` option.
+    pub fn plugins(mut self, plugins: Arc<PluginRegistry>) -> Self {
+        self.plugins = plugins;
+        self
+    }
+
+    /// Encrypts everything the job writes with this passphrase. Required
+    /// when the job's `encrypt` option is set.
+    pub fn passphrase(mut self, passphrase: Passphrase) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Called about once a second with the CPU, memory and network use
+    /// while the job runs.
+    pub fn resource_listener(mut self, listener: ResourceListener) -> Self {
+        self.resource_listener = Some(listener);
+        self
+    }
+
+    /// File name for the clip, without extension. Defaults to
+    /// `trimmed_<timestamp>`; a job script can still change it.
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.output_name = Some(filename::sanitize_filename(&name.into()));
+        self
+    }
+
+    /// Called with a message whenever the job changes course on its own,
+    /// e.g. falls back to a software encoder.
+    pub fn notice_listener(mut self, listener: NoticeListener) -> Self {
+        self.notice_listener = Some(listener);
+        self
+    }
+
+    /// Called with the file in the way when the output's name is taken and
+    /// the job's `on_collision` option is `Ask`.
+    pub fn collision_listener(mut self, listener: CollisionListener) -> Self {
+        self.collision_listener = Some(listener);
+        self
+    }
+
+    /// Called as a direct download is written to disk, about every
+    /// megabyte. YouTube downloads aren't reported.
+    pub fn download_listener(mut self, listener: DownloadListener) -> Self {
+        self.download_listener = Some(listener);
+        self
+    }
+
+    /// Stops the job, and the downloads and tools it started, once `token`
+    /// is cancelled. The job then fails with [`ErrorCode::Cancelled`].
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Checks the range and ratio without touching the source.
+    pub fn build(mut self) -> Result<TrimJob, TrimError> {
+        let invalid_options = |e: String| ErrorCode::InvalidOptions.error(e);
+        let invalid_range = |e: String| ErrorCode::InvalidRange.error(e);
+        filters::validate_ratio(&self.ratio).map_err(invalid_options)?;
+        // Rewritten as HH:MM:SS.mmm so FFmpeg and yt-dlp cut at the exact
+        // millisecond. Frame numbers wait for the source's frame rate in `run`.
+        let start_is_frame = timecode::is_frame_number(&self.start_time);
+        let end_is_frame = timecode::is_frame_number(&self.end_time);
+        if !start_is_frame {
+            self.start_time = timecode::normalize_time(&self.start_time, None).map_err(invalid_range)?;
+        }
+        if !end_is_frame {
+            self.end_time = timecode::normalize_time(&self.end_time, None).map_err(invalid_range)?;
+        }
+        if !start_is_frame
+            && !end_is_frame
+            && timecode::time_to_seconds(&self.end_time).map_err(invalid_range)?
+                <= timecode::time_to_seconds(&self.start_time).map_err(invalid_range)?
+        {
+            return Err(ErrorCode::InvalidRange.error("End time must be after start time"));
+        }
+        if let Some(max_size_mb) = self.options.max_size_mb {
+            if !max_size_mb.is_finite() || max_size_mb <= 0.0 {
+                return Err(ErrorCode::InvalidOptions.error("Maximum file size must be a positive number of MB"));
+            }
+            if self.options.video_codec.is_some_and(VideoCodec::is_intermediate) {
+                return Err(ErrorCode::InvalidOptions.error("ProRes and DNxHR are fixed-quality formats and can't target a file size"));
+            }
+        }
+        if self.options.encrypt && self.passphrase.is_none() {
+            return Err(ErrorCode::InvalidOptions.error("A passphrase is required to encrypt the export"));
+        }
+        for language in self.options.audio_languages.iter().chain(&self.options.subtitle_languages) {
+            metadata::validate_language(language).map_err(invalid_options)?;
+        }
+        self.options.keyframes.validate().map_err(invalid_options)?;
+        if let Some(time) = &self.options.tags.creation_time {
+            metadata::parse_creation_time(time).map_err(invalid_options)?;
+        }
+        if self.options.strip_metadata
+            && (self.options.preserve_creation_time || self.options.location == job::LocationPolicy::Keep)
+        {
+            return Err(ErrorCode::InvalidOptions.error("Stripping metadata can't be combined with keeping the creation time or location"));
+        }
+        let mut options = self.options;
+        let passphrase = self.passphrase.filter(|_| options.encrypt);
+        if options.watermark.is_none() {
+            options.watermark = self.settings.watermark.clone();
+        }
+        let output_dir = self.output_dir.unwrap_or_else(|| self.settings.resolved_output_dir());
+        Ok(TrimJob {
+            source: self.source,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            ratio: self.ratio,
+            options,
+            settings: self.settings,
+            output_dir,
+            plugins: self.plugins,
+            passphrase,
+            resource_listener: self.resource_listener,
+            notice_listener: self.notice_listener,
+            collision_listener: self.collision_listener,
+            download_listener: self.download_listener,
+            cancellation: self.cancellation,
+            output_name: self.output_name,
+        })
+    }
+}
+
+// An empty file claiming a name in the output folder until the clip is
+// written over it; removed if the job ends without doing so
+struct OutputReservation(PathBuf);
+
+impl Drop for OutputReservation {
+    fn drop(&mut self) {
+        if std::fs::metadata(&self.0).is_ok_and(|metadata| metadata.len() == 0) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
+// Picks the name the clip is saved under, claiming it with an empty file
+// unless existing files are to be overwritten
+fn reserve_output(
+    output_dir: &Path,
+    stem: String,
+    container: &str,
+    encrypted: bool,
+    policy: OutputCollision,
+    listener: Option<&CollisionListener>,
+) -> Result<(String, Option<OutputReservation>), TrimError> {
+    if policy == OutputCollision::Overwrite {
+        return Ok((stem, None));
+    }
+    let mut number = 1;
+    loop {
+        let candidate = if number == 1 { stem.clone() } else { format!("{}_{}", stem, number) };
+        let file_name = format!("{}.{}", candidate, container);
+        let path = output_dir.join(if encrypted { format!("{}.age", file_name) } else { file_name });
+        if segment_outputs(output_dir, &candidate).is_empty() {
+            match std::fs::File::options().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok((candidate, Some(OutputReservation(path)))),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(format!("Failed to create {}: {}", path.display(), e).into()),
+            }
+        }
+        match policy {
+            OutputCollision::Rename | OutputCollision::Overwrite => number += 1,
+            OutputCollision::Fail | OutputCollision::Ask => {
+                if let (OutputCollision::Ask, Some(listener)) = (policy, listener) {
+                    listener.notify(path.clone());
+                }
+                return Err(ErrorCode::OutputExists.error(format!(
+                    "{} already exists. Choose another name, or let the clip be renamed or replace it.",
+                    path.display()
+                )));
+            }
+        }
+    }
+}
+
+// Parts written by the segment muxer, in order
+fn segment_outputs(output_dir: &Path, output_stem: &str) -> Vec<PathBuf> {
+    let prefix = format!("{}_part", output_stem);
+    let mut parts: Vec<PathBuf> = std::fs::read_dir(output_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(&prefix))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    parts.sort();
+    parts
+}
+
+// Where a local or direct-link source is cut
+#[derive(Debug, Clone, Copy)]
+enum Seek<'a> {
+    // YouTube segments were already cut by yt-dlp
+    None,
+    // Before the source's -i, so the filters only see the range. The output
+    // is capped at the retimed `length` in seconds, since other inputs
+    // (replacement audio, looped music) don't end with the range.
+    Input { start: &'a str, end: &'a str, length: f64 },
+    // After every input, on the source's timeline
+    Output { start: &'a str, end: &'a str },
+}
+
+impl Seek<'_> {
+    fn add_source(self, command: &mut FfmpegCommand, path: &Path) {
+        if let Seek::Input { start, end, .. } = self {
+            command.args(["-ss", start, "-to", end]);
+        }
+        command.input(path.to_string_lossy());
+    }
+
+    // Output options, so this must follow every input
+    fn add_output_trim(self, command: &mut FfmpegCommand) {
+        match self {
+            Seek::None => {}
+            Seek::Input { length, .. } => {
+                command.args(["-t", &length.to_string()]);
+            }
+            Seek::Output { start, end } => {
+                command.args(["-ss", start, "-to", end]);
+            }
+        }
+    }
+}
+
+// Analysis pass of a two-pass encode: the same picture as the real export,
+// encoded at the target bitrate only to write the encoder's rate statistics
+struct FirstPass<'a> {
+    video_path: &'a Path,
+    seek: Seek<'a>,
+    video_filters: &'a filters::VideoFilters,
+    codec: VideoCodec,
+    color: ColorOutput,
+    kbps: u32,
+    keyframes: Keyframes,
+}
+
+impl FirstPass<'_> {
+    async fn run(&self, passlog: &Path) -> Result<(), String> {
+        let mut command = ffmpeg::new_command();
+        self.seek.add_source(&mut command, self.video_path);
+        self.seek.add_output_trim(&mut command);
+        if !self.video_filters.chain.is_empty() {
+            command.args(["-vf", &self.video_filters.chain.join(",")]);
+        }
+        // The bitrate decides the quality here
+        apply_video_encoding(
+            &mut command,
+            self.codec,
+            self.color,
+            self.video_filters.output_size,
+            Some(self.kbps),
+            Quality::default(),
+            self.keyframes,
+        );
+        command
+            .args(["-pass", "1", "-passlogfile", &passlog.to_string_lossy()])
+            .args(["-an", "-sn", "-dn"])
+            .args(["-f", "null", "-"]);
+        ffmpeg::run(command).await.map(|_| ())
+    }
+}
+
+// How an encode's colors are tagged
+#[derive(Debug, Clone, Copy)]
+enum ColorOutput {
+    // As FFmpeg passes them through from an SDR source
+    Source,
+    // Tone mapped from HDR
+    Sdr,
+    Hdr(HdrFormat),
+}
+
+// Encoder settings for re-encoded output. Without a bitrate each codec
+// encodes at a quality level that looks like the source; with one, the
+// encode is rate-controlled for a size target.
+fn apply_video_encoding(
+    command: &mut FfmpegCommand,
+    codec: VideoCodec,
+    color: ColorOutput,
+    output_size: Option<(u32, u32)>,
+    kbps: Option<u32>,
+    quality: Quality,
+    keyframes: Keyframes,
+) {
+    command.args(["-c:v", codec.encoder()]);
+    let crf = quality.crf(codec);
+    match codec {
+        VideoCodec::H264 => apply_best_quality_h264_encoding(command, output_size, kbps, crf, keyframes.interval),
+        VideoCodec::H265 => {
+            command.args(["-preset", "slow"]);
+            apply_rate_control(command, codec, kbps, "-crf", crf);
+        }
+        VideoCodec::HevcNvenc => {
+            command.args(["-preset", "p6", "-tune", "hq", "-rc", "vbr"]);
+            apply_rate_control(command, codec, kbps, "-cq", crf);
+        }
+        VideoCodec::SvtAv1 => {
+            command.args(["-preset", "6"]);
+            apply_rate_control(command, codec, kbps, "-crf", crf);
+        }
+        VideoCodec::AomAv1 => {
+            command.args(["-cpu-used", "4", "-row-mt", "1"]);
+            apply_rate_control(command, codec, kbps, "-crf", crf);
+        }
+        VideoCodec::ProRes => {
+            // ProRes 422 HQ, tagged as Apple's own so every NLE accepts it
+            command.args(["-profile:v", "3", "-vendor", "apl0"]);
+        }
+        VideoCodec::Dnxhr => {
+            command.args(["-profile:v", "dnxhr_hq"]);
+        }
+    }
+    apply_keyframes(command, codec, keyframes);
+    if matches!(codec, VideoCodec::H265 | VideoCodec::HevcNvenc) {
+        // Apple players only open HEVC in mp4 with the hvc1 tag
+        command.args(["-tag:v", "hvc1"]);
+    }
+    // Intermediates keep 4:2:2 chroma for grading; HDR needs 10 bits
+    let pixel_format = match (codec, color) {
+        (VideoCodec::ProRes, _) => "yuv422p10le",
+        (VideoCodec::Dnxhr, _) => "yuv422p",
+        (VideoCodec::HevcNvenc, ColorOutput::Hdr(_)) => "p010le",
+        (_, ColorOutput::Hdr(_)) => "yuv420p10le",
+        _ => "yuv420p",
+    };
+    command.args(["-pix_fmt", pixel_format]);
+    match color {
+        ColorOutput::Source => {}
+        ColorOutput::Sdr => {
+            command.args(["-color_primaries", "bt709", "-color_trc", "bt709", "-colorspace", "bt709"]);
+        }
+        ColorOutput::Hdr(format) => {
+            command.args([
+                "-color_primaries",
+                "bt2020",
+                "-color_trc",
+                format.transfer(),
+                "-colorspace",
+                "bt2020nc",
+            ]);
+        }
+    }
+}
+
+// A target bitrate, or the codec's constant-quality setting. Codecs without
+// two-pass support cap their peaks instead, so the average stays in budget.
+fn apply_rate_control(
+    command: &mut FfmpegCommand,
+    codec: VideoCodec,
+    kbps: Option<u32>,
+    quality_flag: &str,
+    quality: u32,
+) {
+    match kbps {
+        Some(kbps) => {
+            command.args(["-b:v", &format!("{}k", kbps)]);
+            if !codec.supports_two_pass() {
+                command.args(["-maxrate", &format!("{}k", kbps), "-bufsize", &format!("{}k", kbps * 2)]);
+            }
+        }
+        None => {
+            command.args([quality_flag, &quality.to_string()]);
+            // Pure quality mode; otherwise these encoders also aim for a default bitrate
+            if matches!(codec, VideoCodec::HevcNvenc | VideoCodec::AomAv1) {
+                command.args(["-b:v", "0"]);
+            }
+        }
+    }
+}
+
+pub(crate) fn apply_best_quality_h264_encoding(
+    command: &mut FfmpegCommand,
+    output_size: Option<(u32, u32)>,
+    kbps: Option<u32>,
+    crf: u32,
+    gop: Option<u32>,
+) {
+    command.args(["-preset", "slow"]); // Better quality than fast
+    match kbps {
+        Some(kbps) => command.args(["-b:v", &format!("{}k", kbps)]),
+        None => command.args(["-crf", &crf.to_string()]),
+    };
+    command.args(["-profile:v", "high"]);
+    // Level 4.2 tops out at 1080p; let x264 pick the level for anything larger
+    if output_size.map_or(true, |(w, h)| w * h <= 1920 * 1088) {
+        command.args(["-level", "4.2"]);
+    }
+    command.args([
+        "-g", &gop.unwrap_or(30).to_string(), // Keyframe interval
+        "-bf", "2", // B-frames
+    ]);
+}
+
+// Each encoder takes the scene-cut switch in its own way. ProRes and DNxHR
+// are intra-only, every frame a keyframe, so there's nothing to set.
+fn apply_keyframes(command: &mut FfmpegCommand, codec: VideoCodec, keyframes: Keyframes) {
+    if codec.is_intermediate() {
+        return;
+    }
+    // x264's -g is set with the rest of its tuning
+    if let Some(interval) = keyframes.interval.filter(|_| codec != VideoCodec::H264) {
+        command.args(["-g", &interval.to_string()]);
+    }
+    // NVENC and SVT-AV1 have no minimum interval
+    if let Some(min_interval) = keyframes
+        .min_interval
+        .filter(|_| matches!(codec, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::AomAv1))
+    {
+        command.args(["-keyint_min", &min_interval.to_string()]);
+    }
+    if keyframes.no_scene_cut {
+        match codec {
+            VideoCodec::H264 => command.args(["-x264-params", "scenecut=0"]),
+            VideoCodec::H265 => command.args(["-x265-params", "scenecut=0"]),
+            VideoCodec::HevcNvenc => command.args(["-no-scenecut", "1"]),
+            VideoCodec::SvtAv1 => command.args(["-svtav1-params", "scd=0"]),
+            VideoCodec::AomAv1 | VideoCodec::ProRes | VideoCodec::Dnxhr => command,
+        };
+    }
+}
+
+pub(crate) fn apply_best_quality_audio_encoding(command: &mut FfmpegCommand, kbps: u32) {
+    command.args([
+        "-c:a", "aac",
+        "-b:a", &format!("{}k", kbps),
+        "-ar", "48000",
+        "-ac", "2", // Stereo
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &mut FfmpegCommand) -> Vec<String> {
+        command
+            .as_inner()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn position(args: &[String], arg: &str) -> usize {
+        args.iter().position(|a| a == arg).unwrap()
+    }
+
+    #[test]
+    fn input_seek_comes_before_the_source() {
+        let seek = Seek::Input {
+            start: "00:00:10",
+            end: "00:00:20",
+            length: 5.0,
+        };
+        let mut command = FfmpegCommand::new_with_path("ffmpeg");
+        seek.add_source(&mut command, Path::new("source.mp4"));
+        command.input("music.mp3");
+        seek.add_output_trim(&mut command);
+        let args = args(&mut command);
+
+        assert!(position(&args, "-ss") < position(&args, "source.mp4"));
+        assert!(position(&args, "-to") < position(&args, "source.mp4"));
+        assert_eq!(args[position(&args, "source.mp4") - 1], "-i");
+        // The output is only capped, after every input
+        assert!(position(&args, "-t") > position(&args, "music.mp3"));
+        assert_eq!(args[position(&args, "-t") + 1], "5");
+        assert_eq!(args.iter().filter(|a| *a == "-ss").count(), 1);
+    }
+
+    #[test]
+    fn output_seek_follows_every_input() {
+        let seek = Seek::Output {
+            start: "00:00:10",
+            end: "00:00:20",
+        };
+        let mut command = FfmpegCommand::new_with_path("ffmpeg");
+        seek.add_source(&mut command, Path::new("source.mp4"));
+        command.input("music.mp3");
+        seek.add_output_trim(&mut command);
+        let args = args(&mut command);
+
+        assert!(position(&args, "-ss") > position(&args, "music.mp3"));
+        assert!(position(&args, "-to") > position(&args, "music.mp3"));
+        assert!(!args.iter().any(|a| a == "-t"));
+    }
+}
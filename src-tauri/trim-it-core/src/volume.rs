@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+// FAT32 can't hold a file of 4 GiB or more
+const FAT32_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputTargetReport {
+    pub path: String,
+    pub exists: bool,
+    pub writable: bool,
+    pub free_bytes: Option<u64>,
+    pub filesystem: Option<String>,
+    pub max_file_size: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VolumeInfo {
+    pub filesystem: Option<String>,
+    pub max_file_size: Option<u64>,
+}
+
+// Filesystem of the volume holding `path`, found by the longest mount point
+// that prefixes it.
+pub fn volume_info(path: &Path) -> VolumeInfo {
+    let path = nearest_existing(path)
+        .canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf());
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let filesystem = disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.file_system().to_string_lossy().to_string());
+
+    let max_file_size = filesystem.as_deref().and_then(max_file_size_for);
+    VolumeInfo {
+        filesystem,
+        max_file_size,
+    }
+}
+
+fn max_file_size_for(filesystem: &str) -> Option<u64> {
+    match filesystem.to_ascii_lowercase().as_str() {
+        "vfat" | "fat" | "fat32" | "msdos" | "fat16" => Some(FAT32_MAX_FILE_SIZE),
+        _ => None,
+    }
+}
+
+fn nearest_existing(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+// Fails when the volume holding `dir` has less than `needed` bytes free.
+// Unknown free space passes.
+pub fn ensure_free_space(dir: &Path, needed: u64) -> Result<(), String> {
+    match fs2::available_space(nearest_existing(dir)) {
+        Ok(free) if free < needed => Err(format!(
+            "Not enough free space in {}: the download needs about {} MB but only {} MB is available. Choose a temp folder on a larger drive in settings.",
+            dir.display(),
+            needed / 1_000_000,
+            free / 1_000_000
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_output_target(path: &Path, expected_bytes: Option<u64>) -> OutputTargetReport {
+    let exists = path.is_dir();
+    let mut warnings = Vec::new();
+
+    // Only a real write proves permissions (ACLs, read-only mounts, sandboxes)
+    let writable = exists && tempfile::NamedTempFile::new_in(path).is_ok();
+    if !exists {
+        warnings.push("The output folder does not exist yet and will be created.".to_string());
+    } else if !writable {
+        warnings.push("The output folder is not writable.".to_string());
+    }
+
+    let free_bytes = fs2::available_space(nearest_existing(path)).ok();
+    let volume = volume_info(path);
+
+    if let Some(expected) = expected_bytes {
+        if let Some(free) = free_bytes.filter(|&free| free < expected) {
+            warnings.push(format!(
+                "Not enough free space: the output needs about {} MB but only {} MB is available.",
+                expected / 1_000_000,
+                free / 1_000_000
+            ));
+        }
+        if let Some(limit) = volume.max_file_size.filter(|&limit| limit < expected) {
+            warnings.push(format!(
+                "The {} filesystem can't store files larger than {} MB.",
+                volume.filesystem.as_deref().unwrap_or("target"),
+                limit / 1_000_000
+            ));
+        }
+    } else if let Some(limit) = volume.max_file_size {
+        warnings.push(format!(
+            "The {} filesystem limits files to {} MB; long exports may not fit.",
+            volume.filesystem.as_deref().unwrap_or("target"),
+            limit / 1_000_000
+        ));
+    }
+
+    OutputTargetReport {
+        path: path.display().to_string(),
+        exists,
+        writable,
+        free_bytes,
+        filesystem: volume.filesystem,
+        max_file_size: volume.max_file_size,
+        warnings,
+    }
+}
@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::process::Command;
+
+use crate::{cancel, download, joblog, privacy};
+use crate::settings::Settings;
+use crate::timecode::time_to_seconds;
+
+const RELEASES_API_URL: &str = "https://api.github.com/repos/yt-dlp/yt-dlp/releases/latest";
+const RELEASE_DOWNLOAD_URL: &str = "https://github.com/yt-dlp/yt-dlp/releases/download";
+
+// Directory holding the managed yt-dlp binary, set once at startup.
+static MANAGED_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+// A preinstalled yt-dlp chosen in settings, e.g. by IT on managed machines
+static CONFIGURED_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YtDlpStatus {
+    pub path: String,
+    pub version: String,
+    pub managed: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct YtDlpUpdate {
+    pub previous_version: Option<String>,
+    pub current_version: String,
+    pub updated: bool,
+}
+
+pub fn set_managed_dir(dir: PathBuf) {
+    *MANAGED_DIR.write().unwrap_or_else(|e| e.into_inner()) = Some(dir);
+}
+
+pub fn set_configured_path(path: Option<PathBuf>) {
+    *CONFIGURED_PATH.write().unwrap_or_else(|e| e.into_inner()) = path;
+}
+
+pub fn configured_ytdlp_path() -> Option<PathBuf> {
+    CONFIGURED_PATH
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .filter(|path| path.exists())
+}
+
+fn managed_binary_path() -> Option<PathBuf> {
+    MANAGED_DIR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|dir| dir.join(format!("yt-dlp{}", std::env::consts::EXE_SUFFIX)))
+}
+
+pub fn managed_ytdlp_path() -> Option<PathBuf> {
+    managed_binary_path().filter(|path| path.exists())
+}
+
+// A configured binary wins, then the managed one, which beats PATH so a
+// stale distro package can't break YouTube trims once we've installed our own.
+pub fn ytdlp_path() -> PathBuf {
+    configured_ytdlp_path()
+        .or_else(managed_ytdlp_path)
+        .unwrap_or_else(|| PathBuf::from("yt-dlp"))
+}
+
+// Release asset name for this platform, as published on GitHub.
+fn release_asset_name() -> Result<&'static str, String> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("windows", "x86_64") => Ok("yt-dlp.exe"),
+        ("windows", "x86") => Ok("yt-dlp_x86.exe"),
+        ("macos", _) => Ok("yt-dlp_macos"),
+        ("linux", "x86_64") => Ok("yt-dlp_linux"),
+        ("linux", "aarch64") => Ok("yt-dlp_linux_aarch64"),
+        (os, arch) => Err(format!("No managed yt-dlp build is available for {} {}", os, arch)),
+    }
+}
+
+pub async fn installed_version(path: &Path) -> Option<String> {
+    let output = Command::new(path).arg("--version").output().await.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub async fn latest_release_tag(client: &reqwest::Client) -> Result<String, String> {
+    let release: serde_json::Value = client
+        .get(RELEASES_API_URL)
+        .header(reqwest::header::USER_AGENT, "trim-it")
+        .send()
+        .await
+        .map_err(|e| format!("Failed to check for yt-dlp updates: {}", e))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to check for yt-dlp updates: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Unexpected response from GitHub: {}", e))?;
+
+    release
+        .get("tag_name")
+        .and_then(|tag| tag.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| "GitHub release response is missing a tag name".to_string())
+}
+
+// Downloads the given release into the managed dir, verifying it against the
+// release's SHA2-256SUMS before it replaces the existing binary.
+pub async fn install_release(
+    client: &reqwest::Client,
+    tag: &str,
+    on_progress: impl Fn(u64, Option<u64>),
+) -> Result<PathBuf, String> {
+    let binary_path = managed_binary_path().ok_or("Managed yt-dlp directory is not configured")?;
+    let asset = release_asset_name()?;
+    if let Some(parent) = binary_path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("Failed to create yt-dlp directory: {}", e))?;
+    }
+
+    let checksums = client
+        .get(format!("{}/{}/SHA2-256SUMS", RELEASE_DOWNLOAD_URL, tag))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch yt-dlp checksums: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to fetch yt-dlp checksums: {}", e))?;
+    let expected = checksums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, name)| name.trim().trim_start_matches('*') == asset)
+        .map(|(hash, _)| hash.to_ascii_lowercase())
+        .ok_or_else(|| format!("No checksum published for {}", asset))?;
+
+    let staging_path = binary_path.with_extension("download");
+    let url = format!("{}/{}/{}", RELEASE_DOWNLOAD_URL, tag, asset);
+    download::download_with_progress(client, &url, &staging_path, &on_progress).await?;
+
+    let bytes = tokio::fs::read(&staging_path)
+        .await
+        .map_err(|e| format!("Failed to read downloaded yt-dlp: {}", e))?;
+    let actual: String = Sha256::digest(&bytes).iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        let _ = tokio::fs::remove_file(&staging_path).await;
+        return Err("Downloaded yt-dlp failed checksum verification".to_string());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&staging_path, std::fs::Permissions::from_mode(0o755))
+            .await
+            .map_err(|e| format!("Failed to mark yt-dlp as executable: {}", e))?;
+    }
+
+    tokio::fs::rename(&staging_path, &binary_path)
+        .await
+        .map_err(|e| format!("Failed to install yt-dlp: {}", e))?;
+    Ok(binary_path)
+}
+
+// yt-dlp invocation with the network settings every call needs applied
+pub fn command(settings: &Settings) -> Result<Command, String> {
+    let mut command = Command::new(ytdlp_path());
+    // A cancelled job drops the run, which takes yt-dlp down with it
+    command.kill_on_drop(true);
+    if let Some(proxy) = &settings.proxy {
+        command.arg("--proxy").arg(proxy.url_with_auth()?);
+    }
+    if let Some(bytes_per_sec) = settings.max_download_bytes_per_sec() {
+        command.arg("--limit-rate").arg(bytes_per_sec.to_string());
+    }
+    Ok(command)
+}
+
+pub const NOT_FOUND_ERROR: &str =
+    "yt-dlp command not found. Please install yt-dlp and ensure it is in your system's PATH.";
+
+pub fn spawn_error(e: std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        NOT_FOUND_ERROR.to_string()
+    } else {
+        format!("Failed to execute yt-dlp: {}", e)
+    }
+}
+
+const DRM_ERROR: &str = "This content is DRM-protected and cannot be processed.";
+
+// Checks yt-dlp's metadata for DRM (Widevine, FairPlay or PlayReady
+// manifests, encrypted HLS) before anything is downloaded, since the download
+// itself would only fail with a generic error. Any other problem is left for
+// the download to report.
+pub async fn ensure_not_drm_protected(url: &str, settings: &Settings) -> Result<(), String> {
+    let mut check = command(settings)?;
+    check
+        .args(["--dump-single-json", "--no-playlist", "--no-warnings", "--allow-unplayable-formats"])
+        .arg(url);
+    let output = match cancel::until_cancelled(check.output()).await? {
+        Ok(output) => output,
+        Err(e) => {
+            log::warn!("Skipping DRM check: {}", spawn_error(e));
+            return Ok(());
+        }
+    };
+    if !output.status.success() {
+        if String::from_utf8_lossy(&output.stderr).contains("DRM") {
+            return Err(DRM_ERROR.to_string());
+        }
+        return Ok(());
+    }
+
+    let info: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+        Ok(info) => info,
+        Err(e) => {
+            log::warn!("Skipping DRM check, unexpected yt-dlp output: {}", e);
+            return Ok(());
+        }
+    };
+    let has_drm = |value: &serde_json::Value| value.get("has_drm").and_then(|d| d.as_bool()).unwrap_or(false);
+    // Sites often offer a clear preview next to protected formats, so it only
+    // counts when nothing playable is left
+    let protected = match info.get("formats").and_then(|f| f.as_array()) {
+        Some(formats) if !formats.is_empty() => formats.iter().all(has_drm),
+        _ => has_drm(&info),
+    };
+    if protected {
+        Err(DRM_ERROR.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// Copies yt-dlp's warnings and errors into the running job's log, if any
+fn record_stderr(stderr: &[u8]) {
+    for line in String::from_utf8_lossy(stderr).lines() {
+        // The destination is named after the video's title, spaces and all
+        let line = match line.split_once("Destination: ") {
+            Some((head, path)) => format!("{}Destination: {}", head, privacy::redact(path)),
+            None => line.to_string(),
+        };
+        joblog::record(&format!("yt-dlp: {}", line));
+    }
+}
+
+// The best quality, as MP4 when there's a choice
+pub const DOWNLOAD_FORMAT: &str = "bestvideo[ext=mp4]+bestaudio[ext=m4a]/bestvideo+bestaudio/best";
+
+// Approximate bytes download_youtube_video_segment would fetch for the
+// section, from the sizes published for the formats it picks. None when
+// the site doesn't publish them.
+pub async fn estimate_section_size(
+    url: &str,
+    start_seconds: f64,
+    end_seconds: f64,
+    settings: &Settings,
+) -> Result<Option<u64>, String> {
+    let mut dump = command(settings)?;
+    dump.args(["--dump-single-json", "--no-playlist", "--no-warnings", "-f", DOWNLOAD_FORMAT])
+        .arg(url);
+    let output = cancel::until_cancelled(dump.output()).await?.map_err(spawn_error)?;
+    record_stderr(&output.stderr);
+    if !output.status.success() {
+        return Err("yt-dlp couldn't read the video's formats. The URL might be invalid, private, or require a login.".to_string());
+    }
+    let info: serde_json::Value =
+        serde_json::from_slice(&output.stdout).map_err(|e| format!("Unexpected yt-dlp output: {}", e))?;
+    let format_size = |format: &serde_json::Value| {
+        format
+            .get("filesize")
+            .or_else(|| format.get("filesize_approx"))
+            .and_then(|size| size.as_f64())
+    };
+    // Merged downloads list the video and audio formats separately
+    let full_size = match info.get("requested_formats").and_then(|f| f.as_array()) {
+        Some(formats) => formats.iter().map(format_size).sum::<Option<f64>>(),
+        None => format_size(&info),
+    };
+    let share = info
+        .get("duration")
+        .and_then(|d| d.as_f64())
+        .filter(|duration| *duration > 0.0)
+        .map_or(1.0, |duration| ((end_seconds - start_seconds) / duration).clamp(0.0, 1.0));
+    Ok(full_size.map(|size| (size * share).round() as u64))
+}
+
+// Optimized function to download only the required segment from YouTube
+pub async fn download_youtube_video_segment(
+    url: &str, 
+    output_dir: &Path, 
+    start_time: &str, 
+    end_time: &str,
+    settings: &Settings,
+) -> Result<PathBuf, String> {
+    let output_template = output_dir.join("video.%(ext)s");
+
+    // Convert the timecodes to seconds for yt-dlp, keeping the milliseconds
+    let start_seconds = time_to_seconds(start_time)?;
+    let end_seconds = time_to_seconds(end_time)?;
+    
+    // Create download sections parameter
+    let download_sections = format!("*{:.3}-{:.3}", start_seconds, end_seconds);
+
+    let mut ytdlp = command(settings)?;
+
+    ytdlp
+        // Get absolute best quality
+        .arg("-f")
+        .arg(DOWNLOAD_FORMAT)
+        .arg("--merge-output-format")
+        .arg("mp4")
+        .arg("--download-sections")
+        .arg(&download_sections)
+        .arg("--force-keyframes-at-cuts")
+        // Quality settings
+        .arg("--audio-quality")
+        .arg("0") // Best audio quality
+        .arg("--remux-video")
+        .arg("mp4")
+        // Speed optimizations
+        .arg("--concurrent-fragments")
+        .arg("4")
+        .arg("-o")
+        .arg(&output_template)
+        .arg(url);
+    let output = cancel::until_cancelled(ytdlp.output()).await?.map_err(spawn_error)?;
+    record_stderr(&output.stderr);
+
+    if !output.status.success() {
+        return Err("yt-dlp failed to download the video segment. The URL might be invalid, private, or require a login.".to_string());
+    }
+
+    // Find the downloaded file (it should be video.mp4)
+    let expected_path = output_dir.join("video.mp4");
+    if expected_path.exists() {
+        Ok(expected_path)
+    } else {
+        Err("yt-dlp ran, but the expected output file was not found.".to_string())
+    }
+}
+
+// Fetches subtitles in `language` (e.g. "en"), falling back to YouTube's
+// auto-generated captions. Times are on the full video's timeline.
+pub async fn download_subtitles(
+    url: &str,
+    output_dir: &Path,
+    language: &str,
+    settings: &Settings,
+) -> Result<PathBuf, String> {
+    let output_template = output_dir.join("subtitles.%(ext)s");
+
+    let mut fetch = command(settings)?;
+    fetch
+        .arg("--skip-download")
+        .arg("--write-subs")
+        .arg("--write-auto-subs")
+        .arg("--sub-langs")
+        .arg(language)
+        .arg("--sub-format")
+        .arg("vtt/srt/ass/best")
+        .arg("-o")
+        .arg(&output_template)
+        .arg(url);
+    let output = cancel::until_cancelled(fetch.output()).await?.map_err(spawn_error)?;
+    record_stderr(&output.stderr);
+
+    if !output.status.success() {
+        return Err("yt-dlp failed to download subtitles.".to_string());
+    }
+
+    // Saved as subtitles.<language>.<format>
+    std::fs::read_dir(output_dir)
+        .map_err(|e| format!("Failed to read subtitle download directory: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            name.starts_with("subtitles.")
+                && matches!(path.extension().and_then(|e| e.to_str()), Some("vtt" | "srt" | "ass"))
+        })
+        .ok_or_else(|| format!("No \"{}\" subtitles or captions are available for this video.", language))
+}